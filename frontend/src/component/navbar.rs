@@ -18,10 +18,12 @@ pub fn view(model: &Model) -> Node<Msg> {
                 create_link("Register", url().register()),
                 create_link("Login", url().login()),
                 create_link("Create", url().create()),
+                create_link("Puzzles", url().puzzle()),
                 IF!(model.user.is_some() => match &model.user {
                     Some(name) => create_link(name, url().user(name)),
                     _ => unreachable!()
-                })
+                }),
+                IF!(model.user.is_some() => h2![a!["Logout", ev(Ev::Click, |_| Msg::Logout)]]),
             ]
         ]
     ]
@@ -1,17 +1,15 @@
 use seed::{self, prelude::*, *};
-use shared::model::{http::*, UserCredentials};
+use shared::model::{Session, UserCredentials};
 
 use crate::Msg::Login;
-use shared::ObjectId;
 
-use crate::request::user_cred::*;
+use crate::request::{user_cred::*, ApiResult};
 
-pub fn init(text: String, (end_point, success_code): (String, u32)) -> Model {
+pub fn init(text: String, end_point: String) -> Model {
     Model {
         form: UserCredentials::default(),
         status_text: None,
         end_point,
-        success_code,
         text,
     }
 }
@@ -19,7 +17,6 @@ pub fn init(text: String, (end_point, success_code): (String, u32)) -> Model {
 pub struct Model {
     form: UserCredentials,
     status_text: Option<Status>,
-    success_code: u32,
     end_point: String,
     text: String,
 }
@@ -33,7 +30,7 @@ pub enum Msg {
     Submit,
     NameChanged(String),
     PasswordChanged(String),
-    Fetched(fetch::Result<String>),
+    Fetched(ApiResult<Session>),
 }
 
 pub fn update(
@@ -57,31 +54,19 @@ pub fn update(
             model.form.password = password;
         }
 
-        Msg::Fetched(Ok(text)) => match serde_json::from_str::<ResponseBody>(&text) {
-            Ok(resp) => match resp.status {
-                code if code == model.success_code => {
-                    model.status_text = Some(Status::Success("Success".into()));
-                    let id: ObjectId = resp.get_body();
-                    let name = &model.form.name;
-                    LocalStorage::insert("id", &id).expect("inserting id in LocalStorage");
-                    LocalStorage::insert("name", &name).expect("inserting name in LocalStorage");
-                    orders.send_msg(Login {
-                        name: model.form.name.clone(),
-                    });
-                }
-
-                e => {
-                    let err: String = resp.get_body();
-                    model.status_text = Some(Status::Error(format!("Error ({e}): {err}")));
-                }
-            },
-            Err(e) => {
-                model.status_text = Some(Status::Error(format!("deserialize error, {e:?}")));
-            }
-        },
+        Msg::Fetched(Ok(session)) => {
+            model.status_text = Some(Status::Success("Success".into()));
+            let name = &model.form.name;
+            LocalStorage::insert("token", &session.token).expect("inserting token in LocalStorage");
+            LocalStorage::insert("id", &session.user).expect("inserting id in LocalStorage");
+            LocalStorage::insert("name", &name).expect("inserting name in LocalStorage");
+            orders.send_msg(Login {
+                name: model.form.name.clone(),
+            });
+        }
 
-        Msg::Fetched(Err(text)) => {
-            model.status_text = Some(Status::Error(format!("{text:?}")));
+        Msg::Fetched(Err(e)) => {
+            model.status_text = Some(Status::Error(e.to_string()));
         }
     }
 }
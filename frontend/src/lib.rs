@@ -29,6 +29,8 @@ pub enum Msg {
     Home(page::home::Msg),
     Game(page::game::Msg),
     Login { name: String },
+    Logout,
+    LoggedOut,
 
     LoginPage(page::login::Msg),
     RegisterPage(page::register::Msg),
@@ -56,6 +58,18 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
         ),
 
         Msg::Login { name } => model.user = Some(name),
+        Msg::Logout => {
+            orders.perform_cmd(async {
+                // Forgotten here even if the server could not be reached
+                let _ = request::user_cred::logout().await;
+                Msg::LoggedOut
+            });
+        }
+        Msg::LoggedOut => {
+            request::forget_session();
+            model.user = None;
+            Urls::new(&model.base_url).login().go_and_load();
+        }
         Msg::LoginPage(msg) => page::login::update(msg, model.page.as_login_mut().unwrap(), orders),
         Msg::RegisterPage(msg) => {
             page::register::update(msg, model.page.as_register_mut().unwrap(), orders)
@@ -88,6 +102,9 @@ impl<'a> Urls<'a> {
     pub fn create(self) -> Url {
         self.base_url().add_path_part("create")
     }
+    pub fn puzzle(self) -> Url {
+        self.base_url().add_path_part("puzzle")
+    }
 
     pub fn register(self) -> Url {
         self.base_url().add_path_part("register")
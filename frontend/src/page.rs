@@ -25,9 +25,13 @@ impl Page {
             None => Self::Home(home::init(&mut orders.proxy(crate::Msg::Home))),
             Some("login") => login::init(url).map_or(Self::NotFound, Self::Login),
             Some("register") => register::init(url).map_or(Self::NotFound, Self::Register),
-            Some("create") => Self::Create(create::init()),
+            Some("create") => Self::Create(create::init(&mut orders.proxy(crate::Msg::CreateGame))),
             Some("game") => game::init(url, &mut orders.proxy(crate::Msg::Game))
                 .map_or(Self::NotFound, |o| Self::Game(Box::new(o))),
+            Some("puzzle") => Self::Game(Box::new(game::init_puzzle(
+                url,
+                &mut orders.proxy(crate::Msg::Game),
+            ))),
             Some(_) => Self::NotFound,
         }
     }
@@ -1,21 +1,30 @@
 use seed::{prelude::*, *};
-use shared::model::http::*;
+use shared::model::{AcceptGame, Bot, BotChallenge};
+use shared::ObjectId;
 
-use crate::request::create::*;
+use crate::request::{create::*, ApiResult};
 use crate::Msg::CreateGame;
 
-pub fn init() -> Model {
+pub fn init(orders: &mut impl Orders<Msg>) -> Model {
+    orders
+        .skip()
+        .perform_cmd(async { Msg::FetchedBots(get_bots().await) });
     Model::default()
 }
 
 pub enum Msg {
     Submit,
-    Fetched(fetch::Result<String>),
+    Fetched(ApiResult<ObjectId>),
+
+    FetchedBots(ApiResult<Vec<Bot>>),
+    ChallengeBot(String),
+    ChallengedBot(ApiResult<AcceptGame>),
 }
 
 #[derive(Default)]
 pub struct Model {
     text: Option<Status>,
+    bots: Vec<Bot>,
 }
 
 enum Status {
@@ -25,37 +34,82 @@ enum Status {
 
 pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
     match msg {
-        Msg::Submit => match LocalStorage::get("id") {
-            Ok(id) => {
+        Msg::Submit => match LocalStorage::get::<_, String>("token") {
+            Ok(_) => {
                 orders
                     .skip()
-                    .perform_cmd(async move { Msg::Fetched(send_create_request(id).await) });
+                    .perform_cmd(async { Msg::Fetched(send_create_request().await) });
             }
             Err(_) => {
                 model.text = Some(Status::Error("User not logged in".into()));
             }
         },
-        Msg::Fetched(Ok(text)) => match serde_json::from_str::<ResponseBody>(&text) {
-            Ok(resp) => match resp.status {
-                201 => {
-                    model.text = Some(Status::Success("Game successfully created!".into()));
-                }
+        Msg::Fetched(Ok(_)) => {
+            model.text = Some(Status::Success("Game successfully created!".into()));
+        }
+        Msg::Fetched(Err(e)) => {
+            model.text = Some(Status::Error(e.to_string()));
+        }
+
+        Msg::FetchedBots(Ok(bots)) => {
+            model.bots = bots;
+        }
+        Msg::FetchedBots(Err(e)) => {
+            model.text = Some(Status::Error(e.to_string()));
+        }
 
-                e => {
-                    model.text = Some(Status::Error(format!("Error ({e})")));
-                }
-            },
-            Err(e) => {
-                model.text = Some(Status::Error(format!("deserialize error, {e:?}")));
+        Msg::ChallengeBot(bot) => match LocalStorage::get::<_, String>("token") {
+            Ok(_) => {
+                let challenge = BotChallenge { bot };
+                orders.skip().perform_cmd(async move {
+                    Msg::ChallengedBot(send_bot_challenge(challenge).await)
+                });
+            }
+            Err(_) => {
+                model.text = Some(Status::Error("User not logged in".into()));
             }
         },
 
-        Msg::Fetched(Err(text)) => {
-            model.text = Some(Status::Error(format!("{text:?}")));
+        // The bot accepts right away, so go straight to the game
+        Msg::ChallengedBot(Ok(accept)) => {
+            Url::new()
+                .add_path_part("game")
+                .add_path_part(accept.object_id.to_string())
+                .go_and_load();
+        }
+        Msg::ChallengedBot(Err(e)) => {
+            model.text = Some(Status::Error(e.to_string()));
         }
     }
 }
 
+fn bot_row<Ms: 'static>(bot: &Bot) -> Node<Ms> {
+    let name = bot.name.clone();
+    tr![
+        td![&bot.name],
+        td![format!("{:?}", bot.strength)],
+        td![button![
+            C!("button accept-button"),
+            "Play",
+            ev(Ev::Click, move |event| {
+                event.prevent_default();
+                CreateGame(Msg::ChallengeBot(name))
+            })
+        ]],
+    ]
+}
+
+fn bots<Ms: 'static>(model: &Model) -> Node<Ms> {
+    div![
+        h1!["Play a bot!"],
+        table![
+            C!("challenge-table"),
+            thead![tr![th!["Bot"], th!["Strength"], th!["Play"]]],
+            tbody![model.bots.iter().map(bot_row)]
+        ]
+    ]
+}
+
 pub fn view<Ms: 'static>(model: &Model) -> Node<Ms> {
     let body = || {
         form![
@@ -69,6 +123,7 @@ pub fn view<Ms: 'static>(model: &Model) -> Node<Ms> {
     div![
         C!("container center"),
         body(),
+        IF!(!model.bots.is_empty() => bots(model)),
         IF!(model.text.is_some() => match model.text {
             Some(Status::Success(ref s)) => h2! [C!("success"), s],
             Some(Status::Error(ref s)) => h2! [C!("error"), s],
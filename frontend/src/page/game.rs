@@ -1,5 +1,8 @@
+mod explorer;
 mod hex;
 mod menu;
+mod puzzle;
+mod review;
 mod selected_piece;
 mod selector;
 mod util;
@@ -10,15 +13,20 @@ use selected_piece::*;
 use selector::*;
 use util::*;
 
+pub use puzzle::init as init_puzzle;
+
 use crate::request::game::*;
-use crate::request::ws_url;
+use crate::request::puzzle::get_puzzle;
+use crate::request::{ws_url, ApiResult};
 use seed::{self, prelude::*, *};
-use shared::{model::*, r#move::*, ObjectId};
+use shared::{engine::WIN, model::*, r#move::*, uhp::Position, ObjectId};
 use web_sys::{Event, SvgGraphicsElement};
 
 pub struct ReplayBoard {
     pub board: Box<Board>,
     pub dir: Selector,
+    // Number of moves played on the replay board
+    pub moves: usize,
 }
 
 impl ReplayBoard {
@@ -39,7 +47,11 @@ impl ReplayBoard {
                     dir: None,
                 };
 
-                Self { board, dir }
+                Self {
+                    board,
+                    dir,
+                    moves: max + 1,
+                }
             })
     }
 
@@ -62,6 +74,9 @@ pub struct Model {
     pub label: Option<String>,
     pub socket: Option<WebSocket>,
     pub legal_moves_cache: Option<Vec<Square>>,
+    pub analysis: Option<Analysis>,
+    pub explorer: Vec<ExplorerMove>,
+    pub puzzle: Option<puzzle::Puzzle>,
 
     pub radius: usize,
 
@@ -72,6 +87,10 @@ pub struct Model {
     pub drag: (f32, f32),
 }
 
+const DEFAULT_RAD: usize = 0;
+const DEFAULT_SIZE: f32 = 0.5;
+const DEFAULT_MOD: f32 = 0.5;
+
 fn gen_size(n: f32) -> String {
     let mul = 0.7;
     let l = 5. * n * mul;
@@ -91,7 +110,7 @@ pub fn init(mut url: Url, orders: &mut impl Orders<Msg>) -> Option<Model> {
         Some(id) => match ObjectId::parse_str(id) {
             Ok(id) => {
                 orders.perform_cmd(async move { Msg::FetchGame(get_game(id).await) });
-                let size = gen_size(0.5);
+                let size = gen_size(DEFAULT_SIZE);
 
                 let socket = WebSocket::builder(ws_url(id), orders)
                     .on_message(Msg::MessageReceived)
@@ -99,9 +118,6 @@ pub fn init(mut url: Url, orders: &mut impl Orders<Msg>) -> Option<Model> {
                     .on_close(|_| Msg::Close)
                     .build_and_open()
                     .ok();
-                const DEFAULT_RAD: usize = 0;
-                const DEFAULT_SIZE: f32 = 0.5;
-                const DEFAULT_MOD: f32 = 0.5;
 
 
                 Some(Model {
@@ -116,6 +132,9 @@ pub fn init(mut url: Url, orders: &mut impl Orders<Msg>) -> Option<Model> {
                     label: None,
                     socket,
                     legal_moves_cache: None,
+                    analysis: None,
+                    explorer: Vec::new(),
+                    puzzle: None,
                     radius: DEFAULT_RAD,
 
                     _size: DEFAULT_SIZE,
@@ -132,9 +151,15 @@ pub fn init(mut url: Url, orders: &mut impl Orders<Msg>) -> Option<Model> {
 }
 
 pub enum Msg {
-    FetchGame(fetch::Result<String>),
-    SentMove(fetch::Result<String>),
-    CompleteGame(fetch::Result<String>),
+    FetchGame(ApiResult<GameResource>),
+    SentMove(ApiResult<()>),
+    CompleteGame(ApiResult<()>),
+    Analyzed(usize, ApiResult<Analysis>),
+    Explored(usize, ApiResult<Vec<ExplorerMove>>),
+    JumpTo(usize),
+    FetchPuzzle(ApiResult<PuzzleResource>),
+    PuzzleAnswered(ApiResult<PuzzleAnswer>),
+    NextPuzzle,
 
     Open,
     Close,
@@ -151,20 +176,6 @@ pub enum Msg {
 }
 
 pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
-    let parse_resp = |resp: fetch::Result<String>| -> Result<ResponseBody, String> {
-        resp.map_err(|e| format!("{e:?}"))
-            .and_then(|text| {
-                serde_json::from_str::<ResponseBody>(&text).map_err(|e| format!("{e:?}"))
-            })
-            .and_then(|resp| {
-                if resp.status == 200 {
-                    Ok(resp)
-                } else {
-                    Err("wrong statuscode".into())
-                }
-            })
-    };
-
     match msg {
         Msg::ButtonPress(event) => {
             let key = to_keyboard_event(&event);
@@ -175,6 +186,8 @@ pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
                 event.stop_propagation();
 
                 replay_move(model, key);
+                analyze_replay(model, orders);
+                explore_position(model, orders);
             } else if key.code().as_str() == "Space" {
                 event.prevent_default();
                 event.stop_propagation();
@@ -192,8 +205,8 @@ pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
             if let Ok(r#move) = msg.json::<Move>() {
                 // No need to play the move if we just played it.
                 if !just_my_move(model, &r#move) {
-                    let rad = sq_radius(r#move.sq);
-                    if let Some(old_sq) = r#move.old_sq {
+                    let rad = r#move.r#move.as_ref().map_or(0, |m| sq_radius(m.sq));
+                    if let Some(old_sq) = r#move.r#move.as_ref().and_then(|m| m.old_sq) {
                         remove_top_piece(model, old_sq);
                     }
                     play_move(model, r#move);
@@ -206,36 +219,66 @@ pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
                     }
                 }
                 clear_replay(model);
+                explore_position(model, orders);
             }
         }
 
-        Msg::SentMove(resp) => {
-            if let Err(e) = parse_resp(resp) {
-                model.label = Some(format!("{e:?}"));
-            } else if game_complete(model) {
+        Msg::SentMove(Ok(())) => {
+            if game_complete(model) {
                 let id = model.game.as_ref().unwrap()._id;
                 orders.perform_cmd(async move { Msg::CompleteGame(complete_game(id).await) });
             }
         }
-        Msg::CompleteGame(resp) => {
-            if let Err(e) = parse_resp(resp) {
-                model.label = Some(format!("{e:?}"));
+        // The game moved on before the move got there, from another tab or a
+        // double click, so the board on screen is out of date
+        Msg::SentMove(Err(e)) if e.code == ErrorCode::Conflict => {
+            model.label = Some("The game has changed, reloading it".into());
+            let id = model.game.as_ref().unwrap()._id;
+            orders.perform_cmd(async move { Msg::FetchGame(get_game(id).await) });
+        }
+        Msg::SentMove(Err(e)) | Msg::CompleteGame(Err(e)) => {
+            model.label = Some(e.to_string());
+        }
+        Msg::CompleteGame(Ok(())) => {}
+        Msg::JumpTo(index) => {
+            jump_to_move(model, index);
+            analyze_replay(model, orders);
+            explore_position(model, orders);
+        }
+        Msg::Analyzed(moves, resp) => match resp {
+            // Ignore the answer if another move is viewed by now
+            Ok(analysis) if matches!(&model.replay_board, Some(replay) if replay.moves == moves) => {
+                model.analysis = Some(analysis);
             }
+            Ok(_) => {}
+            Err(e) => model.label = Some(e.to_string()),
+        },
+        Msg::Explored(moves, resp) => match resp {
+            Ok(explorer) if viewed_moves(model) == Some(moves) => {
+                model.explorer = explorer;
+            }
+            Ok(_) => {}
+            Err(e) => model.label = Some(e.to_string()),
+        },
+
+        Msg::FetchPuzzle(resp) => match resp {
+            Ok(resource) => puzzle::load(model, resource),
+            Err(e) => model.label = Some(e.to_string()),
+        },
+        Msg::PuzzleAnswered(resp) => match resp {
+            Ok(answer) => puzzle::answered(model, answer),
+            Err(e) => model.label = Some(e.to_string()),
+        },
+        Msg::NextPuzzle => {
+            orders.perform_cmd(async { Msg::FetchPuzzle(get_puzzle(None).await) });
         }
 
-        Msg::FetchGame(res) => match parse_resp(res) {
-            Ok(resp) => {
-                let game: GameResource = resp.get_body();
+        Msg::FetchGame(res) => match res {
+            Ok(game) => {
                 model.color = get_color(&game);
                 model.game = Some(game);
                 if let Some(color) = model.color {
-                    use BoardPiece::*;
-                    let items = [Ant, Beetle, Grasshopper, Spider, Queen]
-                        .into_iter()
-                        .map(|r#type| Piece { color, r#type });
-
-                    let board = get_board(model).unwrap();
-                    model.menu = Some(Menu::new(items, board));
+                    model.menu = Some(Menu::for_color(color, get_board(model).unwrap()));
                 }
 
                 let rad = get_radius(model);
@@ -244,9 +287,10 @@ pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
                     model.gridv3 = create_gridv3(rad);
                 }
                 grid_from_board(model);
+                explore_position(model, orders);
             }
             Err(e) => {
-                model.label = Some(format!("expected 200 got {e}"));
+                model.label = Some(e.to_string());
             }
         },
 
@@ -300,7 +344,7 @@ pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
                             grid_from_board(model);
                         }
 
-                        orders.perform_cmd(async move { Msg::SentMove(send_move(r#move).await) });
+                        submit_move(model, r#move, orders);
                     }
                 } else {
                     place_piece_back(model, selected_piece);
@@ -358,7 +402,7 @@ pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
 
                 if let Some(r#move) = get_move(model, piece, sq, None) {
                     play_move(model, r#move.clone());
-                    orders.perform_cmd(async move { Msg::SentMove(send_move(r#move).await) });
+                    submit_move(model, r#move, orders);
                 }
                 let rad = get_radius(model);
                 if rad > model.radius {
@@ -408,8 +452,74 @@ pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
     }
 }
 
+fn analyze_replay(model: &mut Model, orders: &mut impl Orders<Msg>) {
+    if let (Some(game), Some(replay)) = (&model.game, &model.replay_board) {
+        let moves = replay.moves;
+        let req = PositionRequest::Game {
+            game: game._id,
+            moves,
+        };
+        model.analysis = None;
+        orders.perform_cmd(async move { Msg::Analyzed(moves, analyze(req).await) });
+    }
+}
+
+fn submit_move(model: &mut Model, r#move: Move, orders: &mut impl Orders<Msg>) {
+    if model.puzzle.is_some() {
+        // The puzzle moves come from the board, they are never passes
+        if let Some(r#move) = r#move.r#move {
+            puzzle::submit(model, r#move, orders);
+        }
+    } else {
+        orders.perform_cmd(async move { Msg::SentMove(send_move(r#move).await) });
+    }
+}
+
+// Number of moves played in the position on screen
+fn viewed_moves(model: &Model) -> Option<usize> {
+    match &model.replay_board {
+        Some(replay) => Some(replay.moves),
+        None => model.game.as_ref().map(|game| game.move_list.len()),
+    }
+}
+
+fn explore_position(model: &mut Model, orders: &mut impl Orders<Msg>) {
+    if let (Some(game), Some(moves)) = (&model.game, viewed_moves(model)) {
+        let req = PositionRequest::Game {
+            game: game._id,
+            moves,
+        };
+        model.explorer.clear();
+        orders.perform_cmd(async move { Msg::Explored(moves, explore(req).await) });
+    }
+}
+
+fn analysis_text(model: &Model) -> Option<String> {
+    let analysis = model.analysis.as_ref()?;
+    let replay = model.replay_board.as_ref()?;
+    let game = model.game.as_ref()?;
+
+    let score = match analysis.score {
+        s if s.abs() > WIN / 2 => {
+            let color = if s > 0 { "White" } else { "Black" };
+            format!("{color} wins in {} moves", (WIN - s.abs() + 1) / 2)
+        }
+        s => format!("{:+.2}", s as f32 / 100.),
+    };
+
+    let pos = Position::from_moves(&game.move_list[..replay.moves]);
+    Some(match &analysis.best_move {
+        Some(r#move) => format!(
+            "Evaluation: {score}, best move: {}",
+            pos.move_string(Some(r#move))
+        ),
+        None => format!("Evaluation: {score}"),
+    })
+}
+
 pub fn view(model: &Model) -> Node<crate::Msg> {
     div![
+        puzzle::view(model),
         IF!(matches!(my_turn(model), Some(true)) => {
             h1![
                 style! {
@@ -438,6 +548,17 @@ pub fn view(model: &Model) -> Node<crate::Msg> {
                 }),
                 div![
                     grid(model),
+                    analysis_text(model).map(|s| h2![s]),
+                    explorer::view(model),
+                    review::view(model),
+                    model
+                        .game
+                        .as_ref()
+                        .filter(|_| model.puzzle.is_none())
+                        .map(|game| a![
+                            attrs! { At::Href => export_url(game._id), At::Download => "" },
+                            "Download game",
+                        ]),
                     IF!(model.label.is_some() => match model.label {
                         Some(ref s) => h2! [C!("error"), s],
                         _ => unreachable!()
@@ -0,0 +1,43 @@
+use super::*;
+use seed::{prelude::*, *};
+
+fn percent(n: u32, total: u32) -> String {
+    format!("{:.0}%", 100. * n as f32 / total as f32)
+}
+
+// The moves played from the viewed position in completed games
+pub fn view(model: &Model) -> Option<Node<crate::Msg>> {
+    let game = model.game.as_ref()?;
+    let moves = viewed_moves(model)?;
+    if model.explorer.is_empty() {
+        return None;
+    }
+
+    let pos = Position::from_moves(&game.move_list[..moves]);
+    let rows = model.explorer.iter().map(|m| {
+        let games = m.games();
+        tr![
+            td![pos.move_string(Some(&m.r#move))],
+            td![games.to_string()],
+            td![percent(m.white, games)],
+            td![percent(m.draws, games)],
+            td![percent(m.black, games)],
+        ]
+    });
+
+    Some(div![
+        C!("explorer"),
+        h2!["Opening explorer"],
+        table![
+            C!("challenge-table"),
+            thead![tr![
+                th!["Move"],
+                th!["Games"],
+                th!["White"],
+                th!["Draw"],
+                th!["Black"],
+            ]],
+            tbody![rows],
+        ],
+    ])
+}
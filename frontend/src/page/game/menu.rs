@@ -32,6 +32,16 @@ impl Menu {
 
         menu
     }
+
+    // Every piece of `color` not on the board yet
+    pub fn for_color(color: Color, board: &Board) -> Self {
+        use BoardPiece::*;
+        let items = [Ant, Beetle, Grasshopper, Spider, Queen]
+            .into_iter()
+            .map(|r#type| Piece { color, r#type });
+        Self::new(items, board)
+    }
+
     pub fn to_node(&self) -> Node<crate::Msg> {
         div![
             style! {
@@ -0,0 +1,162 @@
+use super::*;
+use crate::request::puzzle::*;
+use seed::{prelude::*, *};
+use shared::engine::{play_turn, side_to_move};
+
+#[derive(PartialEq, Eq)]
+pub enum Status {
+    Solving,
+    Wrong,
+    Solved,
+}
+
+pub struct Puzzle {
+    pub resource: PuzzleResource,
+    // Moves played from the puzzle position, None is a pass
+    pub played: Vec<Option<InternalMove>>,
+    // The move waiting to be checked
+    pub pending: Option<InternalMove>,
+    pub status: Status,
+}
+
+pub fn init(mut url: Url, orders: &mut impl Orders<Msg>) -> Model {
+    let id = url
+        .next_path_part()
+        .and_then(|id| ObjectId::parse_str(id).ok());
+    orders.perform_cmd(async move { Msg::FetchPuzzle(get_puzzle(id).await) });
+
+    Model {
+        gridv3: create_gridv3(DEFAULT_RAD),
+        size: gen_size(DEFAULT_SIZE),
+        radius: DEFAULT_RAD,
+        _size: DEFAULT_SIZE,
+        _modifier: DEFAULT_MOD,
+        ..Default::default()
+    }
+}
+
+pub fn load(model: &mut Model, resource: PuzzleResource) {
+    let mut board = Board::default();
+    for r#move in &resource.moves {
+        board = play_turn(&board, r#move);
+    }
+
+    model.color = Some(side_to_move(&board));
+    model.puzzle = Some(Puzzle {
+        resource,
+        played: Vec::new(),
+        pending: None,
+        status: Status::Solving,
+    });
+    reset_board(model);
+}
+
+// Sets the board to the puzzle position and the moves played from it
+fn reset_board(model: &mut Model) {
+    let puzzle = model.puzzle.as_ref().unwrap();
+
+    let mut board = Board::default();
+    let mut move_list = Vec::new();
+    for r#move in puzzle.resource.moves.iter().chain(&puzzle.played) {
+        board = play_turn(&board, r#move);
+        move_list.push(r#move.clone());
+    }
+
+    model.game = Some(GameResource {
+        _id: puzzle.resource._id,
+        players: Default::default(),
+        board,
+        move_list,
+        review: None,
+        result: None,
+        record: None,
+    });
+    if let Some(color) = model.color {
+        model.menu = Some(Menu::for_color(color, get_board(model).unwrap()));
+    }
+
+    let rad = get_radius(model);
+    if rad > model.radius {
+        model.radius = rad;
+        model.gridv3 = create_gridv3(rad);
+    }
+    grid_from_board(model);
+    clear_yellow(&mut model.gridv3);
+}
+
+pub fn submit(model: &mut Model, r#move: InternalMove, orders: &mut impl Orders<Msg>) {
+    if let Some(puzzle) = model.puzzle.as_mut() {
+        let attempt = PuzzleAttempt {
+            puzzle: puzzle.resource._id,
+            played: puzzle.played.clone(),
+            r#move,
+        };
+        puzzle.pending = Some(attempt.r#move.clone());
+        orders.perform_cmd(async move { Msg::PuzzleAnswered(send_attempt(attempt).await) });
+    }
+}
+
+pub fn answered(model: &mut Model, answer: PuzzleAnswer) {
+    let puzzle = match model.puzzle.as_mut() {
+        Some(puzzle) => puzzle,
+        None => return,
+    };
+    let pending = puzzle.pending.take();
+
+    match answer {
+        PuzzleAnswer::Wrong => {
+            puzzle.status = Status::Wrong;
+            reset_board(model);
+        }
+        PuzzleAnswer::Solved => {
+            puzzle.played.push(pending);
+            puzzle.status = Status::Solved;
+        }
+        PuzzleAnswer::Reply(reply) => {
+            puzzle.played.push(pending);
+            puzzle.played.push(reply.clone());
+            puzzle.status = Status::Solving;
+
+            let id = puzzle.resource._id;
+            let move_number = model.game.as_ref().map_or(0, |game| game.move_list.len());
+            if let Some(old_sq) = reply.as_ref().and_then(|reply| reply.old_sq) {
+                remove_top_piece(model, old_sq);
+            }
+            play_move(
+                model,
+                Move {
+                    game_id: id,
+                    r#move: reply,
+                    move_number,
+                },
+            );
+        }
+    }
+}
+
+pub fn view(model: &Model) -> Option<Node<crate::Msg>> {
+    let puzzle = model.puzzle.as_ref()?;
+    let color = match model.color? {
+        Color::White => "White",
+        Color::Black => "Black",
+    };
+    let moves = match puzzle.resource.length {
+        1 => "1 move".to_string(),
+        n => format!("{n} moves"),
+    };
+
+    Some(div![
+        C!("puzzle"),
+        h2![format!("{color} to play and win in {moves}")],
+        match puzzle.status {
+            Status::Solving => empty![],
+            Status::Wrong => h2![C!("error"), "Not the winning move, try again"],
+            Status::Solved => h2!["Solved!"],
+        },
+        button![
+            C!("button"),
+            "Next puzzle",
+            ev(Ev::Click, |_| crate::Msg::Game(Msg::NextPuzzle)),
+        ],
+    ])
+}
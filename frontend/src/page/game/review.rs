@@ -0,0 +1,112 @@
+use super::*;
+use seed::{prelude::*, *};
+
+const WIDTH: f32 = 600.;
+const HEIGHT: f32 = 150.;
+// Scores are cut off here in the graph, a win would flatten everything else
+const CAP: f32 = 1000.;
+
+fn to_y(score: i32) -> f32 {
+    let score = (score as f32).clamp(-CAP, CAP);
+    HEIGHT / 2. - score / CAP * HEIGHT / 2.
+}
+
+fn to_x(index: usize, len: usize) -> f32 {
+    WIDTH * index as f32 / len as f32
+}
+
+fn annotation_text(annotation: Annotation) -> &'static str {
+    match annotation {
+        Annotation::Blunder => "Blunder",
+        Annotation::MissedWin => "Missed win",
+    }
+}
+
+fn graph(review: &Review) -> Node<crate::Msg> {
+    let len = review.moves.len();
+    let mid = HEIGHT / 2.;
+
+    let points = std::iter::once((0., to_y(0)))
+        .chain(
+            review
+                .moves
+                .iter()
+                .enumerate()
+                .map(|(i, m)| (to_x(i + 1, len), to_y(m.score))),
+        )
+        .map(|(x, y)| format!("{x},{y}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    svg![
+        attrs! {
+            At::ViewBox => format!("0 0 {WIDTH} {HEIGHT}"),
+            At::Width => WIDTH,
+            At::Height => HEIGHT,
+        },
+        polyline![attrs! {
+            At::Points => format!("0,{mid} {WIDTH},{mid}"),
+            At::Stroke => "grey",
+        }],
+        polyline![attrs! {
+            At::Points => points,
+            At::Fill => "none",
+            At::Stroke => "black",
+            At::StrokeWidth => 2,
+        }],
+        review.moves.iter().enumerate().filter_map(|(i, m)| {
+            m.annotation.map(|annotation| {
+                let fill = match annotation {
+                    Annotation::Blunder => "red",
+                    Annotation::MissedWin => "orange",
+                };
+                circle![
+                    attrs! {
+                        At::Cx => to_x(i + 1, len),
+                        At::Cy => to_y(m.score),
+                        At::R => 5,
+                        At::Fill => fill,
+                    },
+                    ev(Ev::Click, move |_| crate::Msg::Game(Msg::JumpTo(i))),
+                ]
+            })
+        }),
+    ]
+}
+
+fn flagged_moves(game: &GameResource, review: &Review) -> Node<crate::Msg> {
+    let mut pos = Position::new();
+    let mut items = Vec::new();
+
+    for (i, (r#move, m)) in game.move_list.iter().zip(&review.moves).enumerate() {
+        if let Some(annotation) = m.annotation {
+            let played = pos.move_string(r#move.as_ref());
+            let best = pos.move_string(m.best_move.as_ref());
+            items.push(li![button![
+                C!("button"),
+                format!(
+                    "Move {}: {} ({played}), best was {best}",
+                    i + 1,
+                    annotation_text(annotation)
+                ),
+                ev(Ev::Click, move |_| crate::Msg::Game(Msg::JumpTo(i))),
+            ]]);
+        }
+        pos.play(r#move.clone());
+    }
+
+    ul![items]
+}
+
+// The review of a finished game, if the server has made one yet
+pub fn view(model: &Model) -> Option<Node<crate::Msg>> {
+    let game = model.game.as_ref()?;
+    let review = game.review.as_ref()?;
+
+    Some(div![
+        C!("review"),
+        h2!["Review"],
+        graph(review),
+        flagged_moves(game, review),
+    ])
+}
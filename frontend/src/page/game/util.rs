@@ -1,7 +1,7 @@
 use super::*;
 use crate::page::game::*;
 use seed::{self, prelude::*};
-use shared::{model::*, ObjectId};
+use shared::model::*;
 use web_sys::MouseEvent;
 
 pub const RATIO: f32 = 1.1547005;
@@ -24,22 +24,20 @@ pub fn place_piece(model: &mut Model, piece: Piece, sq: Square) {
 }
 
 pub fn play_move(model: &mut Model, r#move: Move) {
-    place_piece(model, r#move.piece, r#move.sq);
-    get_board_mut(model).unwrap().play_move(r#move.clone());
+    if let Err(e) = get_board_mut(model).unwrap().play_move(r#move.clone()) {
+        model.label = Some(format!("{e}"));
+        return;
+    }
 
     clear_yellow(&mut model.gridv3);
 
-    if let Some(hex) = model.gridv3.iter_mut().find(|hex| hex.sq() == r#move.sq) {
-        hex.yellow = true;
+    // A pass leaves the board as it was
+    if let Some(m) = r#move.r#move.clone() {
+        place_piece(model, m.piece, m.sq);
+        add_yellow(&mut model.gridv3, m);
     }
 
-    if let Some(old) = r#move.old_sq.as_ref() {
-        if let Some(hex) = model.gridv3.iter_mut().find(|hex| hex.sq() == *old) {
-            hex.yellow = true;
-        }
-    }
-
-    model.game.as_mut().unwrap().move_list.push(r#move.into());
+    model.game.as_mut().unwrap().move_list.push(r#move.r#move);
 }
 
 pub fn get_mouse_pos(model: &Model, mm: &MouseEvent) -> (f32, f32) {
@@ -105,17 +103,31 @@ pub fn set_highlight(model: &mut Model, val: bool) {
 }
 
 pub fn get_move(model: &Model, sel: Piece, sq: Square, old_sq: Option<Square>) -> Option<Move> {
-    let id: Result<ObjectId, _> = LocalStorage::get("id");
+    let r#move = InternalMove {
+        piece: sel,
+        sq,
+        old_sq,
+    };
+
+    // Puzzle moves are only checked, no player is needed
+    if let (Some(_), Some(game)) = (&model.puzzle, &model.game) {
+        return Some(Move {
+            game_id: game._id,
+            r#move: Some(r#move),
+            move_number: game.move_list.len(),
+        });
+    }
+
+    // The server knows who is playing from the session token
+    let token: Result<String, _> = LocalStorage::get("token");
     let name: Result<String, _> = LocalStorage::get("name");
 
-    if let (Ok(id), Ok(name), Some(game)) = (id, name, model.game.as_ref()) {
+    if let (Ok(_), Ok(name), Some(game)) = (token, name, model.game.as_ref()) {
         if game.players.iter().any(|_name| &name == _name) {
             return Some(Move {
-                piece: sel,
-                player_id: id,
                 game_id: game._id,
-                old_sq,
-                sq,
+                r#move: Some(r#move),
+                move_number: game.move_list.len(),
             });
         }
     }
@@ -195,11 +207,12 @@ pub fn game_complete(model: &Model) -> bool {
 }
 
 pub fn just_my_move(model: &Model, r#move: &Move) -> bool {
-    model
-        .color
-        .as_ref()
-        .map(|color| *color == r#move.piece.color)
-        .unwrap_or(false)
+    // White makes the even moves, passes included
+    let mover = match r#move.move_number % 2 {
+        0 => Color::White,
+        _ => Color::Black,
+    };
+    model.color == Some(mover)
 }
 
 pub fn sq_radius(sq: Square) -> usize {
@@ -379,31 +392,93 @@ pub fn replay_move(model: &mut Model, key: Key) {
     if let Some(replay) = model.replay_board.as_mut() {
         if let Some(index) = replay.get_and_update_index(key) {
             let m = model.game.as_ref().unwrap().move_list[index].clone();
+            replay.moves = match key {
+                Key::Left => index,
+                Key::Right => index + 1,
+            };
 
             clear_yellow(&mut model.gridv3);
-            match key {
-                Key::Left => {
+            let res = match (key, m) {
+                (Key::Left, m) => {
                     if index > 0 {
-                        let prev = model.game.as_ref().unwrap().move_list[index - 1].clone();
-                        add_yellow(&mut model.gridv3, prev);
+                        if let Some(prev) =
+                            model.game.as_ref().unwrap().move_list[index - 1].clone()
+                        {
+                            add_yellow(&mut model.gridv3, prev);
+                        }
                     }
 
-                    replay.board.unplay_move(m);
+                    m.map_or(Ok(()), |m| replay.board.unplay_move(m))
                 }
-                Key::Right => {
+                (Key::Right, Some(m)) => {
                     add_yellow(&mut model.gridv3, m.clone());
-                    replay.board.play_move_(m);
+                    replay.board.play_move_(m)
+                }
+                (Key::Right, None) => {
+                    replay.board.turns += 1;
+                    Ok(())
                 }
             };
+            if let Err(e) = res {
+                model.label = Some(format!("{e}"));
+            }
 
             grid_from_board_(&mut model.gridv3, &replay.board);
         }
     }
 }
 
+// Show the position before move `index` on the replay board, the arrow keys
+// continue from there
+pub fn jump_to_move(model: &mut Model, index: usize) {
+    let move_list = model.game.as_ref().unwrap().move_list.clone();
+    if index >= move_list.len() {
+        return;
+    }
+
+    let mut board = Board::default();
+    for r#move in &move_list[..index] {
+        let res = match r#move {
+            Some(r#move) => board.play_move_(r#move.clone()),
+            None => {
+                board.turns += 1;
+                Ok(())
+            }
+        };
+        if let Err(e) = res {
+            model.label = Some(format!("{e}"));
+            return;
+        }
+    }
+
+    let max = move_list.len() - 1;
+    let dir = Selector {
+        index,
+        max,
+        dir: if max == 0 || index > 0 {
+            Some(Key::Left)
+        } else {
+            None
+        },
+    };
+
+    clear_yellow(&mut model.gridv3);
+    if let Some(Some(prev)) = index.checked_sub(1).map(|i| move_list[i].clone()) {
+        add_yellow(&mut model.gridv3, prev);
+    }
+    grid_from_board_(&mut model.gridv3, &board);
+
+    model.replay_board = Some(ReplayBoard {
+        board: Box::new(board),
+        dir,
+        moves: index,
+    });
+}
+
 pub fn clear_replay(model: &mut Model) {
     if model.replay_board.is_some() {
         model.replay_board = None;
+        model.analysis = None;
         grid_from_board(model);
     }
 }
@@ -1,188 +1,234 @@
 use seed::{prelude::*, *};
 use shared::model::{
-    CreateGameChallenge, CreateGameChallengeBundle, CreateGameFormResponse, OnGoingGame,
-    ResponseBody,
+    AcceptGame, CreateGameChallenge, CreateGameFormResponse, ErrorCode, OnGoingGame, Page,
 };
 use shared::ObjectId;
 
-use crate::request::home::*;
+use crate::request::{home::*, ApiResult};
 use crate::Msg::Home;
 
+// How close to the bottom of the page the next pages are loaded, in pixels
+const LOAD_MARGIN: f64 = 300.;
+
+// A listing loaded a page at a time
+struct Paged<T> {
+    items: Vec<T>,
+    // Where the next page starts, None when everything is loaded
+    next: Option<String>,
+    // The cursor of the page being loaded, None for the first page
+    loading: Option<Option<String>>,
+}
+
+impl<T> Default for Paged<T> {
+    fn default() -> Self {
+        Self {
+            items: Vec::new(),
+            next: None,
+            loading: Some(None),
+        }
+    }
+}
+
+impl<T> Paged<T> {
+    // Whether the answer for the page at `cursor` is the one being waited
+    // for. A listing that was loaded again has no use for the older pages
+    fn waits_for(&self, cursor: &Option<String>) -> bool {
+        self.loading.as_ref() == Some(cursor)
+    }
+
+    fn add(&mut self, page: Page<T>) {
+        self.items.extend(page.items);
+        self.next = page.next;
+        self.loading = None;
+    }
+
+    // The page is tried again on the next scroll
+    fn failed(&mut self) {
+        self.loading = None;
+    }
+
+    // The cursor of the next page if it should be loaded now
+    fn load_more(&mut self) -> Option<String> {
+        if self.loading.is_some() {
+            return None;
+        }
+        let next = self.next.clone()?;
+        self.loading = Some(Some(next.clone()));
+        Some(next)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Listing {
+    Ongoing,
+    Old,
+    Users,
+}
+
 #[derive(Default)]
 pub struct Model {
-    available_games: Vec<CreateGameChallenge>,
+    available_games: Paged<CreateGameChallenge>,
     label: Option<String>,
-    ongoing_games: Vec<OnGoingGame>,
-    old_games: Vec<OnGoingGame>,   // Shh
-    users_games: Vec<OnGoingGame>, // Shh
+    ongoing_games: Paged<OnGoingGame>,
+    old_games: Paged<OnGoingGame>,   // Shh
+    users_games: Paged<OnGoingGame>, // Shh
+    _scroll: Option<StreamHandle>,
+}
+
+impl Model {
+    fn games(&mut self, listing: Listing) -> &mut Paged<OnGoingGame> {
+        match listing {
+            Listing::Ongoing => &mut self.ongoing_games,
+            Listing::Old => &mut self.old_games,
+            Listing::Users => &mut self.users_games,
+        }
+    }
 }
 
 fn is_logged_in() -> bool {
-    let f: seed::browser::web_storage::Result<ObjectId> = LocalStorage::get("id");
+    let f: seed::browser::web_storage::Result<String> = LocalStorage::get("token");
     f.is_ok()
 }
 
+fn fetch_games(listing: Listing, cursor: Option<String>, orders: &mut impl Orders<Msg>) {
+    orders.perform_cmd(async move {
+        let page = match listing {
+            Listing::Ongoing => get_all_games(cursor.clone()).await,
+            Listing::Old => get_old_games(cursor.clone()).await,
+            Listing::Users => match LocalStorage::get("name") {
+                Ok(name) => get_users_games(name, cursor.clone()).await,
+                // Not logged in
+                Err(_) => return None,
+            },
+        };
+        Some(Msg::FetchedGames(listing, cursor, page))
+    });
+}
+
+fn fetch_challenges(cursor: Option<String>, orders: &mut impl Orders<Msg>) {
+    orders.perform_cmd(async move {
+        let page = get_challenges(cursor.clone()).await;
+        Msg::FetchedChallenges(cursor, page)
+    });
+}
+
 pub fn init(orders: &mut impl Orders<Msg>) -> Model {
-    orders
-        .skip()
-        .perform_cmd(async { Msg::FetchedAvailableGames(get_all_games().await) })
-        .perform_cmd(async { Msg::FetchedOldGames(get_old_games().await) })
-        .perform_cmd(async {
-            let id: ObjectId =
-                LocalStorage::get("id").unwrap_or_else(|_| ObjectId::from_bytes([0; 12]));
-
-            Msg::FetchedCreateGame(send_message(id, "home", Method::Post).await)
-        })
-        .perform_cmd(async {
-            let id: ObjectId =
-                LocalStorage::get("id").unwrap_or_else(|_| ObjectId::from_bytes([0; 12]));
-            Msg::FetchedUsersGames(get_users_games(id.to_string()).await)
-        });
+    orders.skip();
+    fetch_challenges(None, orders);
+    for listing in [Listing::Ongoing, Listing::Old, Listing::Users] {
+        fetch_games(listing, None, orders);
+    }
+    let scroll = orders.stream_with_handle(streams::window_event(Ev::Scroll, |_| Msg::Scrolled));
 
-    Model::default()
+    Model {
+        _scroll: Some(scroll),
+        ..Model::default()
+    }
 }
 
-fn challenge_from_bundle(bundle: Vec<CreateGameChallengeBundle>) -> Vec<CreateGameChallenge> {
-    bundle
-        .into_iter()
-        .flat_map(|user| {
-            let CreateGameChallengeBundle {
-                name,
-                games,
-                creator_id,
-            } = user;
-
-            games.into_iter().map(move |game_id| CreateGameChallenge {
-                name: name.clone(),
-                _id: game_id,
-                creator: creator_id,
-            })
-        })
-        .collect()
+// Whether the bottom of the page is in view, or about to be
+fn near_bottom() -> bool {
+    let window = window();
+    let height = window
+        .inner_height()
+        .ok()
+        .and_then(|h| h.as_f64())
+        .unwrap_or_default();
+    let bottom = window.scroll_y().unwrap_or_default() + height;
+    let page = document()
+        .document_element()
+        .map_or(0, |html| html.scroll_height());
+    bottom + LOAD_MARGIN >= page as f64
 }
 
 pub enum Msg {
-    FetchedCreateGame(fetch::Result<String>),
+    // The cursor the page was asked for with, and the page
+    FetchedChallenges(Option<String>, ApiResult<Page<CreateGameChallenge>>),
     AcceptGame { game: ObjectId, creator: ObjectId },
-    AcceptedGame(fetch::Result<String>),
+    AcceptedGame(ApiResult<AcceptGame>),
 
-    FetchedAvailableGames(fetch::Result<String>),
-    FetchedOldGames(fetch::Result<String>),
-    FetchedUsersGames(fetch::Result<String>),
+    FetchedGames(Listing, Option<String>, ApiResult<Page<OnGoingGame>>),
+    // The next pages are loaded when the bottom of the page comes into view
+    Scrolled,
 }
 
 pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
-    let parse_resp = |resp: fetch::Result<String>| -> Result<ResponseBody, String> {
-        resp.map_err(|e| format!("{e:?}"))
-            .and_then(|text| {
-                serde_json::from_str::<ResponseBody>(&text).map_err(|e| format!("{e:?}"))
-            })
-            .and_then(|resp| {
-                if resp.status == 200 {
-                    Ok(resp)
-                } else {
-                    Err("wrong statuscode".into())
-                }
-            })
-    };
-
     match msg {
-        Msg::FetchedOldGames(text) => match parse_resp(text) {
-            Ok(resp) => {
-                let games: Vec<OnGoingGame> = resp.get_body();
-                model.old_games = games;
-            }
-            Err(e) => model.label = Some(e),
-        },
+        // Answers for a listing that has been loaded again since
+        Msg::FetchedGames(listing, cursor, _) if !model.games(listing).waits_for(&cursor) => {}
+        Msg::FetchedChallenges(cursor, _) if !model.available_games.waits_for(&cursor) => {}
 
-        Msg::FetchedUsersGames(text) => {
-            if let Ok(resp) = parse_resp(text) {
-                let games: Vec<OnGoingGame> = resp.get_body();
-                model.users_games = games;
-            }
+        Msg::FetchedGames(listing, _, Ok(page)) => {
+            model.games(listing).add(page);
+            // Keep loading until the page can be scrolled
+            orders.after_next_render(|_| Msg::Scrolled);
         }
+        // Not logged in
+        Msg::FetchedGames(Listing::Users, _, Err(_)) => model.users_games.failed(),
 
-        Msg::FetchedCreateGame(Ok(text)) => match serde_json::from_str::<ResponseBody>(&text) {
-            Ok(resp) => match resp.status {
-                200 => {
-                    let bundle = resp.get_body::<Vec<CreateGameChallengeBundle>>();
-                    model.available_games = challenge_from_bundle(bundle);
-                }
-                e => {
-                    model.label = Some(format!("error: {e}"));
+        Msg::FetchedChallenges(_, Ok(page)) => {
+            model.available_games.add(page);
+            orders.after_next_render(|_| Msg::Scrolled);
+        }
+
+        Msg::Scrolled => {
+            if !near_bottom() {
+                return;
+            }
+            if let Some(cursor) = model.available_games.load_more() {
+                fetch_challenges(Some(cursor), orders);
+            }
+            for listing in [Listing::Ongoing, Listing::Old, Listing::Users] {
+                if let Some(cursor) = model.games(listing).load_more() {
+                    fetch_games(listing, Some(cursor), orders);
                 }
-            },
-            Err(e) => {
-                model.label = Some(format!("error: {e:?}"));
             }
-        },
-
-        Msg::FetchedCreateGame(Err(text)) => {
-            model.label = Some(format!("error: {text:?}"));
         }
 
-        Msg::AcceptGame { game, creator } => match LocalStorage::get("id").ok() {
-            Some(id) => {
-                let form = CreateGameFormResponse {
-                    game,
-                    creator,
-                    user: id,
-                };
+        Msg::AcceptGame { game, creator } => {
+            if is_logged_in() {
+                let form = CreateGameFormResponse { game, creator };
 
                 orders.skip().perform_cmd(async {
                     Msg::AcceptedGame(send_message(form, "create-game", Method::Put).await)
                 });
-            }
-            _ => {
+            } else {
                 model.label = Some("must be logged in".into());
             }
-        },
-
-        Msg::AcceptedGame(Ok(text)) => match serde_json::from_str::<ResponseBody>(&text) {
-            Ok(resp) => match resp.status {
-                201 => {
-                    let accept: shared::model::AcceptGame = resp.get_body();
-                    let idx = model
-                        .available_games
-                        .iter()
-                        .position(|g| g._id == accept.game)
-                        .unwrap();
-
-                    let game = model.available_games.remove(idx);
-                    let name = LocalStorage::get("name").unwrap();
-                    model.ongoing_games.push(OnGoingGame {
-                        game_object_id: accept.object_id,
-                        players: [game.name, name],
-                    });
-                }
-                e => {
-                    model.label = Some(format!("error: {e}"));
-                }
-            },
-            Err(e) => {
-                model.label = Some(format!("error: {e:?}"));
-            }
-        },
-        Msg::AcceptedGame(Err(text)) => {
-            model.label = Some(format!("error: {text:?}"));
         }
 
-        Msg::FetchedAvailableGames(Ok(text)) => match serde_json::from_str::<ResponseBody>(&text) {
-            Ok(resp) => match resp.status {
-                200 => {
-                    let games: Vec<OnGoingGame> = resp.get_body();
-                    model.ongoing_games = games;
-                }
-                e => {
-                    model.label = Some(format!("error: {e}"));
-                }
-            },
-            Err(e) => {
-                model.label = Some(format!("error: {e:?}"));
-            }
-        },
-        Msg::FetchedAvailableGames(Err(text)) => {
-            model.label = Some(format!("error: {text:?}"));
+        Msg::AcceptedGame(Ok(accept)) => {
+            let idx = model
+                .available_games
+                .items
+                .iter()
+                .position(|g| g._id == accept.game)
+                .unwrap();
+
+            let game = model.available_games.items.remove(idx);
+            let name = LocalStorage::get("name").unwrap();
+            model.ongoing_games.items.push(OnGoingGame {
+                game_object_id: accept.object_id,
+                players: [game.name, name],
+            });
+        }
+        // Someone else accepted it first
+        Msg::AcceptedGame(Err(e)) if e.code == ErrorCode::Conflict => {
+            model.label = Some("the challenge is already taken".into());
+            model.available_games = Paged::default();
+            fetch_challenges(None, orders);
+        }
+
+        Msg::FetchedGames(listing, _, Err(e)) => {
+            model.games(listing).failed();
+            model.label = Some(format!("error: {e}"));
+        }
+        Msg::FetchedChallenges(_, Err(e)) => {
+            model.available_games.failed();
+            model.label = Some(format!("error: {e}"));
+        }
+        Msg::AcceptedGame(Err(e)) => {
+            model.label = Some(format!("error: {e}"));
         }
     }
 }
@@ -210,6 +256,7 @@ fn available_games<Ms: 'static>(model: &Model) -> Node<Ms> {
             tbody![
                 model
                     .available_games
+                    .items
                     .iter()
                     .map(|game| { tr![td![&game.name], td![challenge(game)]] })
             ]
@@ -238,6 +285,7 @@ fn ongoing_games<Ms: 'static>(model: &Model) -> Node<Ms> {
             tbody![
                 model
                     .ongoing_games
+                    .items
                     .iter()
                     .map(|game| { ongoing_game(game) })
             ]
@@ -251,7 +299,7 @@ fn old_games<Ms: 'static>(model: &Model) -> Node<Ms> {
         table![
             C!("challenge-table"),
             thead![tr![th!["White"], th!["Black"]]],
-            tbody![model.old_games.iter().map(|game| { ongoing_game(game) })]
+            tbody![model.old_games.items.iter().map(ongoing_game)]
         ]
     ]
 }
@@ -262,7 +310,7 @@ fn users_games<Ms: 'static>(model: &Model) -> Node<Ms> {
         table![
             C!("challenge-table"),
             thead![tr![th!["White"], th!["Black"]]],
-            tbody![model.users_games.iter().map(|game| { ongoing_game(game) })]
+            tbody![model.users_games.items.iter().map(ongoing_game)]
         ]
     ]
 }
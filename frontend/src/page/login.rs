@@ -4,7 +4,7 @@ use crate::component::user_cred;
 
 pub fn init(_url: Url) -> Option<Model> {
     Some(Model {
-        user_cred: user_cred::init("Login".to_string(), ("login".to_string(), 200)),
+        user_cred: user_cred::init("Login".to_string(), "login".to_string()),
     })
 }
 
@@ -4,7 +4,7 @@ use crate::component::user_cred;
 
 pub fn init(_url: Url) -> Option<Model> {
     Some(Model {
-        user_cred: user_cred::init("Register".to_string(), ("register".to_string(), 201)),
+        user_cred: user_cred::init("Register".to_string(), "register".to_string()),
     })
 }
 
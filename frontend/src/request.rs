@@ -1,10 +1,17 @@
 pub mod create;
 pub mod game;
 pub mod home;
+pub mod puzzle;
 pub mod user_cred;
 
 use crate::ObjectId;
 use const_format::formatcp;
+use seed::prelude::*;
+use serde::de::DeserializeOwned;
+use shared::model::http::{ApiError, ErrorCode};
+
+// The value the server answered with, or what went wrong
+pub type ApiResult<T> = Result<T, ApiError>;
 
 const PORT: usize = 5000;
 const WEB_SOCKET_PORT: usize = 5001;
@@ -24,6 +31,42 @@ fn url(end_point: &str) -> String {
     format!("{}/{}", BASE_API_URL, end_point)
 }
 
+// Sends the session token along when the user is logged in
+fn authorized(request: Request<'_>) -> Request<'_> {
+    match LocalStorage::get::<_, String>("token") {
+        Ok(token) => request.header(Header::bearer(token)),
+        Err(_) => request,
+    }
+}
+
+// Forgets the session, the user has to log in again
+pub fn forget_session() {
+    for key in ["token", "id", "name"] {
+        LocalStorage::remove(key).ok();
+    }
+}
+
+fn network_error(e: FetchError) -> ApiError {
+    ApiError::with_details(ErrorCode::Network, format!("{e:?}"))
+}
+
+// Sends the request. A success has the value as its body, anything else an
+// `ApiError`. When the session has run out or was logged out the user is
+// sent to log in again
+async fn send<T: DeserializeOwned + 'static>(request: Request<'_>) -> ApiResult<T> {
+    let resp = request.fetch().await.map_err(network_error)?;
+    if resp.status().is_ok() {
+        resp.json().await.map_err(network_error)
+    } else {
+        let error: ApiError = resp.json().await.map_err(network_error)?;
+        if error.code == ErrorCode::Unauthorized {
+            forget_session();
+            Url::new().add_path_part("login").go_and_load();
+        }
+        Err(error)
+    }
+}
+
 const WEB_SOCKET_ULR: &str = if cfg!(debug_assertions) {
     formatcp!("ws://{URL}:{WEB_SOCKET_PORT}/ws")
 } else {
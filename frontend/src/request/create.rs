@@ -1,15 +1,21 @@
-use super::url;
+use super::{authorized, network_error, send, url, ApiResult};
 
 use seed::prelude::*;
+use shared::model::{AcceptGame, Bot, BotChallenge};
 use shared::ObjectId;
 
-pub async fn send_create_request(creator: ObjectId) -> fetch::Result<String> {
-    Request::new(url("create-game"))
+pub async fn send_create_request() -> ApiResult<ObjectId> {
+    send(authorized(Request::new(url("create-game"))).method(Method::Post)).await
+}
+
+pub async fn get_bots() -> ApiResult<Vec<Bot>> {
+    send(Request::new(url("bot")).method(Method::Get)).await
+}
+
+pub async fn send_bot_challenge(challenge: BotChallenge) -> ApiResult<AcceptGame> {
+    let request = authorized(Request::new(url("bot")))
         .method(Method::Post)
-        .json(&creator)?
-        .fetch()
-        .await?
-        .check_status()?
-        .text()
-        .await
+        .json(&challenge)
+        .map_err(network_error)?;
+    send(request).await
 }
@@ -1,36 +1,45 @@
-use super::url;
+use super::{authorized, network_error, send, url, ApiResult};
 use seed::prelude::*;
-use shared::model::Move;
+use shared::model::{Analysis, ExplorerMove, GameResource, Move, PositionRequest};
 use shared::ObjectId;
 
-pub async fn get_game(id: ObjectId) -> fetch::Result<String> {
-    Request::new(url(&format!("game?q={id}")))
-        .method(Method::Get)
-        .fetch()
-        .await?
-        .check_status()?
-        .text()
-        .await
+pub async fn get_game(id: ObjectId) -> ApiResult<GameResource> {
+    send(Request::new(url(&format!("game?q={id}"))).method(Method::Get)).await
 }
 
-pub async fn send_move(r#move: Move) -> fetch::Result<String> {
-    Request::new(url(&format!("game?q={id}", id = r#move.player_id)))
+// Linked to directly, the browser downloads the record
+pub fn export_url(id: ObjectId) -> String {
+    url(&format!("game/{id}/export"))
+}
+
+pub async fn send_move(r#move: Move) -> ApiResult<()> {
+    let request = authorized(Request::new(url("game")))
         .method(Method::Post)
-        .json(&r#move)?
-        .fetch()
-        .await?
-        .check_status()?
-        .text()
-        .await
+        .json(&r#move)
+        .map_err(network_error)?;
+    send(request).await
 }
 
-pub async fn complete_game(id: ObjectId) -> fetch::Result<String> {
-    Request::new(url("game"))
+pub async fn complete_game(id: ObjectId) -> ApiResult<()> {
+    let request = Request::new(url("game"))
         .method(Method::Delete)
-        .json(&id)?
-        .fetch()
-        .await?
-        .check_status()?
-        .text()
-        .await
+        .json(&id)
+        .map_err(network_error)?;
+    send(request).await
+}
+
+pub async fn analyze(req: PositionRequest) -> ApiResult<Analysis> {
+    let request = Request::new(url("analyze"))
+        .method(Method::Post)
+        .json(&req)
+        .map_err(network_error)?;
+    send(request).await
+}
+
+pub async fn explore(req: PositionRequest) -> ApiResult<Vec<ExplorerMove>> {
+    let request = Request::new(url("explore"))
+        .method(Method::Post)
+        .json(&req)
+        .map_err(network_error)?;
+    send(request).await
 }
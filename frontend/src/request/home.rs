@@ -1,48 +1,54 @@
-use super::url;
+use super::{authorized, network_error, send, url, ApiResult};
 use seed::prelude::*;
-use serde::Serialize;
-// use shared::ObjectId;
+use serde::{de::DeserializeOwned, Serialize};
+use shared::model::{CreateGameChallenge, OnGoingGame, Page};
 
-pub async fn get_all_games() -> fetch::Result<String> {
-    Request::new(url("game?q=all"))
-        .method(Method::Get)
-        .fetch()
-        .await?
-        .check_status()?
-        .text()
-        .await
+// The end point with the cursor of the page to get, if it is not the first
+fn paged(end_point: &str, cursor: Option<String>) -> String {
+    match cursor {
+        Some(cursor) if end_point.contains('?') => url(&format!("{end_point}&cursor={cursor}")),
+        Some(cursor) => url(&format!("{end_point}?cursor={cursor}")),
+        None => url(end_point),
+    }
 }
 
-pub async fn get_old_games() -> fetch::Result<String> {
-    Request::new(url("game?q=old"))
-        .method(Method::Get)
-        .fetch()
-        .await?
-        .check_status()?
-        .text()
-        .await
+// Everything but letters, digits and `-._~` is escaped
+fn encode(part: &str) -> String {
+    part.bytes()
+        .map(|b| match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{b:02X}"),
+        })
+        .collect()
 }
 
-pub async fn get_users_games(id: String) -> fetch::Result<String> {
-    Request::new(url(&format!("game?q=user({id})")))
-        .method(Method::Get)
-        .fetch()
-        .await?
-        .check_status()?
-        .text()
-        .await
+pub async fn get_all_games(cursor: Option<String>) -> ApiResult<Page<OnGoingGame>> {
+    send(Request::new(paged("games", cursor)).method(Method::Get)).await
 }
 
-pub async fn send_message<T>(body: T, end_point: &str, method: Method) -> fetch::Result<String>
+pub async fn get_old_games(cursor: Option<String>) -> ApiResult<Page<OnGoingGame>> {
+    send(Request::new(paged("games?state=complete", cursor)).method(Method::Get)).await
+}
+
+pub async fn get_users_games(name: String, cursor: Option<String>) -> ApiResult<Page<OnGoingGame>> {
+    let end_point = format!("users/{}/games", encode(&name));
+    send(Request::new(paged(&end_point, cursor)).method(Method::Get)).await
+}
+
+pub async fn get_challenges(cursor: Option<String>) -> ApiResult<Page<CreateGameChallenge>> {
+    send(authorized(Request::new(paged("challenges", cursor))).method(Method::Get)).await
+}
+
+pub async fn send_message<T, R>(body: T, end_point: &str, method: Method) -> ApiResult<R>
 where
     T: Serialize,
+    R: DeserializeOwned + 'static,
 {
-    Request::new(url(end_point))
+    let request = authorized(Request::new(url(end_point)))
         .method(method)
-        .json(&body)?
-        .fetch()
-        .await?
-        .check_status()?
-        .text()
-        .await
+        .json(&body)
+        .map_err(network_error)?;
+    send(request).await
 }
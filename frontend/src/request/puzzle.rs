@@ -0,0 +1,21 @@
+use super::{network_error, send, url, ApiResult};
+use seed::prelude::*;
+use shared::model::{PuzzleAnswer, PuzzleAttempt, PuzzleResource};
+use shared::ObjectId;
+
+// A random puzzle when no id is given
+pub async fn get_puzzle(id: Option<ObjectId>) -> ApiResult<PuzzleResource> {
+    let end_point = match id {
+        Some(id) => format!("puzzle?q={id}"),
+        None => "puzzle".to_string(),
+    };
+    send(Request::new(url(&end_point)).method(Method::Get)).await
+}
+
+pub async fn send_attempt(attempt: PuzzleAttempt) -> ApiResult<PuzzleAnswer> {
+    let request = Request::new(url("puzzle"))
+        .method(Method::Post)
+        .json(&attempt)
+        .map_err(network_error)?;
+    send(request).await
+}
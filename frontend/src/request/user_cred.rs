@@ -1,14 +1,16 @@
-use super::url;
+use super::{authorized, network_error, send, url, ApiResult};
 use seed::prelude::*;
-use shared::model::UserCredentials;
+use shared::model::{Session, UserCredentials};
 
-pub async fn send_message(end_point: &str, form: UserCredentials) -> fetch::Result<String> {
-    Request::new(url(end_point))
+pub async fn send_message(end_point: &str, form: UserCredentials) -> ApiResult<Session> {
+    let request = Request::new(url(end_point))
         .method(Method::Post)
-        .json(&form)?
-        .fetch()
-        .await?
-        .check_status()?
-        .text()
-        .await
+        .json(&form)
+        .map_err(network_error)?;
+    send(request).await
+}
+
+// Ends the session on the server, the token stops working
+pub async fn logout() -> ApiResult<()> {
+    send(authorized(Request::new(url("logout"))).method(Method::Post)).await
 }
@@ -0,0 +1,266 @@
+mod player;
+mod stats;
+use std::{fs::File, io, io::Write, process, time::Duration};
+
+use player::{Player, Spec};
+use shared::{
+    engine::{outcome, side_to_move, Outcome, Rng},
+    model::Color,
+    uhp::Position,
+};
+use stats::Stats;
+
+
+const USAGE: &str = "usage: selfplay --a <player> --b <player> [--games n] [--openings n] \
+                     [--max-moves n] [--time secs] [--seed n] [--out file]
+
+players:
+    alphabeta[:depth[:queen=n,mobility=n]]
+    mcts[:iterations[:queen=n,mobility=n]]
+    uhp:<command>";
+
+struct Args
+{
+    a:         Spec,
+    b:         Spec,
+    games:     u32,
+    // Random moves played at the start of every pair of games
+    openings:  usize,
+    // Games still going after this many moves are draws
+    max_moves: usize,
+    // Thinking time per move for the uhp players
+    time:      Duration,
+    seed:      u64,
+    out:       Option<String>,
+}
+
+fn parse<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String>
+{
+    let value = value.ok_or(format!("missing value for {flag}"))?;
+    value.parse().map_err(|_| format!("invalid value '{value}' for {flag}"))
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Args, String>
+{
+    let (mut a, mut b) = (None, None);
+    let mut res = Args {
+        a:         Spec::Uhp(String::new()),
+        b:         Spec::Uhp(String::new()),
+        games:     10,
+        openings:  4,
+        max_moves: 200,
+        time:      Duration::from_secs(1),
+        seed:      0,
+        out:       None,
+    };
+
+    while let Some(flag) = args.next()
+    {
+        let value = args.next();
+        match flag.as_str()
+        {
+            "--a" => a = Some(parse::<String>(&flag, value)?.parse()?),
+            "--b" => b = Some(parse::<String>(&flag, value)?.parse()?),
+            "--games" => res.games = parse(&flag, value)?,
+            "--openings" => res.openings = parse(&flag, value)?,
+            "--max-moves" => res.max_moves = parse(&flag, value)?,
+            "--time" => res.time = Duration::from_secs(parse(&flag, value)?),
+            "--seed" => res.seed = parse(&flag, value)?,
+            "--out" => res.out = Some(parse(&flag, value)?),
+            _ => return Err(format!("unknown flag {flag}")),
+        }
+    }
+
+    res.a = a.ok_or("missing --a")?;
+    res.b = b.ok_or("missing --b")?;
+    Ok(res)
+}
+
+// Random legal moves, the same opening is played with both colors
+fn opening(seed: u64, moves: usize) -> Position
+{
+    let mut rng = Rng::new(seed);
+    let mut pos = Position::new();
+
+    for _ in 0..moves
+    {
+        let legal = pos.legal_moves();
+        if outcome(pos.board()).is_some() || legal.is_empty()
+        {
+            break;
+        }
+        pos.play(legal[rng.below(legal.len())].clone());
+    }
+    pos
+}
+
+fn play_game<'a>(
+    white: &'a mut dyn Player,
+    black: &'a mut dyn Player,
+    mut pos: Position,
+    max_moves: usize,
+) -> io::Result<(Position, Outcome)>
+{
+    loop
+    {
+        if let Some(outcome) = outcome(pos.board())
+        {
+            return Ok((pos, outcome));
+        }
+        if pos.history().count() >= max_moves
+        {
+            return Ok((pos, Outcome::Draw));
+        }
+
+        let player = match side_to_move(pos.board())
+        {
+            Color::White => &mut *white,
+            Color::Black => &mut *black,
+        };
+
+        let r#move = player.best_move(&pos)?;
+        if !pos.legal_moves().contains(&r#move)
+        {
+            let s = pos.move_string(r#move.as_ref());
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("illegal move {s}")));
+        }
+
+        // Nobody can move
+        if r#move.is_none() && matches!(pos.history().last(), Some(None))
+        {
+            return Ok((pos, Outcome::Draw));
+        }
+        pos.play(r#move);
+    }
+}
+
+fn run(args: Args) -> io::Result<Stats>
+{
+    let mut a = args.a.player(args.seed, args.time)?;
+    let mut b = args.b.player(args.seed, args.time)?;
+
+    let mut out = match &args.out
+    {
+        Some(path) => Some(File::create(path)?),
+        None => None,
+    };
+
+    let mut stats = Stats::default();
+    for game in 0..args.games
+    {
+        let pos = opening(args.seed.wrapping_add((game / 2) as u64), args.openings);
+
+        // `a` is white in the even games
+        let a_color = if game % 2 == 0 { Color::White } else { Color::Black };
+        let (pos, outcome) = match a_color
+        {
+            Color::White => play_game(a.as_mut(), b.as_mut(), pos, args.max_moves)?,
+            Color::Black => play_game(b.as_mut(), a.as_mut(), pos, args.max_moves)?,
+        };
+
+        let result = match outcome
+        {
+            Outcome::Win(c) if c == a_color =>
+            {
+                stats.wins += 1;
+                "win"
+            },
+            Outcome::Win(_) =>
+            {
+                stats.losses += 1;
+                "loss"
+            },
+            Outcome::Draw =>
+            {
+                stats.draws += 1;
+                "draw"
+            },
+        };
+        println!(
+            "game {}/{}: {result} as {a_color:?} in {} moves",
+            game + 1,
+            args.games,
+            pos.history().count()
+        );
+
+        if let Some(out) = out.as_mut()
+        {
+            writeln!(out, "{}", pos.game_string())?;
+        }
+    }
+
+    Ok(stats)
+}
+
+/*
+ * Plays a match between two players and reports the result from the point
+ * of view of the first one.
+ */
+fn main()
+{
+    let args = match parse_args(std::env::args().skip(1))
+    {
+        Ok(args) => args,
+        Err(e) =>
+        {
+            eprintln!("{e}\n\n{USAGE}");
+            process::exit(2);
+        },
+    };
+
+    match run(args)
+    {
+        Ok(stats) => println!("\n{stats}"),
+        Err(e) =>
+        {
+            eprintln!("error: {e}");
+            process::exit(1);
+        },
+    }
+}
+
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    fn args(s: &str) -> Result<Args, String>
+    {
+        parse_args(s.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn test_parse_args()
+    {
+        let res = args("--a alphabeta:2 --b mcts:100 --games 4 --seed 9").unwrap();
+        assert_eq!(res.games, 4);
+        assert_eq!(res.seed, 9);
+        assert_eq!(res.openings, 4);
+
+        assert!(args("--a alphabeta:2").is_err());
+        assert!(args("--a alphabeta:2 --b alphabeta --games").is_err());
+        assert!(args("--a alphabeta:2 --b alphabeta --colour white").is_err());
+    }
+
+    #[test]
+    fn test_match()
+    {
+        let path = std::env::temp_dir().join(format!("selfplay-{}.txt", process::id()));
+        let mut res = args("--a alphabeta:1 --b alphabeta:1 --games 4 --max-moves 30").unwrap();
+        res.out = Some(path.to_str().unwrap().to_string());
+
+        let stats = run(res).unwrap();
+        assert_eq!(stats.games(), 4);
+
+        // Every game can be read back
+        let games = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(games.lines().count(), 4);
+        for game in games.lines()
+        {
+            assert!(game.parse::<Position>().is_ok(), "{game}");
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    time::Duration,
+};
+
+use shared::{
+    engine::{AlphaBeta, Engine, Mcts, Weights},
+    model::InternalMove,
+    uhp::{bestmove_command, newgame_command, parse_bestmove, Position, Response},
+};
+
+
+pub trait Player
+{
+    // `None` is a pass
+    fn best_move(&mut self, pos: &Position) -> io::Result<Option<InternalMove>>;
+}
+
+
+pub struct Builtin
+{
+    engine: Box<dyn Engine>,
+}
+
+impl Player for Builtin
+{
+    fn best_move(&mut self, pos: &Position) -> io::Result<Option<InternalMove>>
+    {
+        Ok(match pos.legal_moves()[..]
+        {
+            [None] => None,
+            _ => self.engine.search(pos.board()).best_move,
+        })
+    }
+}
+
+
+/*
+ * An external engine speaking the Universal Hive Protocol, it is sent the
+ * whole game before every move.
+ */
+pub struct Uhp
+{
+    time:   Duration,
+    child:  Child,
+    stdin:  ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Uhp
+{
+    pub fn spawn(command: &str, time: Duration) -> io::Result<Self>
+    {
+        let mut parts = command.split_whitespace();
+        let program = parts.next().ok_or_else(|| invalid("empty uhp command"))?;
+
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+
+        let mut uhp = Self {
+            time,
+            child,
+            stdin,
+            stdout,
+        };
+        uhp.read_response()?;
+        Ok(uhp)
+    }
+
+    fn read_response(&mut self) -> io::Result<Vec<String>>
+    {
+        let mut response = Response::default();
+        loop
+        {
+            let mut line = String::new();
+            if self.stdout.read_line(&mut line)? == 0
+            {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            if response.push(&line)
+            {
+                return response.finish().map_err(|e| invalid(&e.to_string()));
+            }
+        }
+    }
+
+    fn send(&mut self, cmd: &str) -> io::Result<Vec<String>>
+    {
+        writeln!(self.stdin, "{cmd}")?;
+        self.stdin.flush()?;
+        self.read_response()
+    }
+}
+
+impl Player for Uhp
+{
+    fn best_move(&mut self, pos: &Position) -> io::Result<Option<InternalMove>>
+    {
+        self.send(&newgame_command(pos))?;
+        let lines = self.send(&bestmove_command(self.time))?;
+        parse_bestmove(pos, &lines).map_err(|e| invalid(&e.to_string()))
+    }
+}
+
+impl Drop for Uhp
+{
+    fn drop(&mut self)
+    {
+        let _ = writeln!(self.stdin, "exit");
+        let _ = self.child.wait();
+    }
+}
+
+
+fn invalid(s: &str) -> io::Error
+{
+    io::Error::new(io::ErrorKind::InvalidData, s.to_string())
+}
+
+fn parse<T: std::str::FromStr>(s: &str, what: &str) -> Result<T, String>
+{
+    s.parse().map_err(|_| format!("invalid {what} '{s}'"))
+}
+
+fn parse_weights(s: &str) -> Result<Weights, String>
+{
+    let mut weights = Weights::default();
+
+    for pair in s.split(',').filter(|p| !p.is_empty())
+    {
+        match pair.split_once('=')
+        {
+            Some(("queen", v)) => weights.queen_neighbor = parse(v, "weight")?,
+            Some(("mobility", v)) => weights.mobility = parse(v, "weight")?,
+            _ => return Err(format!("unknown weight '{pair}'")),
+        }
+    }
+    Ok(weights)
+}
+
+/*
+ * A player from the command line:
+ *     alphabeta[:depth[:queen=n,mobility=n]]
+ *     mcts[:iterations[:queen=n,mobility=n]]
+ *     uhp:<command>
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Spec
+{
+    AlphaBeta
+    {
+        depth:   u32,
+        weights: Weights,
+    },
+    Mcts
+    {
+        iterations: u32,
+        weights:    Weights,
+    },
+    Uhp(String),
+}
+
+impl std::str::FromStr for Spec
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        if let Some(command) = s.strip_prefix("uhp:")
+        {
+            return Ok(Spec::Uhp(command.to_string()));
+        }
+
+        let mut parts = s.split(':');
+        let kind = parts.next().unwrap_or_default();
+        let n = parts.next();
+        let weights = parse_weights(parts.next().unwrap_or_default())?;
+
+        if parts.next().is_some()
+        {
+            return Err(format!("too many parts in '{s}'"));
+        }
+
+        match kind
+        {
+            "alphabeta" => Ok(Spec::AlphaBeta {
+                depth: n.map_or(Ok(3), |n| parse(n, "depth"))?,
+                weights,
+            }),
+            "mcts" => Ok(Spec::Mcts {
+                iterations: n.map_or(Ok(2000), |n| parse(n, "iterations"))?,
+                weights,
+            }),
+            _ => Err(format!("unknown player '{s}'")),
+        }
+    }
+}
+
+impl Spec
+{
+    pub fn player(&self, seed: u64, time: Duration) -> io::Result<Box<dyn Player>>
+    {
+        Ok(match self
+        {
+            Spec::AlphaBeta {
+                depth,
+                weights,
+            } =>
+            {
+                let mut engine = AlphaBeta::new(*depth);
+                engine.weights = *weights;
+                Box::new(Builtin {
+                    engine: Box::new(engine)
+                })
+            },
+            Spec::Mcts {
+                iterations,
+                weights,
+            } =>
+            {
+                let mut engine = Mcts::new(*iterations, seed);
+                engine.weights = *weights;
+                Box::new(Builtin {
+                    engine: Box::new(engine)
+                })
+            },
+            Spec::Uhp(command) => Box::new(Uhp::spawn(command, time)?),
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    fn test_parse_spec()
+    {
+        assert_eq!(
+            "alphabeta".parse(),
+            Ok(Spec::AlphaBeta {
+                depth: 3, weights: Weights::default()
+            })
+        );
+
+        let spec: Spec = "mcts:500:queen=50,mobility=2".parse().unwrap();
+        match spec
+        {
+            Spec::Mcts {
+                iterations,
+                weights,
+            } =>
+            {
+                assert_eq!(iterations, 500);
+                assert_eq!((weights.queen_neighbor, weights.mobility), (50, 2));
+            },
+            _ => panic!("expected mcts"),
+        }
+
+        assert_eq!("uhp:./engine --fast".parse(), Ok(Spec::Uhp("./engine --fast".into())));
+        assert!("alphabeta:x".parse::<Spec>().is_err());
+        assert!("alphabeta:2:speed=1".parse::<Spec>().is_err());
+        assert!("minimax".parse::<Spec>().is_err());
+    }
+}
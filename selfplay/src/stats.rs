@@ -0,0 +1,97 @@
+use std::fmt;
+
+
+// Results from the point of view of the first player
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats
+{
+    pub wins:   u32,
+    pub draws:  u32,
+    pub losses: u32,
+}
+
+// 95% of a normal distribution
+const Z: f64 = 1.96;
+
+impl Stats
+{
+    pub fn games(&self) -> u32
+    {
+        self.wins + self.draws + self.losses
+    }
+
+    // Points per game, a draw is half a point
+    pub fn score(&self) -> f64
+    {
+        (self.wins as f64 + self.draws as f64 / 2.) / self.games().max(1) as f64
+    }
+
+    // Normal approximation of the score's 95% confidence interval
+    pub fn interval(&self) -> (f64, f64)
+    {
+        let n = self.games().max(1) as f64;
+        let score = self.score();
+
+        let variance = (self.wins as f64 * (1. - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / n;
+        let margin = Z * (variance / n).sqrt();
+
+        ((score - margin).max(0.), (score + margin).min(1.))
+    }
+}
+
+// Elo difference giving the expected `score`
+pub fn elo(score: f64) -> f64
+{
+    let score = score.clamp(1e-3, 1. - 1e-3);
+    // Adding zero turns -0 into 0 for printing
+    -400. * (1. / score - 1.).log10() + 0.
+}
+
+impl fmt::Display for Stats
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        let (low, high) = self.interval();
+        writeln!(f, "games  {}", self.games())?;
+        writeln!(f, "w/d/l  {}/{}/{}", self.wins, self.draws, self.losses)?;
+        writeln!(f, "score  {:.3} [{low:.3}, {high:.3}]", self.score())?;
+        write!(f, "elo    {:+.0} [{:+.0}, {:+.0}]", elo(self.score()), elo(low), elo(high))
+    }
+}
+
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    fn test_interval()
+    {
+        let even = Stats {
+            wins: 50, draws: 0, losses: 50
+        };
+        assert_eq!(even.score(), 0.5);
+        assert!(elo(even.score()).abs() < 1e-9);
+
+        let (low, high) = even.interval();
+        assert!((0.5 - low - 0.098).abs() < 1e-3);
+        assert!((high - 0.5 - 0.098).abs() < 1e-3);
+
+        // More games, narrower interval
+        let more = Stats {
+            wins: 500, draws: 0, losses: 500
+        };
+        let (l, h) = more.interval();
+        assert!(h - l < high - low);
+
+        let clean = Stats {
+            wins: 10, draws: 0, losses: 0
+        };
+        assert_eq!(clean.interval(), (1., 1.));
+        assert!(elo(clean.score()) > 1000.);
+    }
+}
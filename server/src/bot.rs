@@ -0,0 +1,333 @@
+mod uhp;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use mongodb::bson::oid::ObjectId;
+use shared::{
+    engine::{generate_moves, side_to_move, AlphaBeta, Engine, TranspositionTable},
+    model::{AcceptGame, CreateGameFormResponse, GameResource, InternalMove, Move, Strength},
+};
+use tokio::{
+    select,
+    sync::{broadcast::error::RecvError, mpsc, oneshot, Mutex},
+    task,
+};
+use uhp::UhpEngine;
+
+use crate::{
+    config::Config,
+    database::{DatabaseError, DatabaseResult},
+    routing::submit_move,
+    State,
+};
+
+
+// The built in bots and the depth they search to
+pub const BOTS: [(&str, Strength, u32); 3] = [
+    ("easy-bot", Strength::Easy, 1),
+    ("medium-bot", Strength::Medium, 2),
+    ("hard-bot", Strength::Hard, 3),
+];
+
+const TABLE_SIZE: usize = 1 << 16;
+
+// Thinking time given to the external engines per move
+const UHP_MOVE_TIME: Duration = Duration::from_secs(5);
+
+// The external UHP engines from the config that could be started
+fn uhp_bots(config: &Config) -> Vec<(String, UhpEngine)>
+{
+    config
+        .uhp_bots
+        .iter()
+        .filter_map(|(name, command)| Some((name.clone(), UhpEngine::from_command(command)?)))
+        .collect()
+}
+
+
+// A challenge addressed to a bot, the result of accepting it is sent back
+#[derive(Debug)]
+pub struct Challenge
+{
+    pub creator: ObjectId,
+    pub game:    ObjectId,
+    pub bot:     ObjectId,
+    pub reply:   oneshot::Sender<DatabaseResult<AcceptGame>>,
+}
+
+fn engine(depth: u32) -> AlphaBeta
+{
+    let mut engine = AlphaBeta::new(depth);
+
+    // Too few positions are searched at depth 1 for a table to pay off
+    if depth > 1
+    {
+        engine.table = Some(TranspositionTable::new(TABLE_SIZE));
+    }
+    engine
+}
+
+
+#[derive(Clone)]
+enum BotEngine
+{
+    // The depth of the search
+    Builtin(u32),
+    // One process per bot, shared by all its games
+    Uhp(Arc<Mutex<UhpEngine>>),
+}
+
+impl BotEngine
+{
+    async fn best_move(self, game: GameResource) -> Option<InternalMove>
+    {
+        match self
+        {
+            BotEngine::Builtin(depth) =>
+            {
+                let mut engine = engine(depth);
+                let board = game.board;
+                task::spawn_blocking(move || engine.search(&board)).await.ok()?.best_move
+            },
+            BotEngine::Uhp(engine) =>
+            {
+                match engine.lock().await.best_move(&game.move_list, UHP_MOVE_TIME).await
+                {
+                    Ok(r#move) => Some(r#move),
+                    Err(e) =>
+                    {
+                        println!("uhp engine failed in game {}: {e}", game._id);
+                        None
+                    },
+                }
+            },
+        }
+    }
+}
+
+struct Bot
+{
+    name:   String,
+    engine: BotEngine,
+}
+
+struct Bots
+{
+    state: State,
+    bots:  HashMap<ObjectId, Bot>,
+    // Ongoing games against a bot, and the id of the bot
+    games: HashMap<ObjectId, ObjectId>,
+}
+
+impl Bots
+{
+    async fn new(state: State) -> Self
+    {
+        let mut bots = HashMap::new();
+
+        let builtin = BOTS.iter().map(|(name, strength, depth)| {
+            (name.to_string(), *strength, BotEngine::Builtin(*depth))
+        });
+        let external = uhp_bots(&state.config).into_iter().map(|(name, engine)| {
+            (name, Strength::External, BotEngine::Uhp(Arc::new(Mutex::new(engine))))
+        });
+
+        for (name, strength, engine) in builtin.chain(external)
+        {
+            match state.db().register_bot(&name, strength).await
+            {
+                Ok(id) =>
+                {
+                    bots.insert(id, Bot {
+                        name,
+                        engine,
+                    });
+                },
+                Err(e) => println!("could not register bot {name}: {e:?}"),
+            }
+        }
+
+        Self {
+            state,
+            bots,
+            games: HashMap::new(),
+        }
+    }
+
+    // Pick up the games that were going on when the server stopped
+    async fn resume(&mut self)
+    {
+        for bot in self.bots.keys().copied().collect::<Vec<_>>()
+        {
+            if let Ok(games) = self.state.db().get_users_games(bot).await
+            {
+                for game in games
+                {
+                    self.games.insert(game.game_object_id, bot);
+                    self.play(game.game_object_id).await;
+                }
+            }
+        }
+    }
+
+    async fn accept(&mut self, challenge: Challenge)
+    {
+        let Challenge {
+            creator,
+            game,
+            bot,
+            reply,
+        } = challenge;
+
+        let form = CreateGameFormResponse {
+            game,
+            creator,
+        };
+
+        let res = self.state.db().accept_game(bot, form).await;
+        let id = res.as_ref().ok().map(|accept| accept.object_id);
+        let _ = reply.send(res);
+
+        match id
+        {
+            // The bot might be white
+            Some(id) =>
+            {
+                self.games.insert(id, bot);
+                self.play(id).await;
+            },
+            // The invite was made for the bot, nobody else should get it
+            None => match self.state.db().remove_challenge(creator, game).await
+            {
+                Ok(()) | Err(DatabaseError::Conflict) => (),
+                Err(e) => println!("bot could not remove invite {game}: {e:?}"),
+            },
+        }
+    }
+
+    /*
+     * Start searching for a move if it is the bots turn in the game.
+     * The search runs in its own task and the move is submitted like any
+     * other move, which sends it back to us through the websocket channel.
+     */
+    async fn play(&mut self, game_id: ObjectId)
+    {
+        let bot_id = match self.games.get(&game_id)
+        {
+            Some(id) => *id,
+            None => return,
+        };
+
+        let game = match self.state.db().get_game_by_id(game_id).await
+        {
+            Ok(game) => game,
+            Err(e) =>
+            {
+                println!("bot could not get game {game_id}: {e:?}");
+                return;
+            },
+        };
+
+        if game.board.is_complete()
+        {
+            self.games.remove(&game_id);
+            return;
+        }
+
+        let bot = &self.bots[&bot_id];
+        if game.players[side_to_move(&game.board) as usize] != bot.name
+        {
+            return;
+        }
+
+        let engine = bot.engine.clone();
+        let state = self.state.clone();
+        let move_number = game.move_list.len();
+        tokio::spawn(async move {
+            // Without a legal move the bot has to pass, there is nothing to
+            // search
+            let r#move = match generate_moves(&game.board).is_empty()
+            {
+                true => None,
+                false => match engine.best_move(game).await
+                {
+                    Some(r#move) => Some(r#move),
+                    // Waiting for a move that never comes would leave the game
+                    // hanging, the bot gives up instead
+                    None =>
+                    {
+                        println!("bot found no move in game {game_id}, resigning");
+                        match state.db().resign_game(game_id, bot_id).await
+                        {
+                            Ok(()) => finish(state, game_id),
+                            Err(e) => println!("bot could not resign game {game_id}: {e:?}"),
+                        }
+                        return;
+                    },
+                },
+            };
+
+            let r#move = Move {
+                game_id,
+                r#move,
+                move_number,
+            };
+
+            if let Err(e) = submit_move(&state, bot_id, r#move).await
+            {
+                println!("bot could not play in game {game_id}: {e:?}");
+                return;
+            }
+
+            // The frontend completes the game after a winning move, a bot has
+            // to do it itself
+            if let Ok(game) = state.db().get_game_by_id(game_id).await
+            {
+                if game.board.is_complete() && state.db().complete_game(game_id).await.is_ok()
+                {
+                    finish(state, game_id);
+                }
+            }
+        });
+    }
+}
+
+// The work done on every game once it is over
+fn finish(state: State, game_id: ObjectId)
+{
+    crate::explorer::spawn_index(state.clone());
+    crate::puzzle::spawn_mining(state.clone(), game_id);
+    crate::review::spawn_review(state, game_id);
+}
+
+
+/*
+ * Background task running the bots. Accepts the challenges sent to the bots
+ * and listens to every played move to find out when it is a bots turn.
+ */
+pub async fn spawn_bots(state: State, mut challenges: mpsc::Receiver<Challenge>)
+{
+    let mut moves = state.tx.subscribe();
+    let mut bots = Bots::new(state).await;
+    bots.resume().await;
+
+    loop
+    {
+        select! {
+            Some(challenge) = challenges.recv() => bots.accept(challenge).await,
+
+            msg = moves.recv() => match msg
+            {
+                Ok(msg) => bots.play(msg.r#move.game_id).await,
+                // Some moves were missed, check every game instead
+                Err(RecvError::Lagged(_)) =>
+                {
+                    for game in bots.games.keys().copied().collect::<Vec<_>>()
+                    {
+                        bots.play(game).await;
+                    }
+                },
+                Err(RecvError::Closed) => break,
+            },
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use std::{fmt, io, process::Stdio, time::Duration};
+
+use shared::{
+    model::InternalMove,
+    uhp::{bestmove_command, newgame_command, parse_bestmove, EngineError, Position, Response},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    process::{Child, ChildStdin, ChildStdout, Command},
+    time,
+};
+
+
+// Extra time given to the engine on top of the budget before it is
+// considered hanging
+const GRACE: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub enum UhpEngineError
+{
+    Io(io::Error),
+    Timeout,
+    Engine(EngineError),
+    Pass,
+}
+
+impl fmt::Display for UhpEngineError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            UhpEngineError::Io(e) => write!(f, "io error: {e}"),
+            UhpEngineError::Timeout => write!(f, "no answer in time"),
+            UhpEngineError::Engine(e) => write!(f, "{e}"),
+            UhpEngineError::Pass => write!(f, "engine passed"),
+        }
+    }
+}
+
+impl From<io::Error> for UhpEngineError
+{
+    fn from(e: io::Error) -> Self
+    {
+        UhpEngineError::Io(e)
+    }
+}
+
+impl From<EngineError> for UhpEngineError
+{
+    fn from(e: EngineError) -> Self
+    {
+        UhpEngineError::Engine(e)
+    }
+}
+
+pub type UhpEngineResult<T> = Result<T, UhpEngineError>;
+
+
+struct Process
+{
+    child:  Child,
+    stdin:  ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+}
+
+/*
+ * An external engine speaking the Universal Hive Protocol.
+ * The process is started on the first request and started again if it
+ * crashes or stops answering.
+ */
+pub struct UhpEngine
+{
+    program: String,
+    args:    Vec<String>,
+    process: Option<Process>,
+}
+
+impl UhpEngine
+{
+    pub fn new(program: String, args: Vec<String>) -> Self
+    {
+        Self {
+            program,
+            args,
+            process: None,
+        }
+    }
+
+    // `command` is the program followed by its arguments
+    pub fn from_command(command: &str) -> Option<Self>
+    {
+        let mut parts = command.split_whitespace().map(String::from);
+        Some(Self::new(parts.next()?, parts.collect()))
+    }
+
+    async fn spawn(&self) -> UhpEngineResult<Process>
+    {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap()).lines();
+
+        let mut process = Process {
+            child,
+            stdin,
+            stdout,
+        };
+
+        // The engine greets with its id
+        read_response(&mut process, GRACE).await?;
+        Ok(process)
+    }
+
+    async fn kill(&mut self)
+    {
+        if let Some(mut process) = self.process.take()
+        {
+            let _ = process.child.kill().await;
+        }
+    }
+
+    async fn send(&mut self, cmd: &str, timeout: Duration) -> UhpEngineResult<Vec<String>>
+    {
+        if self.process.is_none()
+        {
+            self.process = Some(self.spawn().await?);
+        }
+        let process = self.process.as_mut().unwrap();
+
+        process.stdin.write_all(format!("{cmd}\n").as_bytes()).await?;
+        process.stdin.flush().await?;
+
+        read_response(process, timeout).await
+    }
+
+    async fn try_best_move(
+        &mut self,
+        move_list: &[Option<InternalMove>],
+        time: Duration,
+    ) -> UhpEngineResult<InternalMove>
+    {
+        let pos = Position::from_moves(move_list);
+        self.send(&newgame_command(&pos), GRACE).await?;
+
+        let lines = self.send(&bestmove_command(time), time + GRACE).await?;
+        parse_bestmove(&pos, &lines)?.ok_or(UhpEngineError::Pass)
+    }
+
+    /*
+     * Ask the engine for a move in the game given by `move_list`.
+     * If anything goes wrong the process is restarted and asked once more,
+     * a second failure is left to the caller.
+     */
+    pub async fn best_move(
+        &mut self,
+        move_list: &[Option<InternalMove>],
+        time: Duration,
+    ) -> UhpEngineResult<InternalMove>
+    {
+        match self.try_best_move(move_list, time).await
+        {
+            Err(_) =>
+            {
+                self.kill().await;
+                let res = self.try_best_move(move_list, time).await;
+                if res.is_err()
+                {
+                    self.kill().await;
+                }
+                res
+            },
+            res => res,
+        }
+    }
+}
+
+async fn read_response(process: &mut Process, timeout: Duration) -> UhpEngineResult<Vec<String>>
+{
+    let read = async {
+        let mut response = Response::default();
+        loop
+        {
+            match process.stdout.next_line().await?
+            {
+                Some(line) if response.push(&line) => return Ok(response.finish()?),
+                Some(_) => (),
+                None =>
+                {
+                    return Err(UhpEngineError::Io(io::ErrorKind::UnexpectedEof.into()));
+                },
+            }
+        }
+    };
+
+    time::timeout(timeout, read).await.unwrap_or(Err(UhpEngineError::Timeout))
+}
+
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    // Answers every bestmove with `answer`, and exits after `lives` of them
+    fn fake_engine(answer: &str, lives: usize) -> UhpEngine
+    {
+        let script = format!(
+            "echo id fake; echo ok; n=0; while read l; do case $l in bestmove*) echo '{answer}'; \
+             n=$((n+1));; esac; echo ok; [ $n -ge {lives} ] && exit; done"
+        );
+        UhpEngine::new("sh".into(), vec!["-c".into(), script])
+    }
+
+    #[tokio::test]
+    async fn test_best_move()
+    {
+        let mut engine = fake_engine("wQ", 100);
+        let r#move = engine.best_move(&[], Duration::from_secs(1)).await.unwrap();
+
+        assert_eq!(r#move.sq, (0, 0, 0));
+        assert_eq!(r#move.old_sq, None);
+
+        // Passing is not allowed with moves to play
+        let mut engine = fake_engine("pass", 100);
+        assert!(matches!(
+            engine.best_move(&[], Duration::from_secs(1)).await,
+            Err(UhpEngineError::Engine(EngineError::BadMove(_)))
+        ));
+
+        let mut engine = fake_engine("err no", 100);
+        assert!(matches!(
+            engine.best_move(&[], Duration::from_secs(1)).await,
+            Err(UhpEngineError::Engine(EngineError::Refused(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_restart_after_crash()
+    {
+        // Dies after every answer
+        let mut engine = fake_engine("wQ", 1);
+
+        for _ in 0..3
+        {
+            let r#move = engine.best_move(&[], Duration::from_secs(1)).await;
+            assert!(r#move.is_ok());
+        }
+    }
+}
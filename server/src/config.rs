@@ -0,0 +1,352 @@
+use std::{collections::BTreeMap, fmt, fs, str::FromStr};
+
+use serde::Deserialize;
+
+
+pub const USAGE: &str = "usage: server [--config file]
+
+Every setting can also be given in the environment:
+    HIVE_HTTP_PORT, HIVE_WEBSOCKET_PORT, HIVE_MAX_CREATE_GAME,
+    HIVE_ANALYSIS_WORKERS, HIVE_DATABASE_BACKEND, HIVE_DATABASE_URL,
+    HIVE_DATABASE_PATH, HIVE_DATABASE_USER, HIVE_DATABASE_PASSWORD, HIVE_DATABASE_NAME and
+    UHP_BOTS=\"name=command;name=command\"";
+
+#[derive(Debug)]
+pub enum ConfigError
+{
+    Usage(String),
+    Read(String, std::io::Error),
+    Parse(toml::de::Error),
+    // The variable and its value
+    Env(String, String),
+    Invalid(&'static str),
+}
+
+impl fmt::Display for ConfigError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            ConfigError::Usage(s) => write!(f, "{s}"),
+            ConfigError::Read(path, e) => write!(f, "could not read {path}: {e}"),
+            ConfigError::Parse(e) => write!(f, "invalid config file: {e}"),
+            ConfigError::Env(var, value) => write!(f, "invalid value '{value}' for {var}"),
+            ConfigError::Invalid(s) => write!(f, "invalid config: {s}"),
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config
+{
+    pub http_port:        u16,
+    pub websocket_port:   u16,
+    // Open game invites a user can have at once
+    pub max_create_game:  u32,
+    // Analysis searches running at once
+    pub analysis_workers: usize,
+    pub database:         DatabaseConfig,
+    // External UHP engines to run as bots, `name = "command"`
+    pub uhp_bots:         BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend
+{
+    Mongo,
+    // A single file at `path`, no database server needed
+    Sqlite,
+    // Nothing is kept when the server stops
+    Memory,
+}
+
+impl FromStr for Backend
+{
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        match s
+        {
+            "mongo" => Ok(Backend::Mongo),
+            "sqlite" => Ok(Backend::Sqlite),
+            "memory" => Ok(Backend::Memory),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig
+{
+    pub backend:  Backend,
+    pub url:      String,
+    pub path:     String,
+    pub user:     String,
+    pub password: String,
+    pub name:     String,
+}
+
+impl Default for Config
+{
+    fn default() -> Self
+    {
+        Self {
+            http_port:        5000,
+            websocket_port:   5001,
+            max_create_game:  25,
+            analysis_workers: 2,
+            database:         DatabaseConfig::default(),
+            uhp_bots:         BTreeMap::new(),
+        }
+    }
+}
+
+// The database from docker-compose.yml, running locally
+impl Default for DatabaseConfig
+{
+    fn default() -> Self
+    {
+        Self {
+            backend:  Backend::Mongo,
+            url:      "mongodb://localhost:27017".into(),
+            path:     "hive.db".into(),
+            user:     "root".into(),
+            password: "rootpassword".into(),
+            name:     "live".into(),
+        }
+    }
+}
+
+impl FromStr for Config
+{
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        toml::from_str(s).map_err(ConfigError::Parse)
+    }
+}
+
+fn parse<T: FromStr>(var: &str, value: String) -> Result<T, ConfigError>
+{
+    value.parse().map_err(|_| ConfigError::Env(var.to_string(), value))
+}
+
+impl Config
+{
+    /*
+     * The file given with `--config`, or the defaults, with the environment
+     * variables on top.
+     */
+    pub fn load<I: Iterator<Item = String>>(mut args: I) -> Result<Self, ConfigError>
+    {
+        let mut config = match (args.next(), args.next(), args.next())
+        {
+            (None, ..) => Self::default(),
+            (Some(flag), ..) if flag != "--config" =>
+            {
+                return Err(ConfigError::Usage(format!("unknown flag {flag}")))
+            },
+            (_, None, _) => return Err(ConfigError::Usage("missing value for --config".into())),
+            (_, _, Some(arg)) => return Err(ConfigError::Usage(format!("unexpected {arg}"))),
+            (_, Some(path), None) =>
+            {
+                let s = fs::read_to_string(&path).map_err(|e| ConfigError::Read(path, e))?;
+                s.parse()?
+            },
+        };
+
+        config.apply_env(std::env::vars())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn apply_env<I: Iterator<Item = (String, String)>>(
+        &mut self,
+        vars: I,
+    ) -> Result<(), ConfigError>
+    {
+        for (var, value) in vars
+        {
+            match var.as_str()
+            {
+                "HIVE_HTTP_PORT" => self.http_port = parse(&var, value)?,
+                "HIVE_WEBSOCKET_PORT" => self.websocket_port = parse(&var, value)?,
+                "HIVE_MAX_CREATE_GAME" => self.max_create_game = parse(&var, value)?,
+                "HIVE_ANALYSIS_WORKERS" => self.analysis_workers = parse(&var, value)?,
+                "HIVE_DATABASE_BACKEND" => self.database.backend = parse(&var, value)?,
+                "HIVE_DATABASE_URL" => self.database.url = value,
+                "HIVE_DATABASE_PATH" => self.database.path = value,
+                "HIVE_DATABASE_USER" => self.database.user = value,
+                "HIVE_DATABASE_PASSWORD" => self.database.password = value,
+                "HIVE_DATABASE_NAME" => self.database.name = value,
+
+                // Separated by `;`, UHP_BOTS="mzinga=/usr/bin/MzingaEngine;test=./uhp --flag"
+                "UHP_BOTS" =>
+                {
+                    for bot in value.split(';').filter(|bot| !bot.trim().is_empty())
+                    {
+                        let (name, command) = bot
+                            .split_once('=')
+                            .ok_or_else(|| ConfigError::Env(var.clone(), bot.to_string()))?;
+                        self.uhp_bots.insert(name.trim().to_string(), command.trim().to_string());
+                    }
+                },
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), ConfigError>
+    {
+        let invalid = |s| Err(ConfigError::Invalid(s));
+
+        if self.http_port == 0 || self.websocket_port == 0
+        {
+            return invalid("ports can not be 0");
+        }
+        if self.http_port == self.websocket_port
+        {
+            return invalid("the http and websocket ports must differ");
+        }
+        if self.analysis_workers == 0
+        {
+            return invalid("analysis_workers must be at least 1");
+        }
+        if self.database.backend == Backend::Mongo
+            && !self.database.url.starts_with("mongodb://")
+            && !self.database.url.starts_with("mongodb+srv://")
+        {
+            return invalid("the database url must start with mongodb:// or mongodb+srv://");
+        }
+        if self.database.backend == Backend::Sqlite && self.database.path.is_empty()
+        {
+            return invalid("the sqlite backend needs a path");
+        }
+        // Mongo does not allow these in database names
+        if self.database.name.is_empty()
+            || self.database.name.contains(&['/', '\\', '.', ' ', '"', '$'][..])
+        {
+            return invalid("bad database name");
+        }
+        if self
+            .uhp_bots
+            .iter()
+            .any(|(name, command)| name.is_empty() || command.is_empty())
+        {
+            return invalid("uhp bots need both a name and a command");
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    fn vars(vars: &[(&str, &str)]) -> impl Iterator<Item = (String, String)>
+    {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn test_parse_file()
+    {
+        let config: Config = "
+            http_port = 8080
+            max_create_game = 5
+
+            [database]
+            url = \"mongodb://db:27017\"
+
+            [uhp_bots]
+            mzinga = \"MzingaEngine\"
+        "
+        .parse()
+        .unwrap();
+
+        assert_eq!(config.http_port, 8080);
+        assert_eq!(config.websocket_port, 5001);
+        assert_eq!(config.max_create_game, 5);
+        assert_eq!(config.database.url, "mongodb://db:27017");
+        assert_eq!(config.database.name, "live");
+        assert_eq!(config.uhp_bots["mzinga"], "MzingaEngine");
+        assert!(config.validate().is_ok());
+
+        assert!(matches!("http_prot = 1".parse::<Config>(), Err(ConfigError::Parse(_))));
+        assert!(matches!("http_port = \"x\"".parse::<Config>(), Err(ConfigError::Parse(_))));
+    }
+
+    #[test]
+    fn test_env_overrides()
+    {
+        let mut config = Config::default();
+        config
+            .apply_env(vars(&[
+                ("HIVE_HTTP_PORT", "80"),
+                ("HIVE_DATABASE_NAME", "test"),
+                ("UHP_BOTS", "mzinga=MzingaEngine; uhp = ./uhp --flag;"),
+                ("PATH", "/bin"),
+            ]))
+            .unwrap();
+
+        assert_eq!(config.http_port, 80);
+        assert_eq!(config.database.name, "test");
+        assert_eq!(config.uhp_bots["mzinga"], "MzingaEngine");
+        assert_eq!(config.uhp_bots["uhp"], "./uhp --flag");
+
+        let res = config.apply_env(vars(&[("HIVE_WEBSOCKET_PORT", "port")]));
+        assert!(matches!(res, Err(ConfigError::Env(var, _)) if var == "HIVE_WEBSOCKET_PORT"));
+        let res = config.apply_env(vars(&[("UHP_BOTS", "mzinga")]));
+        assert!(matches!(res, Err(ConfigError::Env(..))));
+    }
+
+    #[test]
+    fn test_validate()
+    {
+        assert!(Config::default().validate().is_ok());
+
+        let memory: Config = "[database]\nbackend = \"memory\"\nurl = \"\"".parse().unwrap();
+        assert_eq!(memory.database.backend, Backend::Memory);
+        assert!(memory.validate().is_ok());
+
+        let invalid = [
+            "websocket_port = 5000",
+            "http_port = 0",
+            "analysis_workers = 0",
+            "[database]\nurl = \"localhost:27017\"",
+            "[database]\nname = \"a.b\"",
+            "[database]\nname = \"\"",
+            "[database]\nbackend = \"sqlite\"\npath = \"\"",
+            "[uhp_bots]\nempty = \"\"",
+        ];
+        for s in invalid
+        {
+            let config: Config = s.parse().unwrap();
+            assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_args()
+    {
+        let args = |s: &str| Config::load(s.split_whitespace().map(String::from));
+
+        assert!(matches!(args("--config"), Err(ConfigError::Usage(_))));
+        assert!(matches!(args("--port 80"), Err(ConfigError::Usage(_))));
+        assert!(matches!(args("--config a b"), Err(ConfigError::Usage(_))));
+        assert!(matches!(args("--config /does/not/exist"), Err(ConfigError::Read(..))));
+    }
+}
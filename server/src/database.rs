@@ -1,29 +1,35 @@
-use const_format::formatcp;
-use futures::stream::StreamExt;
-use mongodb::{
-    bson::{self, doc, oid::ObjectId},
-    error::Error,
-    options::{ClientOptions, Credential},
-    Client, Database,
+mod memory;
+mod mongo;
+mod sqlite;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+pub use memory::Memory;
+pub use mongo::Mongo;
+use mongodb::bson::{oid::ObjectId, DateTime};
+use shared::{
+    engine::{canonical_move, is_legal_turn, play_turn, side_to_move, Outcome, Symmetry},
+    model::*,
 };
-use shared::model::*;
+pub use sqlite::Sqlite;
+use tokio::task;
 
-use crate::model::{game::Game, user::User};
-
-
-pub const LIVE: &str = "live";
-pub const MAX_CREATE_GAME: u32 = 25;
+use crate::{
+    config::{Backend, DatabaseConfig},
+    model::{game::Game, position::PositionMove, session::Token, user::User},
+};
 
 
-pub const PROTOCOL: &str = "mongodb";
+// Sessions run out this long after logging in
+pub const SESSION_DAYS: i64 = 30;
 
-pub const URL: &str = if cfg!(debug_assertions) { "0.0.0.0" } else { "db" };
-pub const PORT: usize = 27017;
-pub const DB_PATH: &str = formatcp!("{PROTOCOL}://{URL}:{PORT}");
+// Only the start of a game goes into the opening index
+pub const OPENING_MOVES: usize = 40;
 
-// collections types
-pub const USERS: &str = "users";
-pub const GAMES: &str = "games";
+// Items in a page of a listing when the client does not ask for a number,
+// and the most it can ask for
+pub const PAGE_SIZE: usize = 20;
+pub const MAX_PAGE_SIZE: usize = 100;
 
 
 #[derive(Debug)]
@@ -32,375 +38,457 @@ pub enum DatabaseError
     UserAlreadyExist,
     UserDontExist,
     DbError(mongodb::error::Error),
+    SqliteError(rusqlite::Error),
     NoDocumentFound,
     TooManyGames,
     GameNotComplete,
+    IllegalMove,
+    NotYourTurn,
+    InvalidSession,
+    // What the client acted on has changed since it saw it, a move made on
+    // an old copy of a game or an invite someone else took first
+    Conflict,
 }
 
 
 pub type DatabaseResult<T> = Result<T, DatabaseError>;
 
 
-pub async fn connect() -> Result<Client, Error>
+/*
+ * What a page of games or challenges is made of. Ids start with the second
+ * they were made in, so ordering by id is ordering by date, and both the
+ * date range and the cursor become bounds on the id.
+ */
+#[derive(Debug, Clone)]
+pub struct Listing
 {
-    let mut client_options = ClientOptions::parse(DB_PATH).await?;
-    client_options.app_name = Some("My App".to_string());
-
-    client_options.credential = Some(
-        Credential::builder()
-            .username(Some("root".to_string()))
-            .password(Some("rootpassword".to_string()))
-            .build(),
-    );
-
-    Client::with_options(client_options)
+    // Finished games or ongoing ones, challenges have no such thing
+    pub complete: bool,
+    pub player:   Option<String>,
+    pub result:   Option<GameResult>,
+    // Only ids strictly between the two
+    pub after:    Option<ObjectId>,
+    pub before:   Option<ObjectId>,
+    pub sort:     SortOrder,
+    pub limit:    usize,
 }
 
-pub async fn login(db: Database, cred: UserCredentials) -> DatabaseResult<ObjectId>
+impl Default for Listing
 {
-    let col = db.collection::<User>(USERS);
-
-    // Check if user with same name exists
-    let password_hash = hash(&cred.password);
-    let filter = doc! { "name": cred.name.as_str(), "password_hash": password_hash.as_str() };
-    match col.find_one(filter, None).await?
+    fn default() -> Self
     {
-        Some(user) => Ok(user._id),
-        None => Err(DatabaseError::UserDontExist),
+        Self {
+            complete: false,
+            player:   None,
+            result:   None,
+            after:    None,
+            before:   None,
+            sort:     SortOrder::Newest,
+            limit:    PAGE_SIZE,
+        }
     }
 }
 
-
-pub async fn register_user(db: Database, cred: UserCredentials) -> DatabaseResult<ObjectId>
+impl Listing
 {
-    let col = db.collection::<User>(USERS);
-    let user = User::from_cred(cred);
-
-    // Check if user with same name exists
-    let filter = doc! { "name": user.name.as_str() };
-    match col.find_one(filter, None).await?
+    /*
+     * The listing a client asked for, or what was wrong with the query. A
+     * ruleset other than the one played here matches nothing, that is None.
+     */
+    pub fn from_query(query: &ListQuery, complete: bool) -> Result<Option<Self>, String>
     {
-        Some(_) => Err(DatabaseError::UserAlreadyExist),
-        None => match col.insert_one(&user, None).await
+        use std::convert::TryFrom;
+
+        use chrono::{Duration, NaiveDate};
+
+        if matches!(&query.ruleset, Some(ruleset) if ruleset != shared::uhp::GAME_TYPE)
         {
-            Ok(_) => Ok(user._id),
-            Err(e) => Err(DatabaseError::DbError(e)),
-        },
-    }
-}
+            return Ok(None);
+        }
 
-#[allow(dead_code)]
-pub async fn find_user_by_id(db: Database, id: ObjectId) -> DatabaseResult<User>
-{
-    let col = db.collection::<User>(USERS);
+        // Ids keep the seconds in four bytes, from 1970 until 2106
+        let seconds = |s: &str, days: i64| -> Result<u32, String> {
+            let date = NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .map_err(|_| format!("bad date: {s}"))?
+                + Duration::days(days);
+            u32::try_from(date.and_hms(0, 0, 0).timestamp())
+                .map_err(|_| format!("date out of range: {s}"))
+        };
+        let id = |seconds: u32, rest: u8| {
+            let mut bytes = [rest; 12];
+            bytes[..4].copy_from_slice(&seconds.to_be_bytes());
+            ObjectId::from_bytes(bytes)
+        };
 
-    let filter = doc! { "_id": id };
-    match col.find_one(filter, None).await?
-    {
-        Some(user) => Ok(user),
-        None => Err(DatabaseError::UserDontExist),
+        let mut listing = Listing {
+            complete,
+            player: query.player.clone(),
+            result: query.result,
+            sort: query.sort,
+            limit: query.limit.map_or(PAGE_SIZE, |limit| limit as usize).clamp(1, MAX_PAGE_SIZE),
+            ..Listing::default()
+        };
+
+        // The bounds leave themselves out, so the start of the range is the
+        // last id of the second before it
+        if let Some(from) = &query.from
+        {
+            listing.after = Some(seconds(from, 0)?).filter(|s| *s > 0).map(|s| id(s - 1, 0xff));
+        }
+        if let Some(to) = &query.to
+        {
+            listing.before = Some(id(seconds(to, 1)?, 0));
+        }
+
+        if let Some(cursor) = &query.cursor
+        {
+            let cursor =
+                ObjectId::parse_str(cursor).map_err(|_| format!("bad cursor: {cursor}"))?;
+            match listing.sort
+            {
+                SortOrder::Newest =>
+                {
+                    listing.before = Some(listing.before.map_or(cursor, |id| id.min(cursor)))
+                },
+                SortOrder::Oldest =>
+                {
+                    listing.after = Some(listing.after.map_or(cursor, |id| id.max(cursor)))
+                },
+            }
+        }
+        Ok(Some(listing))
     }
-}
-async fn count_create_game(db: Database, id: &ObjectId) -> DatabaseResult<u32>
-{
-    let col = db.collection::<User>(USERS);
-    let sum = "sum";
-    let r#match = doc! { "$match": { "_id": &id } };
-    let crit = doc! { "$project": { sum: { "$size": "$create_games" } } };
 
-    match col.aggregate([r#match, crit], None).await?.next().await
+    fn includes(&self, id: ObjectId) -> bool
     {
-        Some(doc) =>
-        {
-            let num = doc?.get(sum).unwrap().as_i32().unwrap() as u32;
-            Ok(num)
-        },
-        None => Err(DatabaseError::NoDocumentFound),
+        self.after.map_or(true, |after| id > after)
+            && self.before.map_or(true, |before| id < before)
     }
 }
 
-pub async fn create_game(db: Database, user_id: ObjectId) -> DatabaseResult<ObjectId>
+/*
+ * The page out of at most `limit + 1` items in order, the one too many tells
+ * there is a next page.
+ */
+fn page<T, F: Fn(&T) -> ObjectId>(mut items: Vec<T>, limit: usize, id: F) -> Page<T>
 {
-    let col = db.collection::<User>(USERS);
-
-    if count_create_game(db, &user_id).await? > MAX_CREATE_GAME
+    let next = if items.len() > limit
+    {
+        items.truncate(limit);
+        items.last().map(|item| id(item).to_hex())
+    }
+    else
     {
-        return Err(DatabaseError::TooManyGames);
+        None
+    };
+    Page {
+        items,
+        next,
     }
+}
 
-    let user = doc! { "_id": &user_id };
 
-    let id = ObjectId::new();
-    let update = doc! { "$push": { "create_games": id } };
+/*
+ * Everything the server stores, the users and their sessions, game
+ * challenges, games, the opening index and puzzles. See `Mongo`, `Sqlite`
+ * and `Memory`.
+ */
+#[async_trait]
+pub trait Storage: Send + Sync
+{
+    // A wrong password looks the same as a missing user
+    async fn login(&self, cred: UserCredentials) -> DatabaseResult<ObjectId>;
+    async fn register_user(&self, cred: UserCredentials) -> DatabaseResult<ObjectId>;
+    // Replaces the hash of a password, see `hash`
+    async fn set_password_hash(&self, user: ObjectId, password_hash: &str) -> DatabaseResult<()>;
+    // Register a bot account, or get the id of the existing one.
+    // Fails if a human already has the name.
+    async fn register_bot(&self, name: &str, strength: Strength) -> DatabaseResult<ObjectId>;
+    async fn get_bots(&self) -> DatabaseResult<Vec<User>>;
+    async fn find_user_by_id(&self, id: ObjectId) -> DatabaseResult<User>;
+    async fn find_user_by_name(&self, name: &str) -> DatabaseResult<User>;
+
+    async fn create_session(&self, user: ObjectId) -> DatabaseResult<Session>;
+    // The user of a session that has not expired yet
+    async fn find_session(&self, token: &str) -> DatabaseResult<ObjectId>;
+    // Logs the session out, a token that is gone already is no error
+    async fn delete_session(&self, token: &str) -> DatabaseResult<()>;
+
+    // `limit` is how many open invites the user can have
+    async fn create_game(&self, user_id: ObjectId, limit: u32) -> DatabaseResult<ObjectId>;
+    async fn accept_game(
+        &self,
+        user: ObjectId,
+        form: CreateGameFormResponse,
+    ) -> DatabaseResult<AcceptGame>;
+    // The user who made the open invite `id`
+    async fn find_challenge_creator(&self, id: ObjectId) -> DatabaseResult<ObjectId>;
+    // Takes back the open invite `id` of `creator`, a conflict if it is gone
+    async fn remove_challenge(&self, creator: ObjectId, id: ObjectId) -> DatabaseResult<()>;
+    // The open invites of everyone but `id`
+    async fn home(&self, id: ObjectId) -> DatabaseResult<Vec<CreateGameChallengeBundle>>;
+    // A page of the open invites of everyone but `id`
+    async fn find_challenges(
+        &self,
+        id: ObjectId,
+        listing: &Listing,
+    ) -> DatabaseResult<Page<CreateGameChallenge>>;
+
+    async fn get_old_games(&self) -> DatabaseResult<Vec<OnGoingGame>>;
+    async fn get_users_games(&self, id: ObjectId) -> DatabaseResult<Vec<OnGoingGame>>;
+    async fn get_active_games(&self) -> DatabaseResult<Vec<OnGoingGame>>;
+    async fn get_game_by_id(&self, id: ObjectId) -> DatabaseResult<GameResource>;
+    async fn find_games(&self, listing: &Listing) -> DatabaseResult<Page<OnGoingGame>>;
+    async fn play_move(&self, player_id: ObjectId, r#move: Move) -> DatabaseResult<()>;
+    // Stores a game made with `Game::imported`
+    async fn import_game(&self, game: Game) -> DatabaseResult<ObjectId>;
+    async fn complete_game(&self, game_id: ObjectId) -> DatabaseResult<()>;
+    // Ends a game going on, the other player wins
+    async fn resign_game(&self, game_id: ObjectId, player_id: ObjectId) -> DatabaseResult<()>;
+    async fn set_review(&self, game_id: ObjectId, review: Review) -> DatabaseResult<()>;
+
+    // Adds every completed game that is not in the opening index yet, each
+    // game is counted whole or not at all
+    async fn index_games(&self) -> DatabaseResult<usize>;
+    // The moves played from `board` in the opening index, most played first
+    async fn explore(&self, board: &Board) -> DatabaseResult<Vec<ExplorerMove>>;
+
+    // A game gives at most one puzzle, storing another one from the same game
+    // does nothing
+    async fn add_puzzle(
+        &self,
+        game: ObjectId,
+        moves: &[Option<InternalMove>],
+        length: u32,
+    ) -> DatabaseResult<()>;
+    async fn get_puzzle(&self, id: ObjectId) -> DatabaseResult<PuzzleResource>;
+    async fn random_puzzle(&self) -> DatabaseResult<PuzzleResource>;
+}
 
-    match col.update_one(user, update, None).await
+// The storage the config asks for
+pub async fn open(config: &DatabaseConfig) -> DatabaseResult<Arc<dyn Storage>>
+{
+    Ok(match config.backend
     {
-        Ok(_) => Ok(id),
-        Err(e) => Err(e.into()),
-    }
+        Backend::Mongo => Arc::new(Mongo::connect(config).await?),
+        Backend::Sqlite => Arc::new(Sqlite::open(&config.path)?),
+        Backend::Memory => Arc::new(Memory::default()),
+    })
 }
 
-async fn remove_user_create_game(
-    db: Database,
-    creator: &ObjectId,
-    game: &ObjectId,
-) -> DatabaseResult<()>
+
+/*
+ * A new session for the user. The token is random and the only way to act
+ * as the user, so it never comes from anything the client can guess.
+ */
+fn new_session(user: ObjectId) -> Token
 {
-    let col = db.collection::<User>(USERS);
+    let mut bytes = [0_u8; 32];
+    getrandom::getrandom(&mut bytes).expect("random");
+    let token = bytes.iter().map(|b| format!("{b:02x}")).collect();
 
-    col.update_one(doc! { "_id": creator }, doc! { "$pull": { "create_games": game } }, None)
-        .await?;
-    Ok(())
+    let expires = DateTime::now().timestamp_millis() + SESSION_DAYS * 24 * 60 * 60 * 1000;
+    Token {
+        _id: token,
+        user,
+        expires: DateTime::from_millis(expires),
+    }
 }
 
-async fn add_game_id_to_users(
-    db: Database,
-    id: &ObjectId,
-    user1: &ObjectId,
-    user2: &ObjectId,
-) -> DatabaseResult<()>
+// The players of a new game, white first. Who starts is a coin flip
+fn colors(creator: ObjectId, user: ObjectId) -> [ObjectId; 2]
 {
-    let col = db.collection::<User>(USERS);
-
-    let user = doc! {"$or": [ {"_id": &user1}, {"_id": &user2} ]};
-    let update = doc! { "$push": { "active_games": id } };
+    let mut byte = [0_u8];
+    getrandom::getrandom(&mut byte).expect("random");
 
-    match col.update_many(user, update, None).await
+    if byte[0] >= 128
     {
-        Ok(_) => Ok(()),
-        Err(e) => Err(e.into()),
+        [creator, user]
+    }
+    else
+    {
+        [user, creator]
     }
 }
 
-pub async fn accept_game(db: Database, form: CreateGameFormResponse) -> DatabaseResult<AcceptGame>
+/*
+ * Plays the move if it is the player's turn and the move is legal. The move
+ * has to be made on the game as it is now, one made on an older copy, from
+ * another tab or a double click, is a conflict.
+ */
+fn play(game: &mut Game, player_id: ObjectId, r#move: Move) -> DatabaseResult<()>
 {
-    let CreateGameFormResponse {
-        user,
-        game,
-        creator,
-    } = form;
-
-    remove_user_create_game(db.clone(), &creator, &game).await?;
-
-    let games = db.collection::<Game>(GAMES);
-
-
-    // @NOTE: Take a look at this in the future haha Xdd
-    use getrandom::getrandom;
-    let mut byte = [0_u8];
-    getrandom(&mut byte).expect("random");
-
-    let players = if byte[0] >= 128 { [creator, user] } else { [user, creator] };
+    if r#move.move_number != game.move_list.len()
+    {
+        return Err(DatabaseError::Conflict);
+    }
+    if game.player_id(side_to_move(&game.board)) != player_id
+    {
+        return Err(DatabaseError::NotYourTurn);
+    }
+    if !is_legal_turn(&game.board, &r#move.r#move)
+    {
+        return Err(DatabaseError::IllegalMove);
+    }
 
-    let (u1, u2) = tokio::join!(
-        find_user_by_id(db.clone(), players[0]),
-        find_user_by_id(db.clone(), players[1])
-    );
-    let users = [u1?, u2?];
+    game.board.play_move(r#move.clone()).map_err(|_| DatabaseError::IllegalMove)?;
+    game.move_list.push(r#move.r#move);
+    game.version += 1;
+    game.result = game.board_result();
+    Ok(())
+}
 
-    let id = games
-        .insert_one(Game::new(users), None)
-        .await?
-        .inserted_id
-        .as_object_id()
-        .unwrap();
+// The game is given to the opponent of `player_id`
+fn resign(game: &mut Game, player_id: ObjectId)
+{
+    let color = match game.player_id(Color::White) == player_id
+    {
+        true => Color::White,
+        false => Color::Black,
+    };
 
-    add_game_id_to_users(db.clone(), &id, &creator, &user).await?;
+    game.complete = true;
+    game.result = Some(GameResult::from(Outcome::Win(color.opposite())));
+    game.version += 1;
+}
 
-    let accept = AcceptGame {
-        object_id: id,
-        game,
+/*
+ * What a finished game adds to the opening index: the winner, if any, and
+ * the key of every position in the opening with the move played from it.
+ * Both are in the canonical frame of the position.
+ */
+fn opening_moves(game: &Game) -> (Option<Color>, Vec<(i64, InternalMove)>)
+{
+    // A resigned game has a winner the board does not show
+    let result = game.result.or_else(|| game.board_result());
+    let winner = match result
+    {
+        Some(GameResult::White) => Some(Color::White),
+        Some(GameResult::Black) => Some(Color::Black),
+        _ => None,
     };
 
-    Ok(accept)
+    let mut board = Board::default();
+    let mut moves = Vec::new();
+    for r#move in game.move_list.iter().take(OPENING_MOVES)
+    {
+        // An imported game can hold anything, the rest of a game with an
+        // illegal move is left out
+        if !is_legal_turn(&board, r#move)
+        {
+            break;
+        }
+        // Passes are not counted, there was nothing else to play
+        if let Some(r#move) = r#move
+        {
+            let (key, r#move) = canonical_move(&board, r#move);
+            moves.push((key as i64, r#move));
+        }
+        board = play_turn(&board, r#move);
+    }
+    (winner, moves)
 }
 
-pub async fn home(db: Database, id: ObjectId) -> DatabaseResult<Vec<CreateGameChallengeBundle>>
+// The moves of the opening index turned back from the canonical frame to the
+// board's, most played first
+fn explorer_moves(sym: &Symmetry, positions: Vec<PositionMove>) -> Vec<ExplorerMove>
 {
-    let col = db.collection::<User>(USERS);
-    let filter = doc! { "_id": { "$ne": id } };
-
-    Ok(col
-        .find(filter, None)
-        .await?
-        .map(|res| {
-            let res = res.unwrap();
-
-            CreateGameChallengeBundle {
-                name:       res.name,
-                games:      res.create_games,
-                creator_id: res._id,
-            }
+    let mut moves: Vec<ExplorerMove> = positions
+        .into_iter()
+        .map(|pos| {
+            let mut r#move = ExplorerMove::from(pos);
+            r#move.r#move = sym.invert_move(&r#move.r#move);
+            r#move
         })
-        .collect::<Vec<_>>()
-        .await)
+        .collect();
+
+    moves.sort_by_key(|r#move| std::cmp::Reverse(r#move.games()));
+    moves
 }
 
 
-pub async fn get_old_games(db: Database) -> DatabaseResult<Vec<OnGoingGame>>
+/*
+ * Hash a password into a PHC string. It is argon2id with a salt of its own,
+ * so equal passwords get different hashes.
+ */
+pub fn hash(word: &str) -> String
 {
-    let col = db.collection::<Game>(GAMES);
-
-    Ok(col
-        .aggregate(
-            [
-                doc! {
-                    "$match": {
-                        "complete": true
-                    }
-                },
-                doc! {
-                    "$project": {
-                        "players": "$players.name",
-                        "game_object_id": "$_id",
-                    }
-                },
-            ],
-            None,
-        )
-        .await?
-        .map(|doc| {
-            let doc = doc.unwrap();
-            let g: OnGoingGame = bson::from_document(doc).unwrap();
-            g
-        })
-        .collect()
-        .await)
-}
+    use argon2::{
+        password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
+        Argon2,
+    };
 
-pub async fn get_users_games(db: Database, id: ObjectId) -> DatabaseResult<Vec<OnGoingGame>>
-{
-    let col = db.collection::<Game>(GAMES);
-
-    Ok(col
-        .aggregate(
-            [
-                doc! {
-                    "$match": {
-                        "players._id":  id,
-                        "complete": false,
-                    }
-                },
-                doc! {
-                    "$project": {
-                        "players": "$players.name",
-                        "game_object_id": "$_id",
-                    }
-                },
-            ],
-            None,
-        )
-        .await?
-        .map(|doc| {
-            let doc = doc.unwrap();
-            let g: OnGoingGame = bson::from_document(doc).unwrap();
-            g
-        })
-        .collect()
-        .await)
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(word.as_bytes(), &salt)
+        .expect("hashing password")
+        .to_string()
 }
 
-pub async fn get_active_games(db: Database) -> DatabaseResult<Vec<OnGoingGame>>
+// How passwords were hashed before, a bare SHA-256 in hex
+fn legacy_hash(word: &str) -> String
 {
-    let col = db.collection::<Game>(GAMES);
-
-
-    Ok(col
-        .aggregate(
-            [
-                doc! {
-                    "$match": {
-                        "complete": false
-                    }
-                },
-                doc! {
-                    "$project": {
-                        "players": "$players.name",
-                        "game_object_id": "$_id",
-                    }
-                },
-            ],
-            None,
-        )
-        .await?
-        .map(|doc| {
-            let doc = doc.unwrap();
-            let g: OnGoingGame = bson::from_document(doc).unwrap();
-            g
-        })
-        .collect()
-        .await)
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    hasher.update(word);
+    let result = hasher.finalize();
+    format!("{result:x}")
 }
 
-
-pub async fn get_game_by_id(db: Database, id: ObjectId) -> DatabaseResult<GameResource>
+// PHC strings start with `$<algorithm>`, the old hashes are only hex
+fn is_legacy(password_hash: &str) -> bool
 {
-    let col = db.collection::<Game>(GAMES);
-    col.aggregate(
-        [
-            doc! {
-                "$match": { "_id": &id }
-            },
-            doc! {
-                "$project": {
-                    "players": "$players.name",
-                    "board": "$board",
-                    "complete": "$complete",
-                    "move_list": "$move_list",
-                }
-            },
-        ],
-        None,
-    )
-    .await?
-    .map(|doc| {
-        let doc = doc.unwrap();
-        let g: GameResource = bson::from_document(doc).unwrap();
-        g
-    })
-    .next()
-    .await
-    .ok_or(DatabaseError::NoDocumentFound)
+    !password_hash.starts_with('$')
 }
 
-pub async fn play_move(db: Database, r#move: Move) -> DatabaseResult<()>
+pub fn verify(word: &str, password_hash: &str) -> bool
 {
-    let col = db.collection::<Game>(GAMES);
-
-    let query = doc! {
-        "_id": r#move.game_id,
-        "players._id": &r#move.player_id,
+    use argon2::{
+        password_hash::{PasswordHash, PasswordVerifier},
+        Argon2,
     };
 
-    let mut game =
-        col.find_one(query.clone(), None).await?.ok_or(DatabaseError::NoDocumentFound)?;
-
-    game.board.play_move(r#move.clone());
-    game.move_list.push(r#move.into());
+    if is_legacy(password_hash)
+    {
+        return legacy_hash(word) == password_hash;
+    }
 
-    col.replace_one(query, game, None).await.map(|_| ()).map_err(|e| e.into())
+    // The parameters are read from the hash, older ones still verify
+    match PasswordHash::new(password_hash)
+    {
+        Ok(parsed) => Argon2::default().verify_password(word.as_bytes(), &parsed).is_ok(),
+        Err(_) => false,
+    }
 }
 
-pub async fn complete_game(db: Database, game_id: ObjectId) -> DatabaseResult<()>
+// Checked when there is no user to check against, so a missing user takes as
+// long as a wrong password
+const DUMMY_HASH: &str = "$argon2id$v=19$m=19456,t=2,\
+                          p=1$ax2hIoElDtZzaw3x6zgi7Q$dNckyp2vhZG3zntFijFMAeDt/zErrtI8QcdlFZnrlRk";
+
+/*
+ * `hash` and `verify` take long enough to hold up every other request on the
+ * executor, so the storages run them on the blocking thread pool.
+ */
+pub async fn hash_blocking(word: String) -> String
 {
-    let col = db.collection::<Game>(GAMES);
-    let filter = doc! { "_id": game_id };
-    let update = doc! { "$set": { "complete": true } };
-
-    col.find_one_and_update(filter, update, None)
-        .await
-        .map(|_| ())
-        .map_err(|e| e.into())
+    task::spawn_blocking(move || hash(&word)).await.expect("hashing password")
 }
 
-
-pub fn hash(word: &str) -> String
+// Always false without a hash, but only after as much work as with one
+pub async fn verify_blocking(word: String, password_hash: Option<String>) -> bool
 {
-    use sha2::{Digest, Sha256};
-    let mut hasher = Sha256::new();
-    hasher.update(word);
-    let result = hasher.finalize();
-    format!("{result:x}")
+    task::spawn_blocking(move || match password_hash
+    {
+        Some(password_hash) => verify(&word, &password_hash),
+        None =>
+        {
+            verify(&word, DUMMY_HASH);
+            false
+        },
+    })
+    .await
+    .unwrap_or(false)
 }
 
 impl From<mongodb::error::Error> for DatabaseError
@@ -411,151 +499,133 @@ impl From<mongodb::error::Error> for DatabaseError
     }
 }
 
-#[cfg(test)]
-mod test
+impl From<rusqlite::Error> for DatabaseError
 {
-    use mongodb::Database;
-
-    use super::*;
-
-
-    struct Guard
-    {
-        database: Database,
-    }
-
-    impl Guard
+    fn from(error: rusqlite::Error) -> Self
     {
-        fn db(&self) -> Database
-        {
-            self.database.clone()
-        }
+        DatabaseError::SqliteError(error)
     }
+}
 
-    impl Drop for Guard
-    {
-        fn drop(&mut self)
-        {
-            use tokio::{runtime::Handle, task};
-
-            task::block_in_place(move || {
-                Handle::current().block_on(async move {
-                    self.database.drop(None).await.unwrap();
-                });
-            });
-        }
-    }
+#[cfg(test)]
+mod test
+{
+    use shared::{
+        engine::{apply, generate_moves, pass},
+        uhp::Position,
+    };
 
-    async fn get_guard() -> Result<Guard, DatabaseError>
-    {
-        let client = connect().await?;
+    use super::*;
 
-        let name = format!("{}", uuid::Uuid::new_v4());
-        let database = client.database(&name);
+    pub const LIMIT: u32 = 25;
 
-        Ok(Guard {
-            database,
-        })
-    }
 
-    async fn reg(guard: &Guard, name: String) -> DatabaseResult<ObjectId>
+    async fn reg(db: &dyn Storage, name: String) -> DatabaseResult<ObjectId>
     {
         let cred = UserCredentials {
             name,
             password: "password".into(),
         };
-        register_user(guard.db(), cred).await
+        db.register_user(cred).await
     }
 
     async fn create_users_and_game_name(
-        guard: &Guard,
+        db: &dyn Storage,
         name1: &str,
         name2: &str,
     ) -> DatabaseResult<(ObjectId, ObjectId, ObjectId)>
     {
-        let u1 = reg(guard, name1.into()).await?;
-        let u2 = reg(guard, name2.into()).await?;
+        let u1 = reg(db, name1.into()).await?;
+        let u2 = reg(db, name2.into()).await?;
 
-        let game = create_game(guard.db(), u1.clone()).await?;
+        let game = db.create_game(u1.clone(), LIMIT).await?;
 
         let form = CreateGameFormResponse {
             creator: u1.clone(),
-            user: u2.clone(),
             game,
         };
 
-        let game = accept_game(guard.db(), form).await?.object_id;
+        let game = db.accept_game(u2, form).await?.object_id;
 
         Ok((u1, u2, game))
     }
 
-    async fn create_users_and_game(guard: &Guard)
-        -> DatabaseResult<(ObjectId, ObjectId, ObjectId)>
+    async fn create_users_and_game(
+        db: &dyn Storage,
+    ) -> DatabaseResult<(ObjectId, ObjectId, ObjectId)>
     {
-        create_users_and_game_name(guard, "sivert", "sofie").await
+        create_users_and_game_name(db, "sivert", "sofie").await
     }
 
-
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_can_register_and_find_user() -> Result<(), DatabaseError>
+    fn placement(game_id: ObjectId, piece: Piece, sq: Square, move_number: usize) -> Move
     {
-        let guard = get_guard().await?;
+        Move {
+            game_id,
+            r#move: Some(InternalMove {
+                piece,
+                sq,
+                old_sq: None,
+            }),
+            move_number,
+        }
+    }
+
 
-        let res = reg(&guard, "sivert".into()).await;
+    async fn test_can_register_and_find_user(db: &dyn Storage) -> DatabaseResult<()>
+    {
+        let res = reg(db, "sivert".into()).await;
         assert!(res.is_ok());
 
-        let res = find_user_by_id(guard.db(), res.unwrap()).await;
+        let id = res.unwrap();
+        let res = db.find_user_by_id(id).await;
         assert!(res.is_ok());
 
+        let res = db.find_user_by_name("sivert").await;
+        assert_eq!(res?._id, id);
+        let res = db.find_user_by_name("sofie").await;
+        assert!(matches!(res, Err(DatabaseError::UserDontExist)));
+
         Ok(())
     }
 
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_register_user_errors() -> Result<(), DatabaseError>
+    async fn test_register_user_errors(db: &dyn Storage) -> DatabaseResult<()>
     {
-        let guard = get_guard().await?;
-
         let cred = UserCredentials {
             name: "sivert".into(), password: "password".into()
         };
 
-        let res = find_user_by_id(guard.db(), ObjectId::new()).await;
+        let res = db.find_user_by_id(ObjectId::new()).await;
         assert!(matches!(res, Err(DatabaseError::UserDontExist)));
 
-        let res = register_user(guard.db(), cred.clone()).await;
+        let res = db.register_user(cred.clone()).await;
         assert!(res.is_ok());
 
-        let res = register_user(guard.db(), cred).await;
+        let res = db.register_user(cred).await;
         assert!(matches!(res, Err(DatabaseError::UserAlreadyExist)));
 
         Ok(())
     }
 
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_not_registered_user_can_not_login() -> Result<(), DatabaseError>
+    async fn test_not_registered_user_can_not_login(db: &dyn Storage) -> DatabaseResult<()>
     {
-        let guard = get_guard().await?;
         let cred = UserCredentials {
             name: "sivert".into(), password: "password".into()
         };
-        let res = login(guard.db(), cred).await;
+        let res = db.login(cred).await;
         assert!(matches!(res, Err(DatabaseError::UserDontExist)));
 
         Ok(())
     }
 
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_user_can_login() -> Result<(), DatabaseError>
+    async fn test_user_can_login(db: &dyn Storage) -> DatabaseResult<()>
     {
-        let guard = get_guard().await?;
-
         let cred = UserCredentials {
             name: "sivert".into(), password: "password".into()
         };
 
-        let reg_uuid = register_user(guard.db(), cred.clone()).await?;
+        let reg_uuid = db.register_user(cred.clone()).await?;
 
-        let res = login(guard.db(), cred).await;
+        let res = db.login(cred).await;
         assert!(res.is_ok());
         let log_uuid = res.unwrap();
 
@@ -564,172 +634,279 @@ mod test
         Ok(())
     }
 
-
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_user_can_create_game() -> Result<(), DatabaseError>
+    async fn test_legacy_password_is_rehashed(db: &dyn Storage) -> DatabaseResult<()>
     {
-        let guard = get_guard().await?;
-        let creator = reg(&guard, "sivert".into()).await?;
+        let cred = UserCredentials {
+            name: "sivert".into(), password: "password".into()
+        };
+        let id = db.register_user(cred.clone()).await?;
+        db.set_password_hash(id, &legacy_hash(&cred.password)).await?;
+
+        assert_eq!(db.login(cred.clone()).await?, id);
+        assert!(db.find_user_by_id(id).await?.password_hash.starts_with("$argon2id$"));
+        assert_eq!(db.login(cred).await?, id);
 
-        assert!(create_game(guard.db(), creator).await.is_ok());
         Ok(())
     }
 
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_get_home_content() -> Result<(), DatabaseError>
+    #[test]
+    fn test_password_hash()
     {
-        let guard = get_guard().await?;
-        let creator = reg(&guard, "sivert".into()).await?;
+        let first = hash("password");
+        let second = hash("password");
+        assert!(first.starts_with("$argon2id$"));
+        assert_ne!(first, second);
+
+        assert!(verify("password", &first));
+        assert!(verify("password", &second));
+        assert!(!verify("passw0rd", &first));
+
+        let legacy = legacy_hash("password");
+        assert!(verify("password", &legacy));
+        assert!(!verify("passw0rd", &legacy));
+        assert!(!verify("password", "$argon2id$garbage"));
+
+        // The dummy is a real hash, of the empty password, so checking it
+        // takes as long as checking a user's
+        assert!(verify("", DUMMY_HASH));
+    }
 
-        assert!(create_game(guard.db(), creator.clone()).await.is_ok());
-        assert!(create_game(guard.db(), creator.clone()).await.is_ok());
+    async fn test_session_finds_user(db: &dyn Storage) -> DatabaseResult<()>
+    {
+        let user = reg(db, "sivert".into()).await?;
+        let session = db.create_session(user).await?;
 
-        assert_eq!(home(guard.db(), creator).await?.len(), 0);
+        assert_eq!(session.user, user);
+        assert_eq!(db.find_session(&session.token).await?, user);
 
-        let new = reg(&guard, "sofie".into()).await?;
-        let games = home(guard.db(), new).await?;
-        assert_eq!(games[0].games.len(), 2);
+        let other = db.create_session(user).await?;
+        assert_ne!(other.token, session.token);
+
+        let res = db.find_session("not a token").await;
+        assert!(matches!(res, Err(DatabaseError::InvalidSession)));
+
+        db.delete_session(&session.token).await?;
+        let res = db.find_session(&session.token).await;
+        assert!(matches!(res, Err(DatabaseError::InvalidSession)));
+        assert_eq!(db.find_session(&other.token).await?, user);
+        db.delete_session(&session.token).await?;
 
         Ok(())
     }
 
 
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_count_create_game() -> Result<(), DatabaseError>
+    async fn test_user_can_create_game(db: &dyn Storage) -> DatabaseResult<()>
     {
-        let guard = get_guard().await?;
-
-        // Create user#1 and create one game
-        let id = reg(&guard, "sofie".into()).await?;
-        assert!(create_game(guard.db(), id.clone()).await.is_ok());
+        let creator = reg(db, "sivert".into()).await?;
 
+        assert!(db.create_game(creator, LIMIT).await.is_ok());
+        Ok(())
+    }
 
-        // Create user#2 and `n` games
-        let id = reg(&guard, "sivert".into()).await?;
-        let n: u32 = 20;
+    async fn test_challenge_creator(db: &dyn Storage) -> DatabaseResult<()>
+    {
+        let creator = reg(db, "sivert".into()).await?;
+        let game = db.create_game(creator, LIMIT).await?;
+        assert_eq!(db.find_challenge_creator(game).await?, creator);
 
-        for _ in 0..n
-        {
-            assert!(create_game(guard.db(), id.clone()).await.is_ok());
-        }
+        let res = db.find_challenge_creator(ObjectId::new()).await;
+        assert!(matches!(res, Err(DatabaseError::NoDocumentFound)));
 
-        assert_eq!(count_create_game(guard.db(), &id).await?, n);
+        let id = reg(db, "sofie".into()).await?;
+        db.accept_game(id, CreateGameFormResponse {
+            creator,
+            game,
+        })
+        .await?;
+        let res = db.find_challenge_creator(game).await;
+        assert!(matches!(res, Err(DatabaseError::NoDocumentFound)));
 
         Ok(())
     }
 
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_remove_create_game() -> Result<(), DatabaseError>
+    async fn test_get_home_content(db: &dyn Storage) -> DatabaseResult<()>
     {
-        let guard = get_guard().await?;
-        let creator = reg(&guard, "sivert".into()).await?;
+        let creator = reg(db, "sivert".into()).await?;
 
-        let create_id = create_game(guard.db(), creator.clone()).await?;
+        assert!(db.create_game(creator.clone(), LIMIT).await.is_ok());
+        assert!(db.create_game(creator.clone(), LIMIT).await.is_ok());
 
-        assert_eq!(find_user_by_id(guard.db(), creator.clone()).await?.create_games.len(), 1);
-        assert!(remove_user_create_game(guard.db(), &creator, &create_id).await.is_ok());
-
-        assert_eq!(find_user_by_id(guard.db(), creator.clone()).await?.create_games.len(), 0);
+        assert_eq!(db.home(creator).await?.len(), 0);
 
+        let new = reg(db, "sofie".into()).await?;
+        let games = db.home(new).await?;
+        assert_eq!(games[0].games.len(), 2);
 
         Ok(())
     }
 
 
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_can_accept_game() -> Result<(), DatabaseError>
+    async fn test_can_accept_game(db: &dyn Storage) -> DatabaseResult<()>
     {
-        let guard = get_guard().await?;
-
-        let creator = reg(&guard, "sivert".into()).await?;
-        let create_id = create_game(guard.db(), creator.clone()).await?;
+        let creator = reg(db, "sivert".into()).await?;
+        let create_id = db.create_game(creator.clone(), LIMIT).await?;
 
-        let id = reg(&guard, "sofie".into()).await?;
+        let id = reg(db, "sofie".into()).await?;
 
         let form = CreateGameFormResponse {
-            creator: creator.clone(),
-            user:    id.clone(),
-            game:    create_id,
+            creator: creator.clone(), game: create_id
         };
 
-        assert!(accept_game(guard.db(), form).await.is_ok());
+        assert!(db.accept_game(id, form).await.is_ok());
 
-        let user = find_user_by_id(guard.db(), creator).await?;
+        let user = db.find_user_by_id(creator).await?;
         assert_eq!(user.create_games.len(), 0);
         assert_eq!(user.active_games.len(), 1);
 
-        let user = find_user_by_id(guard.db(), id).await?;
+        let user = db.find_user_by_id(id).await?;
         assert_eq!(user.active_games.len(), 1);
 
         Ok(())
     }
 
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_can_get_active_games() -> Result<(), DatabaseError>
+    async fn test_challenge_is_accepted_once(db: &dyn Storage) -> DatabaseResult<()>
     {
-        let guard = get_guard().await?;
+        let creator = reg(db, "sivert".into()).await?;
+        let game = db.create_game(creator, LIMIT).await?;
 
-        let creator = reg(&guard, "sivert".into()).await?;
-        let create_id = create_game(guard.db(), creator.clone()).await?;
+        let mut users = Vec::new();
+        for name in &["sofie", "ola", "kari", "per"]
+        {
+            users.push(reg(db, name.to_string()).await?);
+        }
 
-        let uuid = reg(&guard, "sofie".into()).await?;
+        // Everyone accepts at once, only the first one gets the game
+        let accepts = users.iter().map(|&user| {
+            let form = CreateGameFormResponse {
+                creator,
+                game,
+            };
+            db.accept_game(user, form)
+        });
+        let results = futures::future::join_all(accepts).await;
+
+        assert_eq!(results.iter().filter(|res| res.is_ok()).count(), 1);
+        assert!(results
+            .iter()
+            .filter_map(|res| res.as_ref().err())
+            .all(|e| matches!(e, DatabaseError::Conflict)));
+
+        assert_eq!(db.get_active_games().await?.len(), 1);
+        let user = db.find_user_by_id(creator).await?;
+        assert!(user.create_games.is_empty());
+        assert_eq!(user.active_games.len(), 1);
 
+        // Accepting it again later is no different
         let form = CreateGameFormResponse {
-            creator: creator.clone(),
-            user:    uuid.clone(),
-            game:    create_id,
+            creator,
+            game,
         };
+        assert!(matches!(db.accept_game(users[0], form).await, Err(DatabaseError::Conflict)));
 
-        assert!(accept_game(guard.db(), form).await.is_ok());
+        Ok(())
+    }
 
-        let vec = get_active_games(guard.db()).await?;
-        assert_eq!(vec.len(), 1);
+    async fn test_failed_accept_keeps_challenge(db: &dyn Storage) -> DatabaseResult<()>
+    {
+        let creator = reg(db, "sivert".into()).await?;
+        let game = db.create_game(creator, LIMIT).await?;
+
+        let form = CreateGameFormResponse {
+            creator,
+            game,
+        };
+        let res = db.accept_game(ObjectId::new(), form.clone()).await;
+        assert!(matches!(res, Err(DatabaseError::UserDontExist)));
+
+        assert_eq!(db.find_user_by_id(creator).await?.create_games, [game]);
+        assert!(db.get_active_games().await?.is_empty());
+
+        let user = reg(db, "sofie".into()).await?;
+        assert!(db.accept_game(user, form).await.is_ok());
 
         Ok(())
     }
 
+    async fn test_challenge_is_removed(db: &dyn Storage) -> DatabaseResult<()>
+    {
+        let creator = reg(db, "sivert".into()).await?;
+        let game = db.create_game(creator, LIMIT).await?;
+
+        db.remove_challenge(creator, game).await?;
+        assert!(db.find_user_by_id(creator).await?.create_games.is_empty());
+        assert!(matches!(db.remove_challenge(creator, game).await, Err(DatabaseError::Conflict)));
 
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_cannot_get_non_existing_game() -> Result<(), DatabaseError>
+        let user = reg(db, "sofie".into()).await?;
+        let form = CreateGameFormResponse {
+            creator,
+            game,
+        };
+        assert!(matches!(db.accept_game(user, form).await, Err(DatabaseError::Conflict)));
+
+        Ok(())
+    }
+
+    async fn test_can_get_active_games(db: &dyn Storage) -> DatabaseResult<()>
     {
-        let guard = get_guard().await?;
+        let creator = reg(db, "sivert".into()).await?;
+        let create_id = db.create_game(creator.clone(), LIMIT).await?;
+
+        let uuid = reg(db, "sofie".into()).await?;
+
+        let form = CreateGameFormResponse {
+            creator: creator.clone(), game: create_id
+        };
+
+        assert!(db.accept_game(uuid, form).await.is_ok());
+
+        let vec = db.get_active_games().await?;
+        assert_eq!(vec.len(), 1);
+
+        Ok(())
+    }
 
-        let res = get_game_by_id(guard.db(), ObjectId::new()).await;
+
+    async fn test_cannot_get_non_existing_game(db: &dyn Storage) -> DatabaseResult<()>
+    {
+        let res = db.get_game_by_id(ObjectId::new()).await;
         assert!(matches!(res, Err(DatabaseError::NoDocumentFound)));
         Ok(())
     }
 
 
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_can_get_game() -> Result<(), DatabaseError>
+    async fn test_can_get_game(db: &dyn Storage) -> DatabaseResult<()>
     {
-        let guard = get_guard().await?;
-        let (_, _, game_id) = create_users_and_game(&guard).await?;
+        let (_, _, game_id) = create_users_and_game(db).await?;
 
-        assert!(get_game_by_id(guard.db(), game_id).await.is_ok());
+        assert!(db.get_game_by_id(game_id).await.is_ok());
 
         Ok(())
     }
 
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_can_play_move() -> Result<(), DatabaseError>
+    async fn test_can_play_move(db: &dyn Storage) -> DatabaseResult<()>
     {
-        let guard = get_guard().await?;
-        let (u1, _, game_id) = create_users_and_game(&guard).await?;
+        let (u1, u2, game_id) = create_users_and_game(db).await?;
+
+        let game = db.get_game_by_id(game_id).await?;
+        let (white, black) = if game.players[0] == "sivert" { (u1, u2) } else { (u2, u1) };
 
-        let game = get_game_by_id(guard.db(), game_id).await?;
+        let mov = placement(game._id, Piece::new(BoardPiece::Ant, Color::White), (0, 0, 0), 0);
+        assert!(matches!(db.play_move(black, mov).await, Err(DatabaseError::NotYourTurn)));
 
-        let mov = Move {
-            player_id: u1,
-            game_id:   game._id,
-            sq:        (0, 0, 0),
-            piece:     Piece::new(BoardPiece::Ant, Color::White),
-            old_sq:    None,
+        let mov = placement(game._id, Piece::new(BoardPiece::Ant, Color::Black), (1, 0, -1), 0);
+        assert!(matches!(db.play_move(white, mov).await, Err(DatabaseError::IllegalMove)));
+
+        // Passing is only allowed without any move to play
+        let pass = Move {
+            game_id: game._id, r#move: None, move_number: 0
         };
+        assert!(matches!(db.play_move(white, pass).await, Err(DatabaseError::IllegalMove)));
+
+        let mov = placement(game._id, Piece::new(BoardPiece::Ant, Color::White), (0, 0, 0), 0);
 
-        assert!(play_move(guard.db(), mov).await.is_ok());
+        assert!(db.play_move(white, mov).await.is_ok());
 
-        let game = get_game_by_id(guard.db(), game_id).await?;
+        let game = db.get_game_by_id(game_id).await?;
         assert_eq!(game.board.len(), 1);
         assert_eq!(game.board.turns, 1);
         assert_eq!(game.move_list.len(), 1);
@@ -737,16 +914,74 @@ mod test
         Ok(())
     }
 
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_game_is_completed() -> Result<(), DatabaseError>
+    async fn test_stale_move_is_a_conflict(db: &dyn Storage) -> DatabaseResult<()>
     {
-        let guard = get_guard().await?;
+        let (u1, u2, game_id) = create_users_and_game(db).await?;
+
+        let game = db.get_game_by_id(game_id).await?;
+        let (white, black) = if game.players[0] == "sivert" { (u1, u2) } else { (u2, u1) };
+
+        let first = placement(game_id, Piece::new(BoardPiece::Ant, Color::White), (0, 0, 0), 0);
+        let second = first.clone();
+
+        // Sent twice at once, like a double click
+        let (a, b) = tokio::join!(db.play_move(white, first), db.play_move(white, second));
+        assert_eq!(a.is_ok() as u32 + b.is_ok() as u32, 1);
+        assert!(matches!(a.and(b), Err(DatabaseError::Conflict)));
+
+        // Black answering the board from before white moved
+        let late = placement(game_id, Piece::new(BoardPiece::Spider, Color::Black), (1, -1, 0), 0);
+        assert!(matches!(db.play_move(black, late).await, Err(DatabaseError::Conflict)));
+
+        let game = db.get_game_by_id(game_id).await?;
+        assert_eq!(game.move_list.len(), 1);
+        assert_eq!(game.board.turns, 1);
 
-        let (_, _, game_id) = create_users_and_game(&guard).await?;
+        Ok(())
+    }
+
+    async fn test_imported_pass_is_replayed(db: &dyn Storage) -> DatabaseResult<()>
+    {
+        // The black queen is pinned with nowhere to place a piece, so black
+        // has to pass
+        let mut pos = Position::new();
+        for r#move in ["wQ", "bQ wQ\\", "wA1 \\wQ", "bQ wQ-", "wA1 bQ-", "pass", "wG1 -wQ"]
+        {
+            pos.play_str(r#move).unwrap();
+        }
+        let move_list = pos.history().cloned().collect();
+        let players = [String::from("white"), String::from("black")];
+        let game_id =
+            db.import_game(Game::imported(players, pos.board().clone(), move_list)).await?;
+
+        let game = db.get_game_by_id(game_id).await?;
+        assert_eq!(game.move_list.len(), 7);
+        assert_eq!(game.move_list[5], None);
+
+        let board = game
+            .move_list
+            .iter()
+            .fold(Board::default(), |board, r#move| play_turn(&board, r#move));
+        assert_eq!(board.hash_key(), game.board.hash_key());
+
+        // The pass is left out of the opening index, the moves around it are in
+        assert_eq!(db.index_games().await?, 1);
+        let board = game.move_list[..5]
+            .iter()
+            .fold(Board::default(), |board, r#move| play_turn(&board, r#move));
+        assert!(db.explore(&board).await?.is_empty());
+        assert_eq!(db.explore(&pass(&board)).await?.len(), 1);
+
+        Ok(())
+    }
+
+    async fn test_game_is_completed(db: &dyn Storage) -> DatabaseResult<()>
+    {
+        let (_, _, game_id) = create_users_and_game(db).await?;
 
-        let available_games_len1 = get_active_games(guard.db()).await?.len();
-        complete_game(guard.db(), game_id).await?;
-        let available_games_len2 = get_active_games(guard.db()).await?.len();
+        let available_games_len1 = db.get_active_games().await?.len();
+        db.complete_game(game_id).await?;
+        let available_games_len2 = db.get_active_games().await?.len();
 
         assert_eq!(available_games_len1, 1);
         assert_eq!(available_games_len2, 0);
@@ -754,21 +989,431 @@ mod test
         Ok(())
     }
 
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_get_users_games() -> Result<(), DatabaseError>
+    async fn test_game_is_resigned(db: &dyn Storage) -> DatabaseResult<()>
     {
-        let guard = get_guard().await?;
+        let (u1, u2, game_id) = create_users_and_game(db).await?;
+        let game = db.get_game_by_id(game_id).await?;
+        let winner =
+            if game.players[0] == "sivert" { GameResult::Black } else { GameResult::White };
+
+        db.resign_game(game_id, u1).await?;
+        assert_eq!(db.get_active_games().await?.len(), 0);
+        assert!(matches!(db.resign_game(game_id, u2).await, Err(DatabaseError::NoDocumentFound)));
+
+        let listing = Listing {
+            complete: true,
+            result: Some(winner),
+            ..Listing::default()
+        };
+        assert_eq!(db.find_games(&listing).await?.items.len(), 1);
+
+        Ok(())
+    }
 
+    async fn test_get_users_games(db: &dyn Storage) -> DatabaseResult<()>
+    {
         let name = "u1";
-        let (u1, _, _) = create_users_and_game_name(&guard, name, "u2").await?;
-        let (_, _, _) = create_users_and_game_name(&guard, "y1", "y2").await?;
-        let (_, _, _) = create_users_and_game_name(&guard, "x1", "x2").await?;
+        let (u1, _, _) = create_users_and_game_name(db, name, "u2").await?;
+        let (_, _, _) = create_users_and_game_name(db, "y1", "y2").await?;
+        let (_, _, _) = create_users_and_game_name(db, "x1", "x2").await?;
 
-        let games = get_users_games(guard.db(), u1).await?;
+        let games = db.get_users_games(u1).await?;
 
         assert_eq!(games.len(), 1);
         assert!(games[0].players.iter().any(|n| n == name));
 
         Ok(())
     }
+
+    async fn test_opening_index(db: &dyn Storage) -> DatabaseResult<()>
+    {
+        let (u1, u2, game_id) = create_users_and_game(db).await?;
+
+        let game = db.get_game_by_id(game_id).await?;
+        let (white, black) = if game.players[0] == "sivert" { (u1, u2) } else { (u2, u1) };
+
+        let moves = [
+            (white, (0, 0, 0), Piece::new(BoardPiece::Ant, Color::White)),
+            (black, (1, -1, 0), Piece::new(BoardPiece::Spider, Color::Black)),
+        ];
+        for (move_number, &(player_id, sq, piece)) in moves.iter().enumerate()
+        {
+            db.play_move(player_id, placement(game_id, piece, sq, move_number)).await?;
+        }
+
+        assert_eq!(db.index_games().await?, 0);
+        db.complete_game(game_id).await?;
+        assert_eq!(db.index_games().await?, 1);
+        assert_eq!(db.index_games().await?, 0);
+
+        let board = Board::default();
+        let first = db.explore(&board).await?;
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].draws, 1);
+
+        let board = apply(&board, &first[0].r#move);
+        // Any side of the first piece is the same, the one shown is a legal
+        // placement of the spider
+        let second = db.explore(&board).await?;
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].r#move.piece, Piece::new(BoardPiece::Spider, Color::Black));
+        assert!(generate_moves(&board).contains(&second[0].r#move));
+
+        Ok(())
+    }
+
+    async fn test_symmetric_moves_are_counted_together(db: &dyn Storage) -> DatabaseResult<()>
+    {
+        // Black answers on two different sides of the first piece, which is
+        // the same move turned around
+        let place = |r#type, color, sq| InternalMove {
+            piece: Piece::new(r#type, color),
+            sq,
+            old_sq: None,
+        };
+        let first = Some(place(BoardPiece::Ant, Color::White, (0, 0, 0)));
+        for sq in [(1, -1, 0), (-1, 0, 1)]
+        {
+            let second = Some(place(BoardPiece::Spider, Color::Black, sq));
+            let moves = vec![first.clone(), second];
+            let board = Position::from_moves(&moves).board().clone();
+            let players = [String::from("white"), String::from("black")];
+            db.import_game(Game::imported(players, board, moves)).await?;
+        }
+
+        // The rest of a game is left out from an illegal move on
+        let moves = vec![first.clone(), first.clone()];
+        let players = [String::from("white"), String::from("black")];
+        db.import_game(Game::imported(players, Board::default(), moves)).await?;
+
+        assert_eq!(db.index_games().await?, 3);
+        assert_eq!(db.explore(&Board::default()).await?[0].draws, 3);
+
+        let board = apply(&Board::default(), first.as_ref().unwrap());
+        let second = db.explore(&board).await?;
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].draws, 2);
+        assert!(generate_moves(&board).contains(&second[0].r#move));
+
+        Ok(())
+    }
+
+    async fn test_one_puzzle_per_game(db: &dyn Storage) -> DatabaseResult<()>
+    {
+        let (_, _, game_id) = create_users_and_game(db).await?;
+
+        assert!(matches!(db.random_puzzle().await, Err(DatabaseError::NoDocumentFound)));
+
+        db.add_puzzle(game_id, &[], 1).await?;
+        db.add_puzzle(game_id, &[], 2).await?;
+
+        let puzzle = db.random_puzzle().await?;
+        assert_eq!(puzzle.game, game_id);
+        assert_eq!(puzzle.length, 1);
+        assert_eq!(db.get_puzzle(puzzle._id).await?.length, 1);
+
+        Ok(())
+    }
+
+    async fn test_imported_games_are_finished(db: &dyn Storage) -> DatabaseResult<()>
+    {
+        let r#move = InternalMove {
+            piece:  Piece::new(BoardPiece::Ant, Color::White),
+            sq:     (0, 0, 0),
+            old_sq: None,
+        };
+        let board = apply(&Board::default(), &r#move);
+        let players = [String::from("white"), String::from("black")];
+        let game_id = db.import_game(Game::imported(players, board, vec![Some(r#move)])).await?;
+
+        assert!(db.get_active_games().await?.is_empty());
+        let old = db.get_old_games().await?;
+        assert_eq!(old.len(), 1);
+        assert_eq!(old[0].players, [String::from("white"), String::from("black")]);
+
+        let game = db.get_game_by_id(game_id).await?;
+        assert_eq!(game.move_list.len(), 1);
+        assert_eq!(game.board.turns, 1);
+
+        Ok(())
+    }
+
+    async fn test_games_are_paged(db: &dyn Storage) -> DatabaseResult<()>
+    {
+        let (u1, u2, first) = create_users_and_game(db).await?;
+        let mut ids = vec![first];
+        for _ in 0..4
+        {
+            let game = db.create_game(u1, LIMIT).await?;
+            let form = CreateGameFormResponse {
+                creator: u1,
+                game,
+            };
+            ids.push(db.accept_game(u2, form).await?.object_id);
+        }
+
+        for sort in [SortOrder::Newest, SortOrder::Oldest]
+        {
+            let mut query = ListQuery {
+                sort,
+                limit: Some(2),
+                ..ListQuery::default()
+            };
+            let mut listed = Vec::new();
+            let mut pages = 0;
+            loop
+            {
+                let listing = Listing::from_query(&query, false).unwrap().unwrap();
+                let page = db.find_games(&listing).await?;
+                assert!(page.items.len() <= 2);
+                listed.extend(page.items.iter().map(|game| game.game_object_id));
+                pages += 1;
+
+                match page.next
+                {
+                    Some(next) => query.cursor = Some(next),
+                    None => break,
+                }
+            }
+
+            let mut expected = ids.clone();
+            if sort == SortOrder::Newest
+            {
+                expected.reverse();
+            }
+            assert_eq!(listed, expected);
+            assert_eq!(pages, 3);
+        }
+
+        Ok(())
+    }
+
+    async fn test_games_are_filtered(db: &dyn Storage) -> DatabaseResult<()>
+    {
+        create_users_and_game(db).await?;
+
+        // The black queen is surrounded
+        let mut board = Board::default();
+        let pieces = [
+            ((0, 0, 0), BoardPiece::Queen, Color::Black),
+            ((1, -1, 0), BoardPiece::Spider, Color::Black),
+            ((0, -1, 1), BoardPiece::Ant, Color::Black),
+            ((-1, 0, 1), BoardPiece::Beetle, Color::Black),
+            ((-1, 1, 0), BoardPiece::Queen, Color::White),
+            ((1, 0, -1), BoardPiece::Grasshopper, Color::White),
+            ((0, 1, -1), BoardPiece::Ant, Color::White),
+        ];
+        for &(sq, r#type, color) in pieces.iter()
+        {
+            board.place_piece(Piece::new(r#type, color), sq, None).unwrap();
+        }
+        let players = [String::from("sivert"), String::from("guest")];
+        let won = db.import_game(Game::imported(players, board, Vec::new())).await?;
+        let players = [String::from("guest"), String::from("other")];
+        db.import_game(Game::imported(players, Board::default(), Vec::new())).await?;
+
+        let find = |query: ListQuery, complete| async move {
+            let listing = Listing::from_query(&query, complete).unwrap().unwrap();
+            let page = db.find_games(&listing).await?;
+            DatabaseResult::Ok(
+                page.items.into_iter().map(|game| game.game_object_id).collect::<Vec<_>>(),
+            )
+        };
+        let query = |player: &str, result| ListQuery {
+            player: Some(player.to_string()),
+            result,
+            ..ListQuery::default()
+        };
+
+        assert_eq!(find(query("sofie", None), false).await?.len(), 1);
+        assert_eq!(find(query("sofie", None), true).await?.len(), 0);
+        assert_eq!(find(query("sivert", None), true).await?, vec![won]);
+        assert_eq!(find(query("guest", None), true).await?.len(), 2);
+        assert_eq!(find(query("guest", Some(GameResult::White)), true).await?, vec![won]);
+        assert_eq!(find(query("guest", Some(GameResult::Black)), true).await?.len(), 0);
+
+        let today = chrono::Utc::now().naive_utc().date();
+        let day =
+            |days| Some((today + chrono::Duration::days(days)).format("%Y-%m-%d").to_string());
+        let dates = |from, to| ListQuery {
+            from,
+            to,
+            ..ListQuery::default()
+        };
+        assert_eq!(find(dates(day(0), day(0)), true).await?.len(), 2);
+        assert_eq!(find(dates(day(1), None), true).await?.len(), 0);
+        assert_eq!(find(dates(None, day(-1)), true).await?.len(), 0);
+        assert_eq!(find(dates(day(-1), None), false).await?.len(), 1);
+
+        Ok(())
+    }
+
+    async fn test_challenges_are_paged(db: &dyn Storage) -> DatabaseResult<()>
+    {
+        let sivert = reg(db, "sivert".into()).await?;
+        let sofie = reg(db, "sofie".into()).await?;
+        let guest = reg(db, "guest".into()).await?;
+
+        let mut ids = Vec::new();
+        for _ in 0..3
+        {
+            ids.push(db.create_game(sivert, LIMIT).await?);
+        }
+        ids.push(db.create_game(sofie, LIMIT).await?);
+
+        let find = |id, query: ListQuery| async move {
+            let listing = Listing::from_query(&query, false).unwrap().unwrap();
+            db.find_challenges(id, &listing).await
+        };
+
+        let page = find(guest, ListQuery::default()).await?;
+        let listed: Vec<_> = page.items.iter().map(|challenge| challenge._id).collect();
+        assert_eq!(listed, ids.iter().rev().copied().collect::<Vec<_>>());
+        assert_eq!(page.items[0].name, "sofie");
+        assert_eq!(page.items[0].creator, sofie);
+        assert_eq!(page.next, None);
+
+        let page = find(sofie, ListQuery::default()).await?;
+        assert_eq!(page.items.len(), 3);
+
+        let query = ListQuery {
+            player: Some("sivert".into()),
+            sort: SortOrder::Oldest,
+            limit: Some(2),
+            ..ListQuery::default()
+        };
+        let page = find(guest, query.clone()).await?;
+        let listed: Vec<_> = page.items.iter().map(|challenge| challenge._id).collect();
+        assert_eq!(listed, ids[..2]);
+
+        let query = ListQuery {
+            cursor: page.next,
+            ..query
+        };
+        let page = find(guest, query).await?;
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0]._id, ids[2]);
+        assert_eq!(page.next, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_query()
+    {
+        let listing = |query: ListQuery| Listing::from_query(&query, false);
+
+        let other = ListQuery {
+            ruleset: Some("Base+MLP".into()),
+            ..ListQuery::default()
+        };
+        assert!(matches!(listing(other), Ok(None)));
+        let base = ListQuery {
+            ruleset: Some("Base".into()),
+            ..ListQuery::default()
+        };
+        assert!(matches!(listing(base), Ok(Some(_))));
+
+        for limit in [0, 1000]
+        {
+            let query = ListQuery {
+                limit: Some(limit),
+                ..ListQuery::default()
+            };
+            let limit = listing(query).unwrap().unwrap().limit;
+            assert!((1..=MAX_PAGE_SIZE).contains(&limit));
+        }
+
+        let bad = [
+            ListQuery {
+                from: Some("yesterday".into()),
+                ..ListQuery::default()
+            },
+            ListQuery {
+                to: Some("1969-12-30".into()),
+                ..ListQuery::default()
+            },
+            ListQuery {
+                cursor: Some("page-2".into()),
+                ..ListQuery::default()
+            },
+        ];
+        for query in bad
+        {
+            assert!(listing(query).is_err());
+        }
+    }
+
+    // Every test runs on each backend, the Mongo ones need a database running
+    macro_rules! storage_tests {
+        ($($test:ident),* $(,)?) => {
+            mod memory
+            {
+                $(
+                    #[tokio::test]
+                    async fn $test() -> super::DatabaseResult<()>
+                    {
+                        super::$test(&super::Memory::default()).await
+                    }
+                )*
+            }
+
+            mod sqlite
+            {
+                $(
+                    #[tokio::test]
+                    async fn $test() -> super::DatabaseResult<()>
+                    {
+                        super::$test(&super::Sqlite::open(":memory:")?).await
+                    }
+                )*
+            }
+
+            // Left out of a plain `cargo test`, see the README for running them
+            mod mongo
+            {
+                $(
+                    #[tokio::test(flavor = "multi_thread")]
+                    #[ignore = "needs MongoDB running"]
+                    async fn $test() -> super::DatabaseResult<()>
+                    {
+                        let guard = super::super::mongo::test::Guard::new().await?;
+                        super::$test(guard.db()).await
+                    }
+                )*
+            }
+        };
+    }
+
+    storage_tests!(
+        test_can_register_and_find_user,
+        test_register_user_errors,
+        test_not_registered_user_can_not_login,
+        test_user_can_login,
+        test_legacy_password_is_rehashed,
+        test_session_finds_user,
+        test_user_can_create_game,
+        test_challenge_creator,
+        test_get_home_content,
+        test_can_accept_game,
+        test_challenge_is_accepted_once,
+        test_failed_accept_keeps_challenge,
+        test_challenge_is_removed,
+        test_can_get_active_games,
+        test_cannot_get_non_existing_game,
+        test_can_get_game,
+        test_can_play_move,
+        test_stale_move_is_a_conflict,
+        test_game_is_completed,
+        test_game_is_resigned,
+        test_get_users_games,
+        test_opening_index,
+        test_symmetric_moves_are_counted_together,
+        test_one_puzzle_per_game,
+        test_imported_games_are_finished,
+        test_imported_pass_is_replayed,
+        test_games_are_paged,
+        test_games_are_filtered,
+        test_challenges_are_paged,
+    );
 }
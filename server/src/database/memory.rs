@@ -0,0 +1,540 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use async_trait::async_trait;
+use mongodb::bson::{oid::ObjectId, DateTime};
+use shared::{engine::canonical, model::*};
+
+use super::*;
+use crate::model::{game::Game, position::PositionMove, session::Token, user::User};
+
+
+#[derive(Default)]
+struct Data
+{
+    users:     Vec<User>,
+    sessions:  HashMap<String, Token>,
+    // In the order they were made, like Mongo lists them
+    games:     Vec<(ObjectId, Game)>,
+    positions: Vec<PositionMove>,
+    puzzles:   Vec<PuzzleResource>,
+}
+
+impl Data
+{
+    fn user(&mut self, id: ObjectId) -> DatabaseResult<&mut User>
+    {
+        self.users
+            .iter_mut()
+            .find(|user| user._id == id)
+            .ok_or(DatabaseError::UserDontExist)
+    }
+
+    fn game(&mut self, id: ObjectId) -> DatabaseResult<&mut Game>
+    {
+        self.games
+            .iter_mut()
+            .find(|(game_id, _)| *game_id == id)
+            .map(|(_, game)| game)
+            .ok_or(DatabaseError::NoDocumentFound)
+    }
+
+    fn list_games<F: Fn(ObjectId, &Game) -> bool>(&self, filter: F) -> Vec<OnGoingGame>
+    {
+        self.games
+            .iter()
+            .filter(|(id, game)| filter(*id, game))
+            .map(|(id, game)| game.ongoing(*id))
+            .collect()
+    }
+
+    // Every complete game not yet in the opening index, see `Storage::index_games`
+    fn index_games(&mut self) -> usize
+    {
+        let Data {
+            games,
+            positions,
+            ..
+        } = self;
+
+        let mut count = 0;
+        for (_, game) in games.iter_mut().filter(|(_, game)| game.complete && !game.indexed)
+        {
+            game.indexed = true;
+            count += 1;
+
+            let (winner, moves) = opening_moves(game);
+            for (key, r#move) in moves
+            {
+                let pos = match positions.iter().position(|p| p.key == key && p.r#move == r#move)
+                {
+                    Some(i) => &mut positions[i],
+                    None =>
+                    {
+                        positions.push(PositionMove {
+                            key,
+                            r#move,
+                            white: 0,
+                            black: 0,
+                            draws: 0,
+                        });
+                        positions.last_mut().unwrap()
+                    },
+                };
+
+                match winner
+                {
+                    Some(Color::White) => pos.white += 1,
+                    Some(Color::Black) => pos.black += 1,
+                    None => pos.draws += 1,
+                }
+            }
+        }
+        count
+    }
+}
+
+// The page of `items` in the order the listing asks for
+fn sorted<T, F: Fn(&T) -> ObjectId>(mut items: Vec<T>, listing: &Listing, id: F) -> Page<T>
+{
+    items.sort_by_key(|item| id(item));
+    if listing.sort == SortOrder::Newest
+    {
+        items.reverse();
+    }
+    items.truncate(listing.limit + 1);
+    page(items, listing.limit, id)
+}
+
+/*
+ * Everything is kept in memory and lost when the server stops. Used by the
+ * tests, and to run the server without a database.
+ */
+#[derive(Default)]
+pub struct Memory
+{
+    data: Arc<Mutex<Data>>,
+}
+
+impl Memory
+{
+    // For a lookup or two, nothing awaits while the lock is held
+    fn data(&self) -> MutexGuard<'_, Data>
+    {
+        self.data.lock().unwrap()
+    }
+
+    // Scans over every game run on the blocking pool, like the Sqlite queries
+    async fn blocking<T, F>(&self, f: F) -> DatabaseResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Data) -> DatabaseResult<T> + Send + 'static,
+    {
+        let data = Arc::clone(&self.data);
+        task::spawn_blocking(move || f(&mut data.lock().unwrap()))
+            .await
+            .expect("memory scan")
+    }
+}
+
+#[async_trait]
+impl Storage for Memory
+{
+    async fn login(&self, cred: UserCredentials) -> DatabaseResult<ObjectId>
+    {
+        let user = self.data().users.iter().find(|user| user.name == cred.name).cloned();
+
+        // Hashing is slow, so it is done without holding the lock
+        let password_hash = user.as_ref().map(|user| user.password_hash.clone());
+        let valid = verify_blocking(cred.password.clone(), password_hash).await;
+        let user = match user
+        {
+            Some(user) if valid => user,
+            _ => return Err(DatabaseError::UserDontExist),
+        };
+
+        if is_legacy(&user.password_hash)
+        {
+            let password_hash = hash_blocking(cred.password).await;
+            self.set_password_hash(user._id, &password_hash).await?;
+        }
+        Ok(user._id)
+    }
+
+    async fn register_user(&self, cred: UserCredentials) -> DatabaseResult<ObjectId>
+    {
+        let user = User::new(cred.name, hash_blocking(cred.password).await);
+
+        let mut data = self.data();
+        if data.users.iter().any(|other| other.name == user.name)
+        {
+            return Err(DatabaseError::UserAlreadyExist);
+        }
+        let id = user._id;
+        data.users.push(user);
+        Ok(id)
+    }
+
+    async fn set_password_hash(&self, user: ObjectId, password_hash: &str) -> DatabaseResult<()>
+    {
+        self.data().user(user)?.password_hash = password_hash.to_string();
+        Ok(())
+    }
+
+    async fn register_bot(&self, name: &str, strength: Strength) -> DatabaseResult<ObjectId>
+    {
+        let mut data = self.data();
+        match data.users.iter().find(|user| user.name == name)
+        {
+            Some(user) if user.bot.is_some() => Ok(user._id),
+            Some(_) => Err(DatabaseError::UserAlreadyExist),
+            None =>
+            {
+                let user = User::bot(name, strength);
+                let id = user._id;
+                data.users.push(user);
+                Ok(id)
+            },
+        }
+    }
+
+    async fn get_bots(&self) -> DatabaseResult<Vec<User>>
+    {
+        Ok(self.data().users.iter().filter(|user| user.bot.is_some()).cloned().collect())
+    }
+
+    async fn find_user_by_id(&self, id: ObjectId) -> DatabaseResult<User>
+    {
+        self.data().user(id).map(|user| user.clone())
+    }
+
+    async fn find_user_by_name(&self, name: &str) -> DatabaseResult<User>
+    {
+        let data = self.data();
+        data.users
+            .iter()
+            .find(|user| user.name == name)
+            .cloned()
+            .ok_or(DatabaseError::UserDontExist)
+    }
+
+    async fn create_session(&self, user: ObjectId) -> DatabaseResult<Session>
+    {
+        let token = new_session(user);
+        self.data().sessions.insert(token._id.clone(), token.clone());
+        Ok(token.into())
+    }
+
+    async fn find_session(&self, token: &str) -> DatabaseResult<ObjectId>
+    {
+        let mut data = self.data();
+        match data.sessions.get(token)
+        {
+            Some(session) if session.expires > DateTime::now() => Ok(session.user),
+            Some(_) =>
+            {
+                data.sessions.remove(token);
+                Err(DatabaseError::InvalidSession)
+            },
+            None => Err(DatabaseError::InvalidSession),
+        }
+    }
+
+    async fn delete_session(&self, token: &str) -> DatabaseResult<()>
+    {
+        self.data().sessions.remove(token);
+        Ok(())
+    }
+
+    async fn create_game(&self, user_id: ObjectId, limit: u32) -> DatabaseResult<ObjectId>
+    {
+        let mut data = self.data();
+        let user = data.user(user_id).map_err(|_| DatabaseError::NoDocumentFound)?;
+
+        if user.create_games.len() as u32 > limit
+        {
+            return Err(DatabaseError::TooManyGames);
+        }
+
+        let id = ObjectId::new();
+        user.create_games.push(id);
+        Ok(id)
+    }
+
+    async fn accept_game(
+        &self,
+        user: ObjectId,
+        form: CreateGameFormResponse,
+    ) -> DatabaseResult<AcceptGame>
+    {
+        let CreateGameFormResponse {
+            game,
+            creator,
+        } = form;
+
+        let mut data = self.data();
+        let players = colors(creator, user);
+        let users = [data.user(players[0])?.clone(), data.user(players[1])?.clone()];
+
+        // Taken by someone else already
+        let invites = &mut data.user(creator)?.create_games;
+        if !invites.contains(&game)
+        {
+            return Err(DatabaseError::Conflict);
+        }
+        invites.retain(|id| *id != game);
+
+        let id = ObjectId::new();
+        data.games.push((id, Game::new(users)));
+        for player in players
+        {
+            data.user(player)?.active_games.push(id);
+        }
+
+        Ok(AcceptGame {
+            object_id: id,
+            game,
+        })
+    }
+
+    async fn find_challenge_creator(&self, id: ObjectId) -> DatabaseResult<ObjectId>
+    {
+        self.data()
+            .users
+            .iter()
+            .find(|user| user.create_games.contains(&id))
+            .map(|user| user._id)
+            .ok_or(DatabaseError::NoDocumentFound)
+    }
+
+    async fn remove_challenge(&self, creator: ObjectId, id: ObjectId) -> DatabaseResult<()>
+    {
+        let mut data = self.data();
+        let invites = &mut data.user(creator)?.create_games;
+        if !invites.contains(&id)
+        {
+            return Err(DatabaseError::Conflict);
+        }
+        invites.retain(|game| *game != id);
+        Ok(())
+    }
+
+    async fn home(&self, id: ObjectId) -> DatabaseResult<Vec<CreateGameChallengeBundle>>
+    {
+        Ok(self
+            .data()
+            .users
+            .iter()
+            .filter(|user| user._id != id)
+            .map(|user| CreateGameChallengeBundle {
+                name:       user.name.clone(),
+                games:      user.create_games.clone(),
+                creator_id: user._id,
+            })
+            .collect())
+    }
+
+    async fn find_challenges(
+        &self,
+        id: ObjectId,
+        listing: &Listing,
+    ) -> DatabaseResult<Page<CreateGameChallenge>>
+    {
+        let data = self.data();
+        let challenges =
+            data.users
+                .iter()
+                .filter(|user| user._id != id)
+                .filter(|user| listing.player.as_ref().map_or(true, |name| *name == user.name))
+                .flat_map(|user| {
+                    user.create_games.iter().filter(|game| listing.includes(**game)).map(
+                        move |game| CreateGameChallenge {
+                            name:    user.name.clone(),
+                            creator: user._id,
+                            _id:     *game,
+                        },
+                    )
+                })
+                .collect();
+        Ok(sorted(challenges, listing, |challenge| challenge._id))
+    }
+
+    async fn get_old_games(&self) -> DatabaseResult<Vec<OnGoingGame>>
+    {
+        self.blocking(|data| Ok(data.list_games(|_, game| game.complete))).await
+    }
+
+    async fn get_users_games(&self, id: ObjectId) -> DatabaseResult<Vec<OnGoingGame>>
+    {
+        self.blocking(move |data| {
+            Ok(data.list_games(|_, game| game.has_player(id) && !game.complete))
+        })
+        .await
+    }
+
+    async fn get_active_games(&self) -> DatabaseResult<Vec<OnGoingGame>>
+    {
+        self.blocking(|data| Ok(data.list_games(|_, game| !game.complete))).await
+    }
+
+    async fn get_game_by_id(&self, id: ObjectId) -> DatabaseResult<GameResource>
+    {
+        self.data().game(id).map(|game| game.resource(id))
+    }
+
+    async fn find_games(&self, listing: &Listing) -> DatabaseResult<Page<OnGoingGame>>
+    {
+        let filter = listing.clone();
+        let games = self
+            .blocking(move |data| {
+                Ok(data.list_games(|id, game| {
+                    game.complete == filter.complete
+                        && filter.includes(id)
+                        && filter.player.as_ref().map_or(true, |name| game.has_player_named(name))
+                        && filter.result.map_or(true, |result| game.result == Some(result))
+                }))
+            })
+            .await?;
+        Ok(sorted(games, listing, |game| game.game_object_id))
+    }
+
+    async fn play_move(&self, player_id: ObjectId, r#move: Move) -> DatabaseResult<()>
+    {
+        let mut data = self.data();
+        let game = data.game(r#move.game_id)?;
+        if !game.has_player(player_id) || game.complete
+        {
+            return Err(DatabaseError::NoDocumentFound);
+        }
+
+        // Played on a copy, so a failed move leaves the game as it was
+        let mut played = game.clone();
+        play(&mut played, player_id, r#move)?;
+        *game = played;
+        Ok(())
+    }
+
+    async fn import_game(&self, game: Game) -> DatabaseResult<ObjectId>
+    {
+        let id = ObjectId::new();
+        self.data().games.push((id, game));
+        Ok(id)
+    }
+
+    async fn complete_game(&self, game_id: ObjectId) -> DatabaseResult<()>
+    {
+        if let Ok(game) = self.data().game(game_id)
+        {
+            game.complete = true;
+        }
+        Ok(())
+    }
+
+    async fn resign_game(&self, game_id: ObjectId, player_id: ObjectId) -> DatabaseResult<()>
+    {
+        let mut data = self.data();
+        let game = data.game(game_id)?;
+        if !game.has_player(player_id) || game.complete
+        {
+            return Err(DatabaseError::NoDocumentFound);
+        }
+
+        resign(game, player_id);
+        Ok(())
+    }
+
+    async fn set_review(&self, game_id: ObjectId, review: Review) -> DatabaseResult<()>
+    {
+        if let Ok(game) = self.data().game(game_id)
+        {
+            game.review = Some(review);
+        }
+        Ok(())
+    }
+
+    async fn index_games(&self) -> DatabaseResult<usize>
+    {
+        self.blocking(|data| Ok(data.index_games())).await
+    }
+
+    async fn explore(&self, board: &Board) -> DatabaseResult<Vec<ExplorerMove>>
+    {
+        let (key, sym) = canonical(board);
+        let positions = self
+            .data()
+            .positions
+            .iter()
+            .filter(|pos| pos.key == key as i64)
+            .cloned()
+            .collect();
+
+        Ok(explorer_moves(&sym, positions))
+    }
+
+    async fn add_puzzle(
+        &self,
+        game: ObjectId,
+        moves: &[Option<InternalMove>],
+        length: u32,
+    ) -> DatabaseResult<()>
+    {
+        let mut data = self.data();
+        if !data.puzzles.iter().any(|puzzle| puzzle.game == game)
+        {
+            data.puzzles.push(PuzzleResource {
+                _id: ObjectId::new(),
+                game,
+                moves: moves.to_vec(),
+                length,
+            });
+        }
+        Ok(())
+    }
+
+    async fn get_puzzle(&self, id: ObjectId) -> DatabaseResult<PuzzleResource>
+    {
+        let data = self.data();
+        data.puzzles
+            .iter()
+            .find(|puzzle| puzzle._id == id)
+            .cloned()
+            .ok_or(DatabaseError::NoDocumentFound)
+    }
+
+    async fn random_puzzle(&self) -> DatabaseResult<PuzzleResource>
+    {
+        let data = self.data();
+        if data.puzzles.is_empty()
+        {
+            return Err(DatabaseError::NoDocumentFound);
+        }
+
+        let mut bytes = [0_u8; 4];
+        getrandom::getrandom(&mut bytes).expect("random");
+        let i = u32::from_le_bytes(bytes) as usize % data.puzzles.len();
+        Ok(data.puzzles[i].clone())
+    }
+}
+
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[tokio::test]
+    async fn test_expired_session() -> DatabaseResult<()>
+    {
+        let memory = Memory::default();
+        let session = memory.create_session(ObjectId::new()).await?;
+
+        memory.data().sessions.get_mut(&session.token).unwrap().expires = DateTime::now();
+        let res = memory.find_session(&session.token).await;
+        assert!(matches!(res, Err(DatabaseError::InvalidSession)));
+        assert!(memory.data().sessions.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,874 @@
+use async_trait::async_trait;
+use futures::stream::StreamExt;
+use mongodb::{
+    bson::{self, doc, oid::ObjectId, DateTime},
+    error::Error,
+    options::{ClientOptions, Credential, IndexOptions, UpdateOptions},
+    Client, Database, IndexModel,
+};
+use shared::{engine::canonical, model::*};
+
+use super::*;
+use crate::{
+    config::DatabaseConfig,
+    model::{game::Game, position::PositionMove, session::Token, user::User},
+};
+
+
+// collections types
+pub const USERS: &str = "users";
+pub const GAMES: &str = "games";
+pub const POSITIONS: &str = "positions";
+pub const PUZZLES: &str = "puzzles";
+pub const SESSIONS: &str = "sessions";
+
+
+#[derive(Clone)]
+pub struct Mongo
+{
+    db: Database,
+}
+
+impl Mongo
+{
+    pub async fn connect(config: &DatabaseConfig) -> Result<Self, Error>
+    {
+        let mut client_options = ClientOptions::parse(&config.url).await?;
+        client_options.app_name = Some("My App".to_string());
+
+        client_options.credential = Some(
+            Credential::builder()
+                .username(Some(config.user.clone()))
+                .password(Some(config.password.clone()))
+                .build(),
+        );
+
+        let client = Client::with_options(client_options)?;
+        let mongo = Self {
+            db: client.database(&config.name)
+        };
+        mongo.create_indexes().await?;
+        mongo.fill_results().await?;
+        Ok(mongo)
+    }
+
+    // Games that ended before results were stored get the one their board
+    // shows, so listings can find them by result
+    async fn fill_results(&self) -> Result<(), Error>
+    {
+        let games = self.db.collection::<bson::Document>(GAMES);
+        let mut cursor = games.find(doc! { "complete": true, "result": null }, None).await?;
+
+        while let Some(doc) = cursor.next().await
+        {
+            let doc = doc?;
+            let id = doc.get("_id").cloned();
+            let game: Game = bson::from_document(doc).map_err(Error::from)?;
+            if let Some(result) = game.board_result()
+            {
+                let update = doc! { "$set": { "result": bson::to_bson(&result)? } };
+                games.update_one(doc! { "_id": id }, update, None).await?;
+            }
+        }
+        Ok(())
+    }
+
+    // Indexes the collections need, made at startup
+    async fn create_indexes(&self) -> Result<(), Error>
+    {
+        let col = self.db.collection::<Token>(SESSIONS);
+
+        let options = IndexOptions::builder().expire_after(std::time::Duration::ZERO).build();
+        let index = IndexModel::builder().keys(doc! { "expires": 1 }).options(options).build();
+        col.create_index(index, None).await?;
+        Ok(())
+    }
+
+    async fn count_create_game(&self, id: &ObjectId) -> DatabaseResult<u32>
+    {
+        let col = self.db.collection::<User>(USERS);
+        let sum = "sum";
+        let r#match = doc! { "$match": { "_id": &id } };
+        let crit = doc! { "$project": { sum: { "$size": "$create_games" } } };
+
+        match col.aggregate([r#match, crit], None).await?.next().await
+        {
+            Some(doc) =>
+            {
+                let num = doc?.get(sum).unwrap().as_i32().unwrap() as u32;
+                Ok(num)
+            },
+            None => Err(DatabaseError::NoDocumentFound),
+        }
+    }
+
+    /*
+     * Takes the invite away from the creator. Only one caller can take a
+     * given invite, a single update both checks that it is there and pulls
+     * it, so everyone else gets a conflict.
+     */
+    async fn remove_user_create_game(
+        &self,
+        creator: &ObjectId,
+        game: &ObjectId,
+    ) -> DatabaseResult<()>
+    {
+        let col = self.db.collection::<User>(USERS);
+
+        let filter = doc! { "_id": creator, "create_games": game };
+        let update = doc! { "$pull": { "create_games": game } };
+        match col.update_one(filter, update, None).await?.modified_count
+        {
+            0 => Err(DatabaseError::Conflict),
+            _ => Ok(()),
+        }
+    }
+
+    // Puts back an invite taken by `remove_user_create_game`
+    async fn restore_user_create_game(
+        &self,
+        creator: &ObjectId,
+        game: &ObjectId,
+    ) -> DatabaseResult<()>
+    {
+        let col = self.db.collection::<User>(USERS);
+
+        col.update_one(doc! { "_id": creator }, doc! { "$push": { "create_games": game } }, None)
+            .await?;
+        Ok(())
+    }
+
+    async fn add_game_id_to_users(
+        &self,
+        id: &ObjectId,
+        user1: &ObjectId,
+        user2: &ObjectId,
+    ) -> DatabaseResult<()>
+    {
+        let col = self.db.collection::<User>(USERS);
+
+        let user = doc! {"$or": [ {"_id": &user1}, {"_id": &user2} ]};
+        let update = doc! { "$push": { "active_games": id } };
+
+        match col.update_many(user, update, None).await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // The games matching `filter`, as listed on the home page. `page` are the
+    // stages that order and cut the list
+    async fn list_games(
+        &self,
+        filter: bson::Document,
+        page: &[bson::Document],
+    ) -> DatabaseResult<Vec<OnGoingGame>>
+    {
+        let col = self.db.collection::<Game>(GAMES);
+
+        let mut pipeline = vec![doc! { "$match": filter }];
+        pipeline.extend_from_slice(page);
+        pipeline.push(doc! {
+            "$project": {
+                "players": "$players.name",
+                "game_object_id": "$_id",
+            }
+        });
+
+        Ok(col
+            .aggregate(pipeline, None)
+            .await?
+            .map(|doc| {
+                let doc = doc.unwrap();
+                let g: OnGoingGame = bson::from_document(doc).unwrap();
+                g
+            })
+            .collect()
+            .await)
+    }
+
+    /*
+     * Counts the game in the opening index, all of it or none of it. There
+     * are no transactions outside a replica set, so if a count fails the
+     * ones made so far are taken back.
+     */
+    async fn index_game(&self, game: &Game) -> DatabaseResult<()>
+    {
+        let col = self.db.collection::<PositionMove>(POSITIONS);
+        let (winner, moves) = opening_moves(game);
+        let result = match winner
+        {
+            Some(Color::White) => "white",
+            Some(Color::Black) => "black",
+            None => "draws",
+        };
+
+        let mut counted = Vec::new();
+        for (key, r#move) in moves
+        {
+            let stored = bson::to_bson(&r#move).map_err(mongodb::error::Error::from)?;
+            let filter = doc! { "key": key, "move": stored };
+            let options = UpdateOptions::builder().upsert(true).build();
+
+            if let Err(e) =
+                col.update_one(filter.clone(), doc! { "$inc": { result: 1 } }, options).await
+            {
+                for filter in counted
+                {
+                    let _ = col.update_one(filter, doc! { "$inc": { result: -1 } }, None).await;
+                }
+                return Err(e.into());
+            }
+            counted.push(filter);
+        }
+        Ok(())
+    }
+}
+
+/*
+ * The conditions of a listing that games and challenges share. `id` and
+ * `name` are the fields with the id and the player's name.
+ */
+fn listing_filter(listing: &Listing, id: &str, name: &str) -> bson::Document
+{
+    let mut filter = doc! {};
+    if let Some(player) = &listing.player
+    {
+        filter.insert(name, player);
+    }
+
+    let mut range = doc! {};
+    if let Some(after) = listing.after
+    {
+        range.insert("$gt", after);
+    }
+    if let Some(before) = listing.before
+    {
+        range.insert("$lt", before);
+    }
+    if !range.is_empty()
+    {
+        filter.insert(id, range);
+    }
+    filter
+}
+
+// The stages that put a listing in order and cut it after the page
+fn listing_page(listing: &Listing, id: &str) -> [bson::Document; 2]
+{
+    let order = match listing.sort
+    {
+        SortOrder::Newest => -1,
+        SortOrder::Oldest => 1,
+    };
+    [doc! { "$sort": { id: order } }, doc! { "$limit": (listing.limit + 1) as i64 }]
+}
+
+#[async_trait]
+impl Storage for Mongo
+{
+    async fn login(&self, cred: UserCredentials) -> DatabaseResult<ObjectId>
+    {
+        let col = self.db.collection::<User>(USERS);
+
+        // A wrong password looks the same as a missing user, and takes as long
+        let filter = doc! { "name": cred.name.as_str() };
+        let user = col.find_one(filter, None).await?;
+        let password_hash = user.as_ref().map(|user| user.password_hash.clone());
+        let valid = verify_blocking(cred.password.clone(), password_hash).await;
+        let user = match user
+        {
+            Some(user) if valid => user,
+            _ => return Err(DatabaseError::UserDontExist),
+        };
+
+        // Old accounts still have an unsalted hash, replace it while the
+        // password is known
+        if is_legacy(&user.password_hash)
+        {
+            let password_hash = hash_blocking(cred.password).await;
+            self.set_password_hash(user._id, &password_hash).await?;
+        }
+        Ok(user._id)
+    }
+
+    async fn register_user(&self, cred: UserCredentials) -> DatabaseResult<ObjectId>
+    {
+        let col = self.db.collection::<User>(USERS);
+        let user = User::new(cred.name, hash_blocking(cred.password).await);
+
+        // Check if user with same name exists
+        let filter = doc! { "name": user.name.as_str() };
+        match col.find_one(filter, None).await?
+        {
+            Some(_) => Err(DatabaseError::UserAlreadyExist),
+            None => match col.insert_one(&user, None).await
+            {
+                Ok(_) => Ok(user._id),
+                Err(e) => Err(DatabaseError::DbError(e)),
+            },
+        }
+    }
+
+    async fn set_password_hash(&self, user: ObjectId, password_hash: &str) -> DatabaseResult<()>
+    {
+        let col = self.db.collection::<User>(USERS);
+        let update = doc! { "$set": { "password_hash": password_hash } };
+        let res = col.update_one(doc! { "_id": user }, update, None).await?;
+        match res.matched_count
+        {
+            0 => Err(DatabaseError::UserDontExist),
+            _ => Ok(()),
+        }
+    }
+
+    async fn register_bot(&self, name: &str, strength: Strength) -> DatabaseResult<ObjectId>
+    {
+        let col = self.db.collection::<User>(USERS);
+
+        match col.find_one(doc! { "name": name }, None).await?
+        {
+            Some(user) if user.bot.is_some() => Ok(user._id),
+            Some(_) => Err(DatabaseError::UserAlreadyExist),
+            None =>
+            {
+                let user = User::bot(name, strength);
+                col.insert_one(&user, None).await?;
+                Ok(user._id)
+            },
+        }
+    }
+
+    async fn get_bots(&self) -> DatabaseResult<Vec<User>>
+    {
+        let col = self.db.collection::<User>(USERS);
+        let filter = doc! { "bot": { "$ne": null } };
+
+        Ok(col
+            .find(filter, None)
+            .await?
+            .filter_map(|res| async { res.ok() })
+            .collect()
+            .await)
+    }
+
+    async fn find_user_by_id(&self, id: ObjectId) -> DatabaseResult<User>
+    {
+        let col = self.db.collection::<User>(USERS);
+
+        let filter = doc! { "_id": id };
+        match col.find_one(filter, None).await?
+        {
+            Some(user) => Ok(user),
+            None => Err(DatabaseError::UserDontExist),
+        }
+    }
+
+    async fn find_user_by_name(&self, name: &str) -> DatabaseResult<User>
+    {
+        let col = self.db.collection::<User>(USERS);
+
+        let filter = doc! { "name": name };
+        match col.find_one(filter, None).await?
+        {
+            Some(user) => Ok(user),
+            None => Err(DatabaseError::UserDontExist),
+        }
+    }
+
+    async fn create_session(&self, user: ObjectId) -> DatabaseResult<Session>
+    {
+        let col = self.db.collection::<Token>(SESSIONS);
+
+        let token = new_session(user);
+        col.insert_one(&token, None).await?;
+        Ok(token.into())
+    }
+
+    async fn find_session(&self, token: &str) -> DatabaseResult<ObjectId>
+    {
+        let col = self.db.collection::<Token>(SESSIONS);
+
+        // Expired sessions are only removed now and then, so check the time too
+        let filter = doc! { "_id": token, "expires": { "$gt": DateTime::now() } };
+        match col.find_one(filter, None).await?
+        {
+            Some(session) => Ok(session.user),
+            None => Err(DatabaseError::InvalidSession),
+        }
+    }
+
+    async fn delete_session(&self, token: &str) -> DatabaseResult<()>
+    {
+        let col = self.db.collection::<Token>(SESSIONS);
+        col.delete_one(doc! { "_id": token }, None).await?;
+        Ok(())
+    }
+
+    async fn create_game(&self, user_id: ObjectId, limit: u32) -> DatabaseResult<ObjectId>
+    {
+        let col = self.db.collection::<User>(USERS);
+
+        if self.count_create_game(&user_id).await? > limit
+        {
+            return Err(DatabaseError::TooManyGames);
+        }
+
+        let user = doc! { "_id": &user_id };
+
+        let id = ObjectId::new();
+        let update = doc! { "$push": { "create_games": id } };
+
+        match col.update_one(user, update, None).await
+        {
+            Ok(_) => Ok(id),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn accept_game(
+        &self,
+        user: ObjectId,
+        form: CreateGameFormResponse,
+    ) -> DatabaseResult<AcceptGame>
+    {
+        let CreateGameFormResponse {
+            game,
+            creator,
+        } = form;
+
+        let games = self.db.collection::<Game>(GAMES);
+
+        // Everything that can fail without changing anything goes first
+        let players = colors(creator, user);
+        let (u1, u2) =
+            tokio::join!(self.find_user_by_id(players[0]), self.find_user_by_id(players[1]));
+        let users = [u1?, u2?];
+
+        /*
+         * Transactions need Mongo to run as a replica set, so the steps are
+         * undone by hand instead. Taking the invite decides who gets the
+         * game, and if a later step fails the invite is given back for
+         * someone else to accept.
+         */
+        self.remove_user_create_game(&creator, &game).await?;
+
+        let id = match games.insert_one(Game::new(users), None).await
+        {
+            Ok(res) => res.inserted_id.as_object_id().unwrap(),
+            Err(e) =>
+            {
+                self.restore_user_create_game(&creator, &game).await?;
+                return Err(e.into());
+            },
+        };
+
+        if let Err(e) = self.add_game_id_to_users(&id, &creator, &user).await
+        {
+            games.delete_one(doc! { "_id": id }, None).await?;
+            self.restore_user_create_game(&creator, &game).await?;
+            return Err(e);
+        }
+
+        let accept = AcceptGame {
+            object_id: id,
+            game,
+        };
+
+        Ok(accept)
+    }
+
+    async fn find_challenge_creator(&self, id: ObjectId) -> DatabaseResult<ObjectId>
+    {
+        let col = self.db.collection::<User>(USERS);
+        col.find_one(doc! { "create_games": id }, None)
+            .await?
+            .map(|user| user._id)
+            .ok_or(DatabaseError::NoDocumentFound)
+    }
+
+    async fn remove_challenge(&self, creator: ObjectId, id: ObjectId) -> DatabaseResult<()>
+    {
+        self.remove_user_create_game(&creator, &id).await
+    }
+
+    async fn home(&self, id: ObjectId) -> DatabaseResult<Vec<CreateGameChallengeBundle>>
+    {
+        let col = self.db.collection::<User>(USERS);
+        let filter = doc! { "_id": { "$ne": id } };
+
+        Ok(col
+            .find(filter, None)
+            .await?
+            .map(|res| {
+                let res = res.unwrap();
+
+                CreateGameChallengeBundle {
+                    name:       res.name,
+                    games:      res.create_games,
+                    creator_id: res._id,
+                }
+            })
+            .collect::<Vec<_>>()
+            .await)
+    }
+
+    async fn find_challenges(
+        &self,
+        id: ObjectId,
+        listing: &Listing,
+    ) -> DatabaseResult<Page<CreateGameChallenge>>
+    {
+        let col = self.db.collection::<User>(USERS);
+
+        // One document for every invite, with its creator
+        let mut pipeline = vec![
+            doc! { "$match": { "_id": { "$ne": id } } },
+            doc! { "$unwind": "$create_games" },
+            doc! { "$match": listing_filter(listing, "create_games", "name") },
+        ];
+        pipeline.extend_from_slice(&listing_page(listing, "create_games"));
+        pipeline.push(doc! {
+            "$project": { "_id": "$create_games", "creator": "$_id", "name": "$name" }
+        });
+
+        let challenges = col
+            .aggregate(pipeline, None)
+            .await?
+            .map(|doc| {
+                let challenge: CreateGameChallenge = bson::from_document(doc.unwrap()).unwrap();
+                challenge
+            })
+            .collect()
+            .await;
+        Ok(page(challenges, listing.limit, |challenge| challenge._id))
+    }
+
+    async fn get_old_games(&self) -> DatabaseResult<Vec<OnGoingGame>>
+    {
+        self.list_games(doc! { "complete": true }, &[]).await
+    }
+
+    async fn get_users_games(&self, id: ObjectId) -> DatabaseResult<Vec<OnGoingGame>>
+    {
+        self.list_games(doc! { "players._id": id, "complete": false }, &[]).await
+    }
+
+    async fn get_active_games(&self) -> DatabaseResult<Vec<OnGoingGame>>
+    {
+        self.list_games(doc! { "complete": false }, &[]).await
+    }
+
+    async fn get_game_by_id(&self, id: ObjectId) -> DatabaseResult<GameResource>
+    {
+        let col = self.db.collection::<Game>(GAMES);
+        col.aggregate(
+            [
+                doc! {
+                    "$match": { "_id": &id }
+                },
+                doc! {
+                    "$project": {
+                        "players": "$players.name",
+                        "board": "$board",
+                        "complete": "$complete",
+                        "move_list": "$move_list",
+                        "review": "$review",
+                        "result": "$result",
+                        "record": "$record",
+                    }
+                },
+            ],
+            None,
+        )
+        .await?
+        .map(|doc| {
+            let doc = doc.unwrap();
+            let g: GameResource = bson::from_document(doc).unwrap();
+            g
+        })
+        .next()
+        .await
+        .ok_or(DatabaseError::NoDocumentFound)
+    }
+
+    async fn find_games(&self, listing: &Listing) -> DatabaseResult<Page<OnGoingGame>>
+    {
+        let mut filter = listing_filter(listing, "_id", "players.name");
+        filter.insert("complete", listing.complete);
+        if let Some(result) = listing.result
+        {
+            filter.insert("result", bson::to_bson(&result).map_err(Error::from)?);
+        }
+
+        let games = self.list_games(filter, &listing_page(listing, "_id")).await?;
+        Ok(page(games, listing.limit, |game| game.game_object_id))
+    }
+
+    async fn play_move(&self, player_id: ObjectId, r#move: Move) -> DatabaseResult<()>
+    {
+        let col = self.db.collection::<Game>(GAMES);
+
+        let query = doc! {
+            "_id": r#move.game_id,
+            "players._id": &player_id,
+            "complete": false,
+        };
+
+        let mut game =
+            col.find_one(query.clone(), None).await?.ok_or(DatabaseError::NoDocumentFound)?;
+
+        // Only replaced if it is still the game that was read, games stored
+        // before there were versions have none
+        let mut filter = query;
+        match game.version
+        {
+            0 => filter.insert("version", doc! { "$in": [0, bson::Bson::Null] }),
+            version => filter.insert("version", version),
+        };
+
+        play(&mut game, player_id, r#move)?;
+        match col.replace_one(filter, game, None).await?.matched_count
+        {
+            0 => Err(DatabaseError::Conflict),
+            _ => Ok(()),
+        }
+    }
+
+    async fn import_game(&self, game: Game) -> DatabaseResult<ObjectId>
+    {
+        let col = self.db.collection::<Game>(GAMES);
+        Ok(col.insert_one(game, None).await?.inserted_id.as_object_id().unwrap())
+    }
+
+    async fn complete_game(&self, game_id: ObjectId) -> DatabaseResult<()>
+    {
+        let col = self.db.collection::<Game>(GAMES);
+        let filter = doc! { "_id": game_id };
+        let update = doc! { "$set": { "complete": true } };
+
+        col.find_one_and_update(filter, update, None)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.into())
+    }
+
+    async fn resign_game(&self, game_id: ObjectId, player_id: ObjectId) -> DatabaseResult<()>
+    {
+        let col = self.db.collection::<Game>(GAMES);
+
+        let query = doc! {
+            "_id": game_id,
+            "players._id": &player_id,
+            "complete": false,
+        };
+
+        let mut game =
+            col.find_one(query.clone(), None).await?.ok_or(DatabaseError::NoDocumentFound)?;
+
+        let mut filter = query;
+        match game.version
+        {
+            0 => filter.insert("version", doc! { "$in": [0, bson::Bson::Null] }),
+            version => filter.insert("version", version),
+        };
+
+        resign(&mut game, player_id);
+        match col.replace_one(filter, game, None).await?.matched_count
+        {
+            0 => Err(DatabaseError::Conflict),
+            _ => Ok(()),
+        }
+    }
+
+    async fn set_review(&self, game_id: ObjectId, review: Review) -> DatabaseResult<()>
+    {
+        let col = self.db.collection::<Game>(GAMES);
+        let review = bson::to_bson(&review).map_err(mongodb::error::Error::from)?;
+
+        col.update_one(doc! { "_id": game_id }, doc! { "$set": { "review": review } }, None)
+            .await?;
+        Ok(())
+    }
+
+    // Each game is marked before it is counted, so several calls running at
+    // once never count a game twice.
+    async fn index_games(&self) -> DatabaseResult<usize>
+    {
+        // Read as documents for the id, a game does not hold its own
+        let games = self.db.collection::<bson::Document>(GAMES);
+        let filter = doc! { "complete": true, "indexed": { "$ne": true } };
+        let update = doc! { "$set": { "indexed": true } };
+
+        let mut count = 0;
+        while let Some(doc) =
+            games.find_one_and_update(filter.clone(), update.clone(), None).await?
+        {
+            let id = doc.get("_id").cloned();
+            let game: Game = bson::from_document(doc).map_err(Error::from)?;
+
+            // Left for the next call if it could not be counted
+            if let Err(e) = self.index_game(&game).await
+            {
+                let unmark = doc! { "$set": { "indexed": false } };
+                games.update_one(doc! { "_id": id }, unmark, None).await?;
+                return Err(e);
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    async fn explore(&self, board: &Board) -> DatabaseResult<Vec<ExplorerMove>>
+    {
+        let col = self.db.collection::<PositionMove>(POSITIONS);
+        let (key, sym) = canonical(board);
+
+        let positions = col
+            .find(doc! { "key": key as i64 }, None)
+            .await?
+            .filter_map(|pos| async move { pos.ok() })
+            .collect()
+            .await;
+
+        Ok(explorer_moves(&sym, positions))
+    }
+
+    async fn add_puzzle(
+        &self,
+        game: ObjectId,
+        moves: &[Option<InternalMove>],
+        length: u32,
+    ) -> DatabaseResult<()>
+    {
+        let col = self.db.collection::<PuzzleResource>(PUZZLES);
+        let moves = bson::to_bson(moves).map_err(mongodb::error::Error::from)?;
+
+        let update = doc! { "$setOnInsert": { "moves": moves, "length": length } };
+        let options = UpdateOptions::builder().upsert(true).build();
+        col.update_one(doc! { "game": game }, update, options).await?;
+        Ok(())
+    }
+
+    async fn get_puzzle(&self, id: ObjectId) -> DatabaseResult<PuzzleResource>
+    {
+        let col = self.db.collection::<PuzzleResource>(PUZZLES);
+        col.find_one(doc! { "_id": id }, None)
+            .await?
+            .ok_or(DatabaseError::NoDocumentFound)
+    }
+
+    async fn random_puzzle(&self) -> DatabaseResult<PuzzleResource>
+    {
+        let col = self.db.collection::<PuzzleResource>(PUZZLES);
+        match col.aggregate([doc! { "$sample": { "size": 1 } }], None).await?.next().await
+        {
+            Some(doc) => Ok(bson::from_document(doc?).map_err(mongodb::error::Error::from)?),
+            None => Err(DatabaseError::NoDocumentFound),
+        }
+    }
+}
+
+
+#[cfg(test)]
+pub(super) mod test
+{
+    use super::*;
+    use crate::{config::Config, database::test::LIMIT};
+
+    // A database of its own for every test, dropped afterwards
+    pub struct Guard
+    {
+        mongo: Mongo,
+    }
+
+    impl Guard
+    {
+        pub async fn new() -> DatabaseResult<Self>
+        {
+            let config = DatabaseConfig {
+                name: format!("{}", uuid::Uuid::new_v4()),
+                ..Config::default().database
+            };
+
+            Ok(Self {
+                mongo: Mongo::connect(&config).await?
+            })
+        }
+
+        pub fn db(&self) -> &Mongo
+        {
+            &self.mongo
+        }
+    }
+
+    impl Drop for Guard
+    {
+        fn drop(&mut self)
+        {
+            use tokio::{runtime::Handle, task};
+
+            task::block_in_place(move || {
+                Handle::current().block_on(async move {
+                    self.mongo.db.drop(None).await.unwrap();
+                });
+            });
+        }
+    }
+
+    async fn reg(guard: &Guard, name: &str) -> DatabaseResult<ObjectId>
+    {
+        let cred = UserCredentials {
+            name: name.into(), password: "password".into()
+        };
+        guard.db().register_user(cred).await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_count_create_game() -> Result<(), DatabaseError>
+    {
+        let guard = Guard::new().await?;
+
+        // Create user#1 and create one game
+        let id = reg(&guard, "sofie").await?;
+        assert!(guard.db().create_game(id, LIMIT).await.is_ok());
+
+
+        // Create user#2 and `n` games
+        let id = reg(&guard, "sivert").await?;
+        let n: u32 = 20;
+
+        for _ in 0..n
+        {
+            assert!(guard.db().create_game(id, LIMIT).await.is_ok());
+        }
+
+        assert_eq!(guard.db().count_create_game(&id).await?, n);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_remove_create_game() -> Result<(), DatabaseError>
+    {
+        let guard = Guard::new().await?;
+        let creator = reg(&guard, "sivert").await?;
+
+        let create_id = guard.db().create_game(creator, LIMIT).await?;
+
+        assert_eq!(guard.db().find_user_by_id(creator).await?.create_games.len(), 1);
+        assert!(guard.db().remove_user_create_game(&creator, &create_id).await.is_ok());
+
+        assert_eq!(guard.db().find_user_by_id(creator).await?.create_games.len(), 0);
+
+        let res = guard.db().remove_user_create_game(&creator, &create_id).await;
+        assert!(matches!(res, Err(DatabaseError::Conflict)));
+
+        guard.db().restore_user_create_game(&creator, &create_id).await?;
+        assert_eq!(guard.db().find_user_by_id(creator).await?.create_games, [create_id]);
+
+
+        Ok(())
+    }
+}
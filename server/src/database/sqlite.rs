@@ -0,0 +1,922 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use async_trait::async_trait;
+use mongodb::bson::{oid::ObjectId, DateTime};
+use rusqlite::{
+    functions::{Context, FunctionFlags},
+    params, params_from_iter, Connection, ErrorCode, OptionalExtension, Params, Row,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use shared::{engine::canonical, model::*};
+
+use super::*;
+use crate::model::{game::Game, position::PositionMove, session::Token, user::User};
+
+
+/*
+ * The schema, one step per version. A database is at the version of the
+ * last step it has run, stored in `user_version`, so only the new steps run
+ * when the server starts. Steps are never changed once released, add a new
+ * one instead.
+ */
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE users (
+        id            TEXT PRIMARY KEY,
+        name          TEXT NOT NULL UNIQUE,
+        password_hash TEXT NOT NULL,
+        bot           TEXT
+    );
+    CREATE TABLE sessions (
+        token   TEXT PRIMARY KEY,
+        user    TEXT NOT NULL REFERENCES users(id),
+        expires INTEGER NOT NULL
+    );
+    CREATE TABLE invites (
+        id      TEXT PRIMARY KEY,
+        creator TEXT NOT NULL REFERENCES users(id)
+    );
+    CREATE TABLE games (
+        id       TEXT PRIMARY KEY,
+        white    TEXT NOT NULL,
+        black    TEXT NOT NULL,
+        complete INTEGER NOT NULL,
+        indexed  INTEGER NOT NULL,
+        game     TEXT NOT NULL
+    );
+    CREATE TABLE positions (
+        key   INTEGER NOT NULL,
+        move  TEXT NOT NULL,
+        white INTEGER NOT NULL DEFAULT 0,
+        black INTEGER NOT NULL DEFAULT 0,
+        draws INTEGER NOT NULL DEFAULT 0,
+        PRIMARY KEY (key, move)
+    );
+    CREATE TABLE puzzles (
+        id     TEXT PRIMARY KEY,
+        game   TEXT NOT NULL UNIQUE,
+        moves  TEXT NOT NULL,
+        length INTEGER NOT NULL
+    );",
+    "CREATE INDEX invites_creator ON invites(creator);
+    CREATE INDEX games_white ON games(white);
+    CREATE INDEX games_black ON games(black);
+    CREATE INDEX games_complete ON games(complete, indexed);",
+    "ALTER TABLE games ADD COLUMN version INTEGER NOT NULL DEFAULT 0;",
+    "UPDATE games SET game = json_set(game, '$.result', json(board_result(game)))
+     WHERE complete = 1 AND json_extract(game, '$.result') IS NULL
+       AND board_result(game) IS NOT NULL;",
+];
+
+// The result a stored game's board shows, for the steps that fill in results
+fn board_result(ctx: &Context) -> rusqlite::Result<Option<String>>
+{
+    let game: Game = serde_json::from_str(ctx.get_raw(0).as_str()?)
+        .map_err(|e| rusqlite::Error::UserFunctionError(Box::new(e)))?;
+    Ok(game.board_result().map(|result| to_json(&result)))
+}
+
+
+fn migrate(conn: &mut Connection) -> rusqlite::Result<()>
+{
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    conn.create_scalar_function("board_result", 1, FunctionFlags::SQLITE_UTF8, board_result)?;
+
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version)
+    {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", i + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+// Ids are kept as hex, like Mongo shows them
+fn id(row: &Row, i: usize) -> rusqlite::Result<ObjectId>
+{
+    let hex: String = row.get(i)?;
+    ObjectId::parse_str(&hex).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(i, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+// Games, moves and bots are stored as JSON
+fn to_json<T: Serialize>(value: &T) -> String
+{
+    serde_json::to_string(value).expect("serializing to json")
+}
+
+fn json<T: DeserializeOwned>(row: &Row, i: usize) -> rusqlite::Result<T>
+{
+    let s: String = row.get(i)?;
+    serde_json::from_str(&s).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(i, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+fn user(conn: &Connection, id: ObjectId) -> DatabaseResult<User>
+{
+    let user = conn
+        .query_row(
+            "SELECT name, password_hash, bot FROM users WHERE id = ?1",
+            [id.to_hex()],
+            |row| {
+                let bot: Option<String> = row.get(2)?;
+                Ok(User {
+                    _id:           id,
+                    name:          row.get(0)?,
+                    password_hash: row.get(1)?,
+                    create_games:  Vec::new(),
+                    active_games:  Vec::new(),
+                    bot:           bot.map(|_| json(row, 2)).transpose()?,
+                })
+            },
+        )
+        .optional()?;
+    let mut user = user.ok_or(DatabaseError::UserDontExist)?;
+
+    user.create_games = ids(conn, "SELECT id FROM invites WHERE creator = ?1 ORDER BY rowid", id)?;
+    user.active_games =
+        ids(conn, "SELECT id FROM games WHERE white = ?1 OR black = ?1 ORDER BY rowid", id)?;
+    Ok(user)
+}
+
+fn ids(conn: &Connection, sql: &str, id: ObjectId) -> rusqlite::Result<Vec<ObjectId>>
+{
+    let mut stmt = conn.prepare(sql)?;
+    let ids = stmt.query_map([id.to_hex()], |row| self::id(row, 0))?.collect();
+    ids
+}
+
+fn game(conn: &Connection, id: ObjectId) -> DatabaseResult<Game>
+{
+    conn.query_row("SELECT game FROM games WHERE id = ?1", [id.to_hex()], |row| json(row, 0))
+        .optional()?
+        .ok_or(DatabaseError::NoDocumentFound)
+}
+
+fn insert_game(conn: &Connection, game: &Game) -> DatabaseResult<ObjectId>
+{
+    let id = ObjectId::new();
+    conn.execute(
+        "INSERT INTO games (id, white, black, complete, indexed, version, game)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            id.to_hex(),
+            game.player_id(Color::White).to_hex(),
+            game.player_id(Color::Black).to_hex(),
+            game.complete,
+            game.indexed,
+            game.version,
+            to_json(game)
+        ],
+    )?;
+    Ok(id)
+}
+
+/*
+ * The columns are copies of the game's fields, so they are written together.
+ * Nothing is written unless the stored game is still at `version`, the one
+ * it was read at.
+ */
+fn update_game(conn: &Connection, id: ObjectId, game: &Game, version: u32) -> DatabaseResult<()>
+{
+    let updated = conn.execute(
+        "UPDATE games SET complete = ?2, indexed = ?3, version = ?4, game = ?5
+         WHERE id = ?1 AND version = ?6",
+        params![id.to_hex(), game.complete, game.indexed, game.version, to_json(game), version],
+    )?;
+
+    match updated
+    {
+        0 => Err(DatabaseError::Conflict),
+        _ => Ok(()),
+    }
+}
+
+// The games matching `filter`, a `WHERE` clause, as listed on the home page.
+// `order` is what follows `ORDER BY`
+fn list_games<P: Params>(
+    conn: &Connection,
+    filter: &str,
+    order: &str,
+    params: P,
+) -> DatabaseResult<Vec<OnGoingGame>>
+{
+    let sql = format!("SELECT id, game FROM games WHERE {filter} ORDER BY {order}");
+    let mut stmt = conn.prepare(&sql)?;
+    let games = stmt
+        .query_map(params, |row| {
+            let game: Game = json(row, 1)?;
+            Ok(game.ongoing(id(row, 0)?))
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(games)
+}
+
+// Every game in one transaction, see `Storage::index_games`
+fn index_games(conn: &mut Connection) -> DatabaseResult<usize>
+{
+    let tx = conn.transaction()?;
+
+    let games = tx
+        .prepare("SELECT id, game FROM games WHERE complete = 1 AND indexed = 0")?
+        .query_map([], |row| Ok((id(row, 0)?, json::<Game>(row, 1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    for (id, mut game) in games.iter().cloned()
+    {
+        let (winner, moves) = opening_moves(&game);
+        let result = match winner
+        {
+            Some(Color::White) => "white",
+            Some(Color::Black) => "black",
+            None => "draws",
+        };
+
+        let sql = format!(
+            "INSERT INTO positions (key, move, {result}) VALUES (?1, ?2, 1)
+             ON CONFLICT (key, move) DO UPDATE SET {result} = {result} + 1"
+        );
+        for (key, r#move) in moves
+        {
+            tx.execute(&sql, params![key, to_json(&r#move)])?;
+        }
+
+        game.indexed = true;
+        update_game(&tx, id, &game, game.version)?;
+    }
+
+    tx.commit()?;
+    Ok(games.len())
+}
+
+/*
+ * The conditions of a listing that games and challenges share, as a `WHERE`
+ * clause with `params` numbered after the ones already in it. `names` are
+ * the columns the player's name can be in.
+ */
+fn listing_filter(listing: &Listing, id: &str, names: &[&str], params: &mut Vec<String>) -> String
+{
+    let mut filter = Vec::new();
+    if let Some(name) = &listing.player
+    {
+        params.push(name.clone());
+        let n = params.len();
+        let names: Vec<_> = names.iter().map(|column| format!("{column} = ?{n}")).collect();
+        filter.push(format!("({})", names.join(" OR ")));
+    }
+    if let Some(after) = listing.after
+    {
+        params.push(after.to_hex());
+        filter.push(format!("{id} > ?{}", params.len()));
+    }
+    if let Some(before) = listing.before
+    {
+        params.push(before.to_hex());
+        filter.push(format!("{id} < ?{}", params.len()));
+    }
+
+    match filter.is_empty()
+    {
+        true => String::from("1"),
+        false => filter.join(" AND "),
+    }
+}
+
+// Ids are hex of the same length, so they sort as text like they do as bytes
+fn listing_order(listing: &Listing, id: &str) -> String
+{
+    let order = match listing.sort
+    {
+        SortOrder::Newest => "DESC",
+        SortOrder::Oldest => "ASC",
+    };
+    format!("{id} {order} LIMIT {}", listing.limit + 1)
+}
+
+fn puzzle(row: &Row) -> rusqlite::Result<PuzzleResource>
+{
+    Ok(PuzzleResource {
+        _id:    id(row, 0)?,
+        game:   id(row, 1)?,
+        moves:  json(row, 2)?,
+        length: row.get(3)?,
+    })
+}
+
+
+/*
+ * Everything in a single SQLite file, for running the server without
+ * MongoDB. The schema is brought up to date when the file is opened.
+ */
+pub struct Sqlite
+{
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Sqlite
+{
+    // `:memory:` opens a database that is gone when the server stops
+    pub fn open(path: &str) -> DatabaseResult<Self>
+    {
+        let mut conn = Connection::open(path)?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut conn)?;
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn))
+        })
+    }
+
+    // For queries on a row or two, nothing awaits while the lock is held
+    fn conn(&self) -> MutexGuard<'_, Connection>
+    {
+        self.conn.lock().unwrap()
+    }
+
+    // Queries over whole tables run on the blocking pool, so waiting for the
+    // lock or the query does not hold up other requests
+    async fn blocking<T, F>(&self, f: F) -> DatabaseResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> DatabaseResult<T> + Send + 'static,
+    {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || f(&mut conn.lock().unwrap()))
+            .await
+            .expect("sqlite query")
+    }
+}
+
+#[async_trait]
+impl Storage for Sqlite
+{
+    async fn login(&self, cred: UserCredentials) -> DatabaseResult<ObjectId>
+    {
+        let user = self
+            .conn()
+            .query_row("SELECT id, password_hash FROM users WHERE name = ?1", [&cred.name], |row| {
+                Ok((id(row, 0)?, row.get::<_, String>(1)?))
+            })
+            .optional()?;
+
+        // Hashing is slow, so it is done without holding the lock
+        let password_hash = user.as_ref().map(|(_, password_hash)| password_hash.clone());
+        let valid = verify_blocking(cred.password.clone(), password_hash).await;
+        let (id, password_hash) = match user
+        {
+            Some(user) if valid => user,
+            _ => return Err(DatabaseError::UserDontExist),
+        };
+
+        if is_legacy(&password_hash)
+        {
+            let password_hash = hash_blocking(cred.password).await;
+            self.set_password_hash(id, &password_hash).await?;
+        }
+        Ok(id)
+    }
+
+    async fn register_user(&self, cred: UserCredentials) -> DatabaseResult<ObjectId>
+    {
+        let user = User::new(cred.name, hash_blocking(cred.password).await);
+
+        let res = self.conn().execute(
+            "INSERT INTO users (id, name, password_hash) VALUES (?1, ?2, ?3)",
+            [user._id.to_hex(), user.name, user.password_hash],
+        );
+        match res
+        {
+            Ok(_) => Ok(user._id),
+            Err(rusqlite::Error::SqliteFailure(e, _))
+                if e.code == ErrorCode::ConstraintViolation =>
+            {
+                Err(DatabaseError::UserAlreadyExist)
+            },
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn set_password_hash(&self, user: ObjectId, password_hash: &str) -> DatabaseResult<()>
+    {
+        let updated =
+            self.conn().execute("UPDATE users SET password_hash = ?2 WHERE id = ?1", [
+                user.to_hex(),
+                password_hash.to_string(),
+            ])?;
+        match updated
+        {
+            0 => Err(DatabaseError::UserDontExist),
+            _ => Ok(()),
+        }
+    }
+
+    async fn register_bot(&self, name: &str, strength: Strength) -> DatabaseResult<ObjectId>
+    {
+        let conn = self.conn();
+        let existing = conn
+            .query_row("SELECT id, bot FROM users WHERE name = ?1", [name], |row| {
+                Ok((id(row, 0)?, row.get::<_, Option<String>>(1)?))
+            })
+            .optional()?;
+
+        match existing
+        {
+            Some((id, Some(_))) => Ok(id),
+            Some(_) => Err(DatabaseError::UserAlreadyExist),
+            None =>
+            {
+                let user = User::bot(name, strength);
+                conn.execute(
+                    "INSERT INTO users (id, name, password_hash, bot) VALUES (?1, ?2, ?3, ?4)",
+                    [user._id.to_hex(), user.name, user.password_hash, to_json(&strength)],
+                )?;
+                Ok(user._id)
+            },
+        }
+    }
+
+    async fn get_bots(&self) -> DatabaseResult<Vec<User>>
+    {
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT id FROM users WHERE bot IS NOT NULL ORDER BY rowid")?;
+        let ids = stmt.query_map([], |row| id(row, 0))?.collect::<rusqlite::Result<Vec<_>>>()?;
+
+        ids.into_iter().map(|id| user(&conn, id)).collect()
+    }
+
+    async fn find_user_by_id(&self, id: ObjectId) -> DatabaseResult<User>
+    {
+        user(&self.conn(), id)
+    }
+
+    async fn find_user_by_name(&self, name: &str) -> DatabaseResult<User>
+    {
+        let conn = self.conn();
+        let id = conn
+            .query_row("SELECT id FROM users WHERE name = ?1", [name], |row| id(row, 0))
+            .optional()?
+            .ok_or(DatabaseError::UserDontExist)?;
+        user(&conn, id)
+    }
+
+    async fn create_session(&self, user: ObjectId) -> DatabaseResult<Session>
+    {
+        let token = new_session(user);
+        self.conn().execute(
+            "INSERT INTO sessions (token, user, expires) VALUES (?1, ?2, ?3)",
+            params![token._id, user.to_hex(), token.expires.timestamp_millis()],
+        )?;
+        Ok(token.into())
+    }
+
+    async fn find_session(&self, token: &str) -> DatabaseResult<ObjectId>
+    {
+        let conn = self.conn();
+        let session = conn
+            .query_row("SELECT user, expires FROM sessions WHERE token = ?1", [token], |row| {
+                Ok(Token {
+                    _id:     token.to_string(),
+                    user:    id(row, 0)?,
+                    expires: DateTime::from_millis(row.get(1)?),
+                })
+            })
+            .optional()?;
+
+        match session
+        {
+            Some(session) if session.expires > DateTime::now() => Ok(session.user),
+            Some(_) =>
+            {
+                conn.execute("DELETE FROM sessions WHERE token = ?1", [token])?;
+                Err(DatabaseError::InvalidSession)
+            },
+            None => Err(DatabaseError::InvalidSession),
+        }
+    }
+
+    async fn delete_session(&self, token: &str) -> DatabaseResult<()>
+    {
+        self.conn().execute("DELETE FROM sessions WHERE token = ?1", [token])?;
+        Ok(())
+    }
+
+    async fn create_game(&self, user_id: ObjectId, limit: u32) -> DatabaseResult<ObjectId>
+    {
+        let conn = self.conn();
+        let exists = conn
+            .query_row("SELECT 1 FROM users WHERE id = ?1", [user_id.to_hex()], |_| Ok(()))
+            .optional()?;
+        if exists.is_none()
+        {
+            return Err(DatabaseError::NoDocumentFound);
+        }
+
+        let count: u32 = conn.query_row(
+            "SELECT COUNT(*) FROM invites WHERE creator = ?1",
+            [user_id.to_hex()],
+            |row| row.get(0),
+        )?;
+        if count > limit
+        {
+            return Err(DatabaseError::TooManyGames);
+        }
+
+        let id = ObjectId::new();
+        conn.execute("INSERT INTO invites (id, creator) VALUES (?1, ?2)", [
+            id.to_hex(),
+            user_id.to_hex(),
+        ])?;
+        Ok(id)
+    }
+
+    async fn accept_game(
+        &self,
+        user: ObjectId,
+        form: CreateGameFormResponse,
+    ) -> DatabaseResult<AcceptGame>
+    {
+        let CreateGameFormResponse {
+            game,
+            creator,
+        } = form;
+
+        // Returning early rolls the transaction back
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let taken = tx.execute("DELETE FROM invites WHERE id = ?1 AND creator = ?2", [
+            game.to_hex(),
+            creator.to_hex(),
+        ])?;
+        if taken == 0
+        {
+            return Err(DatabaseError::Conflict);
+        }
+
+        let players = colors(creator, user);
+        let users = [self::user(&tx, players[0])?, self::user(&tx, players[1])?];
+        let id = insert_game(&tx, &Game::new(users))?;
+        tx.commit()?;
+
+        Ok(AcceptGame {
+            object_id: id,
+            game,
+        })
+    }
+
+    async fn find_challenge_creator(&self, id: ObjectId) -> DatabaseResult<ObjectId>
+    {
+        self.conn()
+            .query_row("SELECT creator FROM invites WHERE id = ?1", [id.to_hex()], |row| {
+                self::id(row, 0)
+            })
+            .optional()?
+            .ok_or(DatabaseError::NoDocumentFound)
+    }
+
+    async fn remove_challenge(&self, creator: ObjectId, id: ObjectId) -> DatabaseResult<()>
+    {
+        let removed =
+            self.conn().execute("DELETE FROM invites WHERE id = ?1 AND creator = ?2", [
+                id.to_hex(),
+                creator.to_hex(),
+            ])?;
+        match removed
+        {
+            0 => Err(DatabaseError::Conflict),
+            _ => Ok(()),
+        }
+    }
+
+    async fn home(&self, id: ObjectId) -> DatabaseResult<Vec<CreateGameChallengeBundle>>
+    {
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT id, name FROM users WHERE id != ?1 ORDER BY rowid")?;
+        let users = stmt
+            .query_map([id.to_hex()], |row| Ok((self::id(row, 0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<(ObjectId, String)>>>()?;
+
+        let mut bundles = Vec::new();
+        for (creator_id, name) in users
+        {
+            bundles.push(CreateGameChallengeBundle {
+                name,
+                games: ids(
+                    &conn,
+                    "SELECT id FROM invites WHERE creator = ?1 ORDER BY rowid",
+                    creator_id,
+                )?,
+                creator_id,
+            });
+        }
+        Ok(bundles)
+    }
+
+    async fn find_challenges(
+        &self,
+        id: ObjectId,
+        listing: &Listing,
+    ) -> DatabaseResult<Page<CreateGameChallenge>>
+    {
+        let mut params = vec![id.to_hex()];
+        let filter = listing_filter(listing, "invites.id", &["users.name"], &mut params);
+        let sql = format!(
+            "SELECT invites.id, users.id, users.name FROM invites
+             JOIN users ON users.id = invites.creator
+             WHERE users.id != ?1 AND {filter} ORDER BY {}",
+            listing_order(listing, "invites.id")
+        );
+
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&sql)?;
+        let challenges = stmt
+            .query_map(params_from_iter(params), |row| {
+                Ok(CreateGameChallenge {
+                    _id:     self::id(row, 0)?,
+                    creator: self::id(row, 1)?,
+                    name:    row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(page(challenges, listing.limit, |challenge| challenge._id))
+    }
+
+    async fn get_old_games(&self) -> DatabaseResult<Vec<OnGoingGame>>
+    {
+        self.blocking(|conn| list_games(conn, "complete = 1", "rowid", [])).await
+    }
+
+    async fn get_users_games(&self, id: ObjectId) -> DatabaseResult<Vec<OnGoingGame>>
+    {
+        let filter = "(white = ?1 OR black = ?1) AND complete = 0";
+        self.blocking(move |conn| list_games(conn, filter, "rowid", [id.to_hex()]))
+            .await
+    }
+
+    async fn get_active_games(&self) -> DatabaseResult<Vec<OnGoingGame>>
+    {
+        self.blocking(|conn| list_games(conn, "complete = 0", "rowid", [])).await
+    }
+
+    async fn get_game_by_id(&self, id: ObjectId) -> DatabaseResult<GameResource>
+    {
+        game(&self.conn(), id).map(|game| game.resource(id))
+    }
+
+    async fn find_games(&self, listing: &Listing) -> DatabaseResult<Page<OnGoingGame>>
+    {
+        // The names and the result are only in the game's json
+        let mut params = Vec::new();
+        let names =
+            ["json_extract(game, '$.players[0].name')", "json_extract(game, '$.players[1].name')"];
+        let mut filter = listing_filter(listing, "id", &names, &mut params);
+        if let Some(result) = listing.result
+        {
+            params.push(to_json(&result));
+            let n = params.len();
+            filter += &format!(" AND json_extract(game, '$.result') = json_extract(?{n}, '$')");
+        }
+        filter += &format!(" AND complete = {}", listing.complete as i32);
+
+        let order = listing_order(listing, "id");
+        let games = self
+            .blocking(move |conn| list_games(conn, &filter, &order, params_from_iter(params)))
+            .await?;
+        Ok(page(games, listing.limit, |game| game.game_object_id))
+    }
+
+    async fn play_move(&self, player_id: ObjectId, r#move: Move) -> DatabaseResult<()>
+    {
+        let conn = self.conn();
+        let id = r#move.game_id;
+        let mut game = game(&conn, id)?;
+        if !game.has_player(player_id) || game.complete
+        {
+            return Err(DatabaseError::NoDocumentFound);
+        }
+
+        let version = game.version;
+        play(&mut game, player_id, r#move)?;
+        update_game(&conn, id, &game, version)
+    }
+
+    async fn import_game(&self, game: Game) -> DatabaseResult<ObjectId>
+    {
+        insert_game(&self.conn(), &game)
+    }
+
+    async fn complete_game(&self, game_id: ObjectId) -> DatabaseResult<()>
+    {
+        let conn = self.conn();
+        if let Ok(mut game) = game(&conn, game_id)
+        {
+            game.complete = true;
+            update_game(&conn, game_id, &game, game.version)?;
+        }
+        Ok(())
+    }
+
+    async fn resign_game(&self, game_id: ObjectId, player_id: ObjectId) -> DatabaseResult<()>
+    {
+        let conn = self.conn();
+        let mut game = game(&conn, game_id)?;
+        if !game.has_player(player_id) || game.complete
+        {
+            return Err(DatabaseError::NoDocumentFound);
+        }
+
+        let version = game.version;
+        resign(&mut game, player_id);
+        update_game(&conn, game_id, &game, version)
+    }
+
+    async fn set_review(&self, game_id: ObjectId, review: Review) -> DatabaseResult<()>
+    {
+        let conn = self.conn();
+        if let Ok(mut game) = game(&conn, game_id)
+        {
+            game.review = Some(review);
+            update_game(&conn, game_id, &game, game.version)?;
+        }
+        Ok(())
+    }
+
+    async fn index_games(&self) -> DatabaseResult<usize>
+    {
+        self.blocking(index_games).await
+    }
+
+    async fn explore(&self, board: &Board) -> DatabaseResult<Vec<ExplorerMove>>
+    {
+        let (key, sym) = canonical(board);
+
+        let conn = self.conn();
+        let positions = conn
+            .prepare("SELECT move, white, black, draws FROM positions WHERE key = ?1")?
+            .query_map([key as i64], |row| {
+                Ok(PositionMove {
+                    key:    key as i64,
+                    r#move: json(row, 0)?,
+                    white:  row.get(1)?,
+                    black:  row.get(2)?,
+                    draws:  row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(explorer_moves(&sym, positions))
+    }
+
+    async fn add_puzzle(
+        &self,
+        game: ObjectId,
+        moves: &[Option<InternalMove>],
+        length: u32,
+    ) -> DatabaseResult<()>
+    {
+        self.conn().execute(
+            "INSERT OR IGNORE INTO puzzles (id, game, moves, length) VALUES (?1, ?2, ?3, ?4)",
+            params![ObjectId::new().to_hex(), game.to_hex(), to_json(&moves), length],
+        )?;
+        Ok(())
+    }
+
+    async fn get_puzzle(&self, id: ObjectId) -> DatabaseResult<PuzzleResource>
+    {
+        self.conn()
+            .query_row(
+                "SELECT id, game, moves, length FROM puzzles WHERE id = ?1",
+                [id.to_hex()],
+                puzzle,
+            )
+            .optional()?
+            .ok_or(DatabaseError::NoDocumentFound)
+    }
+
+    async fn random_puzzle(&self) -> DatabaseResult<PuzzleResource>
+    {
+        self.conn()
+            .query_row(
+                "SELECT id, game, moves, length FROM puzzles ORDER BY RANDOM() LIMIT 1",
+                [],
+                puzzle,
+            )
+            .optional()?
+            .ok_or(DatabaseError::NoDocumentFound)
+    }
+}
+
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    fn test_migrations_run_once() -> DatabaseResult<()>
+    {
+        let mut conn = Connection::open_in_memory()?;
+        migrate(&mut conn)?;
+        conn.execute("INSERT INTO users (id, name, password_hash) VALUES ('a', 'sivert', '')", [])?;
+
+        // Running them again leaves the data alone
+        migrate(&mut conn)?;
+        let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        let users: u32 = conn.query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))?;
+        assert_eq!(version, MIGRATIONS.len());
+        assert_eq!(users, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_older_database_is_migrated() -> DatabaseResult<()>
+    {
+        let mut conn = Connection::open_in_memory()?;
+        conn.execute_batch(MIGRATIONS[0])?;
+        conn.pragma_update(None, "user_version", 1)?;
+
+        migrate(&mut conn)?;
+        let indexes: u32 = conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'index' AND name = 'games_white'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(indexes, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_old_games_get_results() -> DatabaseResult<()>
+    {
+        let mut conn = Connection::open_in_memory()?;
+        for migration in &MIGRATIONS[..3]
+        {
+            conn.execute_batch(migration)?;
+        }
+        conn.pragma_update(None, "user_version", 3)?;
+
+        // The white queen is surrounded
+        let mut board = Board::default();
+        let pieces = [
+            ((0, 0, 0), BoardPiece::Queen, Color::White),
+            ((1, -1, 0), BoardPiece::Spider, Color::White),
+            ((0, -1, 1), BoardPiece::Ant, Color::White),
+            ((-1, 0, 1), BoardPiece::Beetle, Color::White),
+            ((-1, 1, 0), BoardPiece::Queen, Color::Black),
+            ((1, 0, -1), BoardPiece::Grasshopper, Color::Black),
+            ((0, 1, -1), BoardPiece::Ant, Color::Black),
+        ];
+        for &(sq, r#type, color) in pieces.iter()
+        {
+            board.place_piece(Piece::new(r#type, color), sq, None).unwrap();
+        }
+
+        // Stored the way games were before they had a result
+        let players = || [String::from("sivert"), String::from("sofie")];
+        let games = [
+            Game::imported(players(), board, Vec::new()),
+            Game::imported(players(), Board::default(), Vec::new()),
+        ];
+        for (i, game) in games.iter().enumerate()
+        {
+            let mut json = serde_json::to_value(game).unwrap();
+            json.as_object_mut().unwrap().remove("result");
+            conn.execute(
+                "INSERT INTO games (id, white, black, complete, indexed, game) VALUES (?1, '', \
+                 '', 1, 0, ?2)",
+                params![i, json.to_string()],
+            )?;
+        }
+
+        migrate(&mut conn)?;
+        let results = conn
+            .prepare("SELECT game FROM games ORDER BY id")?
+            .query_map([], |row| json::<Game>(row, 0))?
+            .map(|game| game.map(|game| game.result))
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        assert_eq!(results, vec![Some(GameResult::Black), None]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_expired_session() -> DatabaseResult<()>
+    {
+        let db = Sqlite::open(":memory:")?;
+        let user = db.register_user(UserCredentials::default()).await?;
+        let session = db.create_session(user).await?;
+
+        db.conn().execute("UPDATE sessions SET expires = 0", [])?;
+        let res = db.find_session(&session.token).await;
+        assert!(matches!(res, Err(DatabaseError::InvalidSession)));
+
+        let left: u32 =
+            db.conn().query_row("SELECT COUNT(*) FROM sessions", [], |row| row.get(0))?;
+        assert_eq!(left, 0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,16 @@
+use crate::State;
+
+
+/*
+ * Brings the opening index up to date with the completed games. Run at
+ * startup to pick up games from before the index, and whenever a game ends.
+ */
+pub fn spawn_index(state: State)
+{
+    tokio::spawn(async move {
+        if let Err(e) = state.db().index_games().await
+        {
+            println!("could not update the opening index: {e:?}");
+        }
+    });
+}
@@ -1,10 +1,16 @@
+mod bot;
+mod config;
+mod explorer;
 mod model;
+mod puzzle;
+mod review;
 mod routing;
 mod websocket;
 use routing::handle;
 mod database;
 use std::convert::Infallible;
 
+use config::Config;
 use hyper::{
     service::{make_service_fn, service_fn},
     Server,
@@ -14,21 +20,27 @@ use websocket::*;
 
 type SError = Box<dyn std::error::Error + Send + Sync>;
 
-use tokio::sync::mpsc;
+use std::sync::Arc;
+
+use tokio::sync::{broadcast, mpsc, Semaphore};
 use websocket::Message;
 
 #[derive(Clone)]
 pub struct State
 {
-    pub client: mongodb::Client,
-    pub tx:     mpsc::Sender<Message>,
+    pub storage:  Arc<dyn database::Storage>,
+    pub tx:       broadcast::Sender<Message>,
+    pub bots:     mpsc::Sender<bot::Challenge>,
+    // Limits the number of analysis searches running at once
+    pub analysis: Arc<Semaphore>,
+    pub config:   Arc<Config>,
 }
 
 impl State
 {
-    pub fn db(&self) -> mongodb::Database
+    pub fn db(&self) -> &dyn database::Storage
     {
-        self.client.database(database::LIVE)
+        &*self.storage
     }
 }
 
@@ -45,7 +57,7 @@ async fn spawn_http_server(state: State) -> Result<(), SError>
         }
     });
 
-    let addr = ([0, 0, 0, 0], 5000).into();
+    let addr = ([0, 0, 0, 0], state.config.http_port).into();
     let server = Server::bind(&addr).serve(make_svc);
     println!("Listening on http://{}", addr);
     server.await?;
@@ -56,15 +68,39 @@ async fn spawn_http_server(state: State) -> Result<(), SError>
 #[tokio::main(flavor = "multi_thread")]
 pub async fn main() -> Result<(), SError>
 {
-    let client = database::connect().await?;
-    let (tx, rx) = tokio::sync::mpsc::channel(10); // 10 good??
+    let config = match Config::load(std::env::args().skip(1))
+    {
+        Ok(config) => config,
+        Err(e) =>
+        {
+            eprintln!("{e}\n\n{}", config::USAGE);
+            std::process::exit(1);
+        },
+    };
+
+    let storage = match database::open(&config.database).await
+    {
+        Ok(storage) => storage,
+        Err(e) =>
+        {
+            eprintln!("could not open the database: {e:?}");
+            std::process::exit(1);
+        },
+    };
+    let (tx, rx) = broadcast::channel(10); // 10 good??
+    let (bots, bot_rx) = mpsc::channel(10);
 
     let state = State {
-        client,
+        storage,
         tx,
+        bots,
+        analysis: Arc::new(Semaphore::new(config.analysis_workers)),
+        config: Arc::new(config),
     };
 
-    tokio::spawn(spawn_web_socket_server(rx));
+    explorer::spawn_index(state.clone());
+    tokio::spawn(spawn_web_socket_server(rx, state.config.websocket_port));
+    tokio::spawn(bot::spawn_bots(state.clone(), bot_rx));
     let _ = spawn_http_server(state).await;
 
     Ok(())
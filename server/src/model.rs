@@ -1,2 +1,4 @@
 pub mod game;
+pub mod position;
+pub mod session;
 pub mod user;
@@ -1,16 +1,43 @@
 use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
-use shared::model::{Board, InternalMove};
+use shared::{
+    engine::outcome,
+    model::{
+        Board, Color, GameResource, GameResult, InternalMove, OnGoingGame, RecordInfo, Review,
+    },
+};
 
 use super::user::User;
 
-#[derive(Serialize, Deserialize)]
+fn board_result(board: &Board) -> Option<GameResult>
+{
+    outcome(board).map(GameResult::from)
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Game
 {
     players:       [Player; 2],
-    complete:      bool,
+    pub complete:  bool,
     pub board:     Board,
-    pub move_list: Vec<InternalMove>,
+    // None is a pass
+    pub move_list: Vec<Option<InternalMove>>,
+    #[serde(default)]
+    pub review:    Option<Review>,
+    // Counted in the opening index
+    #[serde(default)]
+    pub indexed:   bool,
+    // Bumped by every move, a move is only stored if nobody else has moved
+    // since the game was read
+    #[serde(default)]
+    pub version:   u32,
+    // How the game ended, set by the move that ended it. Games that ended
+    // before this was stored get it from the board when the database opens
+    #[serde(default)]
+    pub result:    Option<GameResult>,
+    // What the record of an imported game had besides the moves
+    #[serde(default)]
+    pub record:    Option<RecordInfo>,
 }
 
 impl Game
@@ -34,11 +61,93 @@ impl Game
             complete:  false,
             board:     Board::default(),
             move_list: Vec::new(),
+            review:    None,
+            indexed:   false,
+            version:   0,
+            result:    None,
+            record:    None,
+        }
+    }
+
+    /*
+     * A finished game played somewhere else. The players are not users here,
+     * so nobody can play on in it. The result is the one the board shows,
+     * a record can say otherwise.
+     */
+    pub fn imported(names: [String; 2], board: Board, move_list: Vec<Option<InternalMove>>)
+        -> Self
+    {
+        let [u1, u2] = names;
+        Self {
+            players: [
+                Player {
+                    _id: ObjectId::new(), name: u1
+                },
+                Player {
+                    _id: ObjectId::new(), name: u2
+                },
+            ],
+            complete: true,
+            result: board_result(&board),
+            board,
+            move_list,
+            review: None,
+            indexed: false,
+            version: 0,
+            record: None,
+        }
+    }
+
+    // What the board shows for games that ended before results were stored
+    pub fn board_result(&self) -> Option<GameResult>
+    {
+        board_result(&self.board)
+    }
+
+    pub fn player_id(&self, color: Color) -> ObjectId
+    {
+        self.players[color as usize]._id
+    }
+
+    pub fn has_player(&self, id: ObjectId) -> bool
+    {
+        self.players.iter().any(|player| player._id == id)
+    }
+
+    pub fn has_player_named(&self, name: &str) -> bool
+    {
+        self.players.iter().any(|player| player.name == name)
+    }
+
+    fn names(&self) -> [String; 2]
+    {
+        [self.players[0].name.clone(), self.players[1].name.clone()]
+    }
+
+    // The game as it is sent to the frontend, the ids of the players are left
+    // out
+    pub fn resource(&self, id: ObjectId) -> GameResource
+    {
+        GameResource {
+            _id:       id,
+            players:   self.names(),
+            board:     self.board.clone(),
+            move_list: self.move_list.clone(),
+            review:    self.review.clone(),
+            result:    self.result,
+            record:    self.record.clone(),
+        }
+    }
+
+    pub fn ongoing(&self, id: ObjectId) -> OnGoingGame
+    {
+        OnGoingGame {
+            game_object_id: id, players: self.names()
         }
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Player
 {
     _id:  ObjectId,
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+use shared::model::{ExplorerMove, InternalMove};
+
+// A move from a position in the opening index. The key and the move are both
+// in the canonical frame of the position, see shared::engine::canonical
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PositionMove
+{
+    pub key:    i64,
+    pub r#move: InternalMove,
+    #[serde(default)]
+    pub white:  u32,
+    #[serde(default)]
+    pub black:  u32,
+    #[serde(default)]
+    pub draws:  u32,
+}
+
+impl From<PositionMove> for ExplorerMove
+{
+    fn from(pos: PositionMove) -> Self
+    {
+        Self {
+            r#move: pos.r#move, white: pos.white, black: pos.black, draws: pos.draws
+        }
+    }
+}
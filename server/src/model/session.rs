@@ -0,0 +1,23 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+use shared::model::Session;
+
+// A logged in session. The token is the id, so looking one up is a single
+// find, and Mongo removes it some time after it expires.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Token
+{
+    pub _id:     String,
+    pub user:    ObjectId,
+    pub expires: DateTime,
+}
+
+impl From<Token> for Session
+{
+    fn from(token: Token) -> Self
+    {
+        Self {
+            token: token._id, user: token.user
+        }
+    }
+}
@@ -1,10 +1,10 @@
 use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
-use shared::model::UserCredentials;
+use shared::model::Strength;
 
 use crate::database::hash;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct User
 {
     pub _id:           ObjectId,
@@ -12,23 +12,31 @@ pub struct User
     pub password_hash: String,
     pub create_games:  Vec<ObjectId>,
     pub active_games:  Vec<ObjectId>,
+    #[serde(default)]
+    pub bot:           Option<Strength>,
 }
 
 impl User
 {
-    pub fn from_cred(cred: UserCredentials) -> Self
+    // `password_hash` is made with `hash`
+    pub fn new(name: String, password_hash: String) -> Self
     {
-        let UserCredentials {
-            name,
-            password,
-        } = cred;
-
         Self {
             _id: ObjectId::new(),
             name,
-            password_hash: hash(&password),
+            password_hash,
             create_games: Vec::new(),
             active_games: Vec::new(),
+            bot: None,
+        }
+    }
+
+    // Nobody knows the password of a bot, so it can not be logged into
+    pub fn bot(name: &str, strength: Strength) -> Self
+    {
+        Self {
+            bot: Some(strength),
+            ..Self::new(name.to_string(), hash(&uuid::Uuid::new_v4().to_string()))
         }
     }
 }
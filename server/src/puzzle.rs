@@ -0,0 +1,55 @@
+use mongodb::bson::oid::ObjectId;
+use shared::engine::find_puzzle;
+use tokio::task;
+
+use crate::State;
+
+
+// The longest forced wins looked for, in moves by the winning side
+pub const PUZZLE_MOVES: u32 = 2;
+
+
+/*
+ * Look through a finished game for a position with a forced win and store
+ * it as a puzzle. Waits for a worker like the review does.
+ */
+pub fn spawn_mining(state: State, game_id: ObjectId)
+{
+    tokio::spawn(async move {
+        let permit = match state.analysis.clone().acquire_owned().await
+        {
+            Ok(permit) => permit,
+            Err(_) => return,
+        };
+
+        let moves = match state.db().get_game_by_id(game_id).await
+        {
+            Ok(game) => game.move_list,
+            Err(e) =>
+            {
+                println!("could not look for puzzles in game {game_id}: {e:?}");
+                return;
+            },
+        };
+
+        let res = task::spawn_blocking(move || {
+            let _permit = permit;
+            find_puzzle(&moves, PUZZLE_MOVES).map(|(i, length)| (moves[..i].to_vec(), length))
+        })
+        .await;
+
+        match res
+        {
+            Ok(Some((moves, length))) =>
+            {
+                if let Err(e) = state.db().add_puzzle(game_id, &moves, length).await
+                {
+                    println!("could not store the puzzle from game {game_id}: {e:?}");
+                }
+            },
+            Ok(None) =>
+            {},
+            Err(e) => println!("could not look for puzzles in game {game_id}: {e}"),
+        }
+    });
+}
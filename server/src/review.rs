@@ -0,0 +1,54 @@
+use mongodb::bson::oid::ObjectId;
+use shared::engine::review;
+use tokio::task;
+
+use crate::State;
+
+
+pub const REVIEW_DEPTH: u32 = 2;
+
+
+/*
+ * Review a finished game in the background and store it with the game.
+ * Shares the workers with the analysis endpoint, but waits for a free one
+ * instead of giving up.
+ */
+pub fn spawn_review(state: State, game_id: ObjectId)
+{
+    tokio::spawn(async move {
+        let permit = match state.analysis.clone().acquire_owned().await
+        {
+            Ok(permit) => permit,
+            Err(_) => return,
+        };
+
+        let game = match state.db().get_game_by_id(game_id).await
+        {
+            Ok(game) if game.review.is_none() => game,
+            Ok(_) => return,
+            Err(e) =>
+            {
+                println!("could not review game {game_id}: {e:?}");
+                return;
+            },
+        };
+
+        let review = task::spawn_blocking(move || {
+            let _permit = permit;
+            review(&game.move_list, REVIEW_DEPTH)
+        })
+        .await;
+
+        match review
+        {
+            Ok(review) =>
+            {
+                if let Err(e) = state.db().set_review(game_id, review).await
+                {
+                    println!("could not store the review of game {game_id}: {e:?}");
+                }
+            },
+            Err(e) => println!("could not review game {game_id}: {e}"),
+        }
+    });
+}
@@ -1,44 +1,156 @@
 use std::convert::Infallible;
 
-use hyper::{body, Body, Method, Request, Response};
-use shared::model::http::*;
+use hyper::{body, Body, Method, Request, Response, StatusCode};
+use mongodb::bson::oid::ObjectId;
+use shared::model::{http::*, ListQuery};
 
-use crate::database::DatabaseError;
+use crate::database::{DatabaseError, Listing};
 
 
+mod analyze;
+mod bot;
 mod create_game;
+mod explore;
 mod game;
 mod home;
+mod import;
 mod login;
+mod puzzle;
 mod register;
-use create_game::create_game;
-use game::game;
+mod router;
+use analyze::analyze;
+use bot::bot;
+use create_game::{challenge, challenges, create_game};
+use explore::explore;
+pub use game::submit_move;
+use game::{export, game, games, legacy_game, moves, user_games};
 use home::home;
-use login::login;
+use import::import;
+use login::{login, logout};
+use puzzle::puzzle;
 use register::register;
-use serde::Serialize;
+use router::Params;
 
 use crate::State;
 
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Route
+{
+    Register,
+    Login,
+    Logout,
+    Games,
+    Game,
+    Moves,
+    Export,
+    UserGames,
+    Challenges,
+    Challenge,
+    Bot,
+    Analyze,
+    Explore,
+    Puzzle,
+    Import,
+
+    // The old routes, answered with a `Deprecation` header
+    LegacyGame,
+    LegacyCreateGame,
+    LegacyHome,
+    LegacyExport,
+}
+
+/*
+ * Paths after `/api/`, the first match is used. `{name}` matches one
+ * segment, see `router::matches`.
+ */
+const ROUTES: &[(&str, Route)] = &[
+    ("register", Route::Register),
+    ("login", Route::Login),
+    ("logout", Route::Logout),
+    ("games", Route::Games),
+    ("games/{id}", Route::Game),
+    ("games/{id}/moves", Route::Moves),
+    ("games/{id}/export", Route::Export),
+    ("users/{name}/games", Route::UserGames),
+    ("challenges", Route::Challenges),
+    ("challenges/{id}", Route::Challenge),
+    ("bot", Route::Bot),
+    ("analyze", Route::Analyze),
+    ("explore", Route::Explore),
+    ("puzzle", Route::Puzzle),
+    ("import", Route::Import),
+    ("game", Route::LegacyGame),
+    ("create-game", Route::LegacyCreateGame),
+    ("home", Route::LegacyHome),
+    ("game/{id}/export", Route::LegacyExport),
+];
+
+impl Route
+{
+    fn deprecated(self) -> bool
+    {
+        matches!(
+            self,
+            Route::LegacyGame | Route::LegacyCreateGame | Route::LegacyHome | Route::LegacyExport
+        )
+    }
+}
+
 #[derive(Debug)]
 pub enum HttpError
 {
     Serialize,
+    BadRequest(String),
     MethodNotAllowed,
     NotFound,
     Database(DatabaseError),
-    Channel(Box<dyn std::error::Error>),
+    Unauthorized,
+    // Wrong name or password
+    InvalidLogin,
+    Channel(Box<dyn std::error::Error + Send + Sync>),
+    Busy,
 }
 
-use serde::ser::Serializer;
-impl Serialize for HttpError
+/*
+ * What the client is told. Errors from the database itself and the channels
+ * are only logged, the client gets to know that something went wrong.
+ */
+impl From<&HttpError> for ApiError
 {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
+    fn from(e: &HttpError) -> Self
     {
-        let s = format!("{:?}", self);
-        serializer.collect_str(&s)
+        use ErrorCode::*;
+        match e
+        {
+            HttpError::Serialize => ApiError::new(InvalidBody),
+            HttpError::BadRequest(s) => ApiError::with_details(BadRequest, s.as_str()),
+            HttpError::MethodNotAllowed => ApiError::new(MethodNotAllowed),
+            HttpError::NotFound => ApiError::new(NotFound),
+            HttpError::Unauthorized => ApiError::new(Unauthorized),
+            HttpError::InvalidLogin => ApiError::new(InvalidLogin),
+            HttpError::Channel(_) => ApiError::new(Internal),
+            HttpError::Busy => ApiError::new(Busy),
+            HttpError::Database(e) => match e
+            {
+                DatabaseError::UserAlreadyExist => ApiError::new(UserAlreadyExists),
+                DatabaseError::UserDontExist => ApiError::with_details(NotFound, "no such user"),
+                DatabaseError::NoDocumentFound => ApiError::new(NotFound),
+                DatabaseError::TooManyGames => ApiError::new(TooManyGames),
+                DatabaseError::GameNotComplete => ApiError::new(GameNotComplete),
+                DatabaseError::IllegalMove => ApiError::new(IllegalMove),
+                DatabaseError::NotYourTurn => ApiError::new(NotYourTurn),
+                DatabaseError::Conflict => ApiError::new(Conflict),
+                DatabaseError::InvalidSession =>
+                {
+                    ApiError::with_details(Unauthorized, "the session has run out")
+                },
+                DatabaseError::DbError(_) | DatabaseError::SqliteError(_) =>
+                {
+                    ApiError::new(Internal)
+                },
+            },
+        }
     }
 }
 
@@ -47,6 +159,8 @@ pub enum HttpResult
 {
     Ok(Box<dyn erased_serde::Serialize>),
     Create(Box<dyn erased_serde::Serialize>),
+    // A file name and its contents, sent as they are instead of as json
+    Download(String, String),
     Err(HttpError),
 }
 
@@ -72,7 +186,8 @@ impl<T> CorsExt for Response<T>
     fn add_cors_headers(mut self) -> Self
     {
         let headers = self.headers_mut();
-        headers.insert("Access-Control-Allow-Headers", "Content-Type".parse().unwrap());
+        headers
+            .insert("Access-Control-Allow-Headers", "Content-Type, Authorization".parse().unwrap());
         headers.insert("Access-Control-Allow-Origin", "*".parse().unwrap());
         headers.insert("Access-Control-Allow-Methods", "GET, POST, PUT, DELETE".parse().unwrap());
         self
@@ -86,6 +201,36 @@ pub async fn get_body<T: serde::de::DeserializeOwned>(req: Request<Body>) -> Opt
     serde_json::from_str::<T>(s).ok()
 }
 
+/*
+ * The listing the query string asks for, see `ListQuery`. None if it can
+ * match nothing.
+ */
+pub fn listing(req: &Request<Body>, complete: bool) -> Result<Option<Listing>, HttpError>
+{
+    let query = router::query::<ListQuery>(req)?;
+    Listing::from_query(&query, complete).map_err(HttpError::BadRequest)
+}
+
+/*
+ * The user making the request, from the session token in the
+ * `Authorization: Bearer <token>` header.
+ */
+pub async fn caller(req: &Request<Body>, state: &State) -> Result<ObjectId, HttpError>
+{
+    let token = token(req).ok_or(HttpError::Unauthorized)?;
+    state.db().find_session(token).await.map_err(HttpError::Database)
+}
+
+// The session token in the `Authorization: Bearer <token>` header
+fn token(req: &Request<Body>) -> Option<&str>
+{
+    req.headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
 fn log_req(req: &Request<Body>) -> String
 {
     use chrono::prelude::*;
@@ -102,20 +247,12 @@ fn log_resp(resp: &HttpResult, time: std::time::Duration) -> String
     {
         HttpResult::Ok(_) => format!("200 OK\t{}", time.as_secs_f32()),
         HttpResult::Create(_) => format!("201 Create\t{}", time.as_secs_f32()),
-        HttpResult::Err(e) => format!("{} {:?}\t{}", error_code(e), e, time.as_secs_f32()),
-    }
-}
-
-#[inline]
-fn error_code(e: &HttpError) -> u32
-{
-    use HttpError::*;
-    match e
-    {
-        Serialize => 400,
-        NotFound => 404,
-        MethodNotAllowed => 405,
-        Database(_) | Channel(_) => 500,
+        HttpResult::Download(..) => format!("200 Download\t{}", time.as_secs_f32()),
+        HttpResult::Err(e) =>
+        {
+            let code = ApiError::from(e).code.status();
+            format!("{} {:?}\t{}", code, e, time.as_secs_f32())
+        },
     }
 }
 
@@ -123,51 +260,100 @@ impl From<HttpResult> for Response<Body>
 {
     fn from(http_res: HttpResult) -> Self
     {
-        let f = |code: u32, t: &dyn erased_serde::Serialize| -> Response<Body> {
-            Response::new(Body::from(ResponseBody::to_body(
-                code,
-                serde_json::to_string(&t).unwrap(),
-            )))
+        let f = |code: u16, t: &dyn erased_serde::Serialize| -> Response<Body> {
+            let mut resp = Response::new(Body::from(serde_json::to_string(&t).unwrap()));
+            *resp.status_mut() = StatusCode::from_u16(code).unwrap();
+            resp.headers_mut().insert("Content-Type", "application/json".parse().unwrap());
+            resp
         };
 
         match http_res
         {
             HttpResult::Ok(t) => f(200, &t),
             HttpResult::Create(t) => f(201, &t),
-            HttpResult::Err(e) => f(error_code(&e), &e),
+            HttpResult::Download(name, body) =>
+            {
+                let mut resp = Response::new(Body::from(body));
+                let headers = resp.headers_mut();
+                headers.insert("Content-Type", "text/plain; charset=utf-8".parse().unwrap());
+                headers.insert(
+                    "Content-Disposition",
+                    format!("attachment; filename=\"{name}\"").parse().unwrap(),
+                );
+                resp
+            },
+            HttpResult::Err(e) =>
+            {
+                let error = ApiError::from(&e);
+                f(error.code.status(), &error)
+            },
         }
     }
 }
 
 
-async fn handle_request(req: Request<Body>, state: State) -> Response<Body>
+/*
+ * Calls the handler of the route, path parameters that do not parse are
+ * returned as errors.
+ */
+async fn dispatch(
+    req: Request<Body>,
+    state: State,
+    route: Route,
+    params: Params,
+) -> Result<HttpResult, HttpError>
 {
-    if let Some(path) = req.uri().path().strip_prefix("/api/")
+    let id = || params.get::<ObjectId>("id");
+    Ok(match route
     {
-        let mut info = log_req(&req);
-        info.push(' ');
+        Route::Register => register(req, state).await,
+        Route::Login => login(req, state).await,
+        Route::Logout => logout(req, state).await,
+        Route::Games => games(req, state).await,
+        Route::Game => game(req, state, id()?).await,
+        Route::Moves => moves(req, state, id()?).await,
+        Route::Export | Route::LegacyExport => export(req, state, id()?).await,
+        Route::UserGames => user_games(req, state, params.get("name")?).await,
+        Route::Challenges => challenges(req, state).await,
+        Route::Challenge => challenge(req, state, id()?).await,
+        Route::Bot => bot(req, state).await,
+        Route::Analyze => analyze(req, state).await,
+        Route::Explore => explore(req, state).await,
+        Route::Puzzle => puzzle(req, state).await,
+        Route::Import => import(req, state).await,
+        Route::LegacyGame => legacy_game(req, state).await,
+        Route::LegacyCreateGame => create_game(req, state).await,
+        Route::LegacyHome => home(req, state).await,
+    })
+}
+
+async fn handle_request(req: Request<Body>, state: State) -> Response<Body>
+{
+    let mut info = log_req(&req);
+    info.push(' ');
 
-        let now = std::time::Instant::now();
-        let resp = match path
+    let now = std::time::Instant::now();
+    let path = req.uri().path().strip_prefix("/api/").unwrap_or_default();
+    // Paths that match no route are logged like any other request
+    let (resp, deprecated) = match router::route(ROUTES, path)
+    {
+        Some((route, params)) =>
         {
-            "register" => register(req, state).await,
-            "login" => login(req, state).await,
-            "game" => game(req, state).await,
-            "create-game" => create_game(req, state).await,
-            "home" => home(req, state).await,
+            let resp = dispatch(req, state, route, params).await.unwrap_or_else(HttpResult::Err);
+            (resp, route.deprecated())
+        },
+        None => (HttpResult::Err(HttpError::NotFound), false),
+    };
 
-            _ => HttpResult::Err(HttpError::NotFound),
-        };
+    info.push_str(&log_resp(&resp, now.elapsed()));
+    println!("{}", info);
 
-        info.push_str(&log_resp(&resp, now.elapsed()));
-        println!("{}", info);
-        resp
-    }
-    else
+    let mut resp: Response<Body> = resp.into();
+    if deprecated
     {
-        HttpResult::Err(HttpError::NotFound)
+        resp.headers_mut().insert("Deprecation", "true".parse().unwrap());
     }
-    .into()
+    resp
 }
 
 pub async fn handle(req: Request<Body>, state: State) -> Result<Response<Body>, Infallible>
@@ -179,3 +365,232 @@ pub async fn handle(req: Request<Body>, state: State) -> Result<Response<Body>,
     }
     .add_cors_headers())
 }
+
+
+#[cfg(test)]
+mod test
+{
+    use shared::model::*;
+
+    use super::*;
+
+    async fn read(resp: Response<Body>) -> (StatusCode, String)
+    {
+        let status = resp.status();
+        let body = body::to_bytes(resp.into_body()).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_body_is_the_value()
+    {
+        let (status, body) = read(HttpResult::new(HttpResult::Create, vec![1, 2]).into()).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body, "[1,2]");
+    }
+
+    #[tokio::test]
+    async fn test_errors_have_a_status_and_code()
+    {
+        let errors = [
+            (HttpError::Serialize, 400, ErrorCode::InvalidBody),
+            (HttpError::InvalidLogin, 401, ErrorCode::InvalidLogin),
+            (HttpError::Database(DatabaseError::Conflict), 409, ErrorCode::Conflict),
+            (HttpError::Database(DatabaseError::UserDontExist), 404, ErrorCode::NotFound),
+            (HttpError::Channel("closed".into()), 500, ErrorCode::Internal),
+        ];
+
+        for (e, status, code) in errors
+        {
+            let (got, body) = read(HttpResult::Err(e).into()).await;
+            let error: ApiError = serde_json::from_str(&body).unwrap();
+            assert_eq!(got.as_u16(), status);
+            assert_eq!(error.code, code);
+        }
+
+        let (_, body) = read(HttpResult::Err(HttpError::BadRequest("bad id".into())).into()).await;
+        let error: ApiError = serde_json::from_str(&body).unwrap();
+        assert_eq!(error.details.as_deref(), Some("bad id"));
+        assert_eq!(error.to_string(), "bad request: bad id");
+    }
+
+    fn state() -> State
+    {
+        use std::sync::Arc;
+
+        use tokio::sync::{broadcast, mpsc, Semaphore};
+
+        State {
+            storage:  Arc::new(crate::database::Memory::default()),
+            tx:       broadcast::channel(10).0,
+            bots:     mpsc::channel(10).0,
+            analysis: Arc::new(Semaphore::new(1)),
+            config:   Arc::new(crate::config::Config::default()),
+        }
+    }
+
+    async fn session(state: &State, name: &str) -> String
+    {
+        let cred = UserCredentials {
+            name: name.into(), password: "password".into()
+        };
+        let id = state.db().register_user(cred).await.unwrap();
+        state.db().create_session(id).await.unwrap().token
+    }
+
+    async fn send(state: &State, method: Method, uri: &str, token: &str) -> Response<Body>
+    {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Authorization", format!("Bearer {token}"))
+            .body(Body::empty())
+            .unwrap();
+        handle_request(req, state.clone()).await
+    }
+
+    #[tokio::test]
+    async fn test_resources_have_paths()
+    {
+        let state = state();
+        let sivert = session(&state, "sivert").await;
+        let sofie = session(&state, "sofie").await;
+
+        let (status, body) =
+            read(send(&state, Method::POST, "/api/challenges", &sivert).await).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let challenge: ObjectId = serde_json::from_str(&body).unwrap();
+
+        let uri = format!("/api/challenges/{challenge}");
+        let (status, body) = read(send(&state, Method::PUT, &uri, &sofie).await).await;
+        assert_eq!(status, StatusCode::OK);
+        let accepted: AcceptGame = serde_json::from_str(&body).unwrap();
+
+        let (status, _) = read(send(&state, Method::PUT, &uri, &sofie).await).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let uri = format!("/api/games/{}", accepted.object_id);
+        let (status, body) = read(send(&state, Method::GET, &uri, "").await).await;
+        assert_eq!(status, StatusCode::OK);
+        let game: GameResource = serde_json::from_str(&body).unwrap();
+        assert_eq!(game._id, accepted.object_id);
+
+        let (_, body) = read(send(&state, Method::GET, "/api/users/sofie/games", "").await).await;
+        let games: Page<OnGoingGame> = serde_json::from_str(&body).unwrap();
+        assert_eq!(games.items.len(), 1);
+
+        let (status, _) =
+            read(send(&state, Method::GET, "/api/users/nobody/games", "").await).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (_, body) =
+            read(send(&state, Method::GET, "/api/games?state=complete", "").await).await;
+        assert_eq!(body, r#"{"items":[],"next":null}"#);
+
+        let uri = "/api/games?ruleset=Base&player=sofie&sort=oldest&limit=1";
+        let (_, body) = read(send(&state, Method::GET, uri, "").await).await;
+        let games: Page<OnGoingGame> = serde_json::from_str(&body).unwrap();
+        assert_eq!(games.items[0].game_object_id, accepted.object_id);
+
+        let uri = "/api/games?from=2020-13-01";
+        let (status, _) = read(send(&state, Method::GET, uri, "").await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_logged_out_token_is_refused()
+    {
+        let state = state();
+        let sivert = session(&state, "sivert").await;
+
+        let (status, _) = read(send(&state, Method::POST, "/api/logout", &sivert).await).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) =
+            read(send(&state, Method::POST, "/api/challenges", &sivert).await).await;
+        let error: ApiError = serde_json::from_str(&body).unwrap();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(error.code, ErrorCode::Unauthorized);
+    }
+
+    #[tokio::test]
+    async fn test_imported_record_is_exported_as_it_was()
+    {
+        let state = state();
+
+        // Black resigns, nothing on the board shows it. Every move has a
+        // comment, so the review has nothing to add
+        let record = "[GameType \"Base\"]\n[Date \"2021-03-14\"]\n[White \"alice\"]\n[Black \
+                      \"bob\"]\n[Result \"WhiteWins\"]\n\n1. wS1 {10:01:02} ; Opens\n2. bG1 -wS1 \
+                      ; Resigns\n";
+        let body = serde_json::to_string(&ImportGame::Record(record.into())).unwrap();
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri("/api/import")
+            .body(Body::from(body))
+            .unwrap();
+        let (status, body) = read(handle_request(req, state.clone()).await).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let id: ObjectId = serde_json::from_str(&body).unwrap();
+
+        let uri = format!("/api/games/{id}/export");
+        let (status, body) = read(send(&state, Method::GET, &uri, "").await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, record);
+    }
+
+    #[tokio::test]
+    async fn test_bad_paths()
+    {
+        let state = state();
+
+        let (status, body) = read(send(&state, Method::GET, "/api/games/1234", "").await).await;
+        let error: ApiError = serde_json::from_str(&body).unwrap();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.details.as_deref(), Some("bad id: 1234"));
+
+        let (status, _) = read(send(&state, Method::GET, "/api/games?state=lost", "").await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        for uri in ["/api/gamez", "/api/games/1/2/3", "/games"]
+        {
+            let (status, _) = read(send(&state, Method::GET, uri, "").await).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_old_routes_still_work()
+    {
+        let state = state();
+        let sivert = session(&state, "sivert").await;
+        let sofie = session(&state, "sofie").await;
+
+        let resp = send(&state, Method::POST, "/api/create-game", &sivert).await;
+        assert!(resp.headers().contains_key("Deprecation"));
+        let (_, body) = read(resp).await;
+        let challenge: ObjectId = serde_json::from_str(&body).unwrap();
+
+        let (_, body) = read(send(&state, Method::POST, "/api/home", &sofie).await).await;
+        let bundles: Vec<CreateGameChallengeBundle> = serde_json::from_str(&body).unwrap();
+        assert_eq!(bundles[0].games, vec![challenge]);
+
+        let resp = send(&state, Method::GET, "/api/challenges", &sofie).await;
+        assert!(!resp.headers().contains_key("Deprecation"));
+        let page: Page<CreateGameChallenge> = serde_json::from_str(&read(resp).await.1).unwrap();
+        assert_eq!(page.items[0]._id, challenge);
+
+        let (status, body) = read(send(&state, Method::GET, "/api/game?q=all", "").await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "[]");
+
+        let id = state.db().find_session(&sofie).await.unwrap();
+        let uri = format!("/api/game?q=user({id})");
+        let (status, body) = read(send(&state, Method::GET, &uri, "").await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "[]");
+
+        let (status, _) = read(send(&state, Method::GET, "/api/game?q=user(1)", "").await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,108 @@
+use hyper::{Body, Method, Request};
+use shared::{
+    engine::{side_to_move, AlphaBeta, Engine, TranspositionTable},
+    model::{Analysis, Color, PositionRequest},
+    uhp::Position,
+};
+use tokio::task;
+
+use super::{get_body, HttpError, HttpResult};
+use crate::State;
+
+
+const DEPTH: u32 = 3;
+const TABLE_SIZE: usize = 1 << 16;
+
+
+/*
+ * Function for analyzing a position:
+ *     POST: Takes a PositionRequest in the body, returns an Analysis
+ * The search runs on the blocking thread pool, at most `analysis_workers`
+ * from the config at a time. Requests are turned away while every worker is
+ * busy.
+ */
+pub async fn analyze(req: Request<Body>, state: State) -> HttpResult
+{
+    match *req.method()
+    {
+        Method::POST => match get_body::<PositionRequest>(req).await
+        {
+            Some(req) =>
+            {
+                // The error is not Send, so it can not be held over the search
+                let pos = match position(req, &state).await
+                {
+                    Ok(pos) => pos,
+                    Err(e) => return HttpResult::Err(e),
+                };
+                search(pos, &state).await
+            },
+            None => HttpResult::Err(HttpError::Serialize),
+        },
+        _ => HttpResult::Err(HttpError::MethodNotAllowed),
+    }
+}
+
+// The position a request points at, shared with the opening explorer
+pub(super) async fn position(req: PositionRequest, state: &State) -> Result<Position, HttpError>
+{
+    match req
+    {
+        PositionRequest::Game {
+            game,
+            moves,
+        } =>
+        {
+            let game = state.db().get_game_by_id(game).await.map_err(HttpError::Database)?;
+            match game.move_list.get(..moves)
+            {
+                Some(moves) => Ok(Position::from_moves(moves)),
+                None => Err(HttpError::BadRequest(format!(
+                    "the game only has {} moves",
+                    game.move_list.len()
+                ))),
+            }
+        },
+        PositionRequest::Position(s) =>
+        {
+            s.parse().map_err(|e| HttpError::BadRequest(format!("{e}")))
+        },
+    }
+}
+
+async fn search(pos: Position, state: &State) -> HttpResult
+{
+    let permit = match state.analysis.clone().try_acquire_owned()
+    {
+        Ok(permit) => permit,
+        Err(_) => return HttpResult::Err(HttpError::Busy),
+    };
+
+    let res = task::spawn_blocking(move || {
+        let _permit = permit;
+        let board = pos.board();
+
+        let mut engine = AlphaBeta::new(DEPTH);
+        engine.table = Some(TranspositionTable::new(TABLE_SIZE));
+        let res = engine.search(board);
+
+        let score = match side_to_move(board)
+        {
+            Color::White => res.score,
+            Color::Black => -res.score,
+        };
+        Analysis {
+            score,
+            depth: DEPTH,
+            best_move: res.best_move,
+            pv: res.pv,
+        }
+    })
+    .await;
+
+    match res
+    {
+        Ok(analysis) => HttpResult::new(HttpResult::Ok, analysis),
+        Err(e) => HttpResult::Err(HttpError::Channel(Box::new(e))),
+    }
+}
@@ -0,0 +1,89 @@
+use hyper::{Body, Method, Request};
+use mongodb::bson::oid::ObjectId;
+use shared::model::{Bot, BotChallenge};
+use tokio::sync::oneshot;
+
+use super::{caller, get_body, HttpError, HttpResult};
+use crate::{bot::Challenge, State};
+
+
+/*
+ * Function for the bots:
+ *     GET : list the bots
+ *     POST: challenge a bot. Takes a BotChallenge struct in the body, the
+ * bot           accepts right away and the new game is returned.
+ */
+pub async fn bot(req: Request<Body>, state: State) -> HttpResult
+{
+    match *req.method()
+    {
+        Method::GET => match state.db().get_bots().await
+        {
+            Ok(users) => HttpResult::new(
+                HttpResult::Ok,
+                users
+                    .into_iter()
+                    .filter_map(|user| {
+                        user.bot.map(|strength| Bot {
+                            name: user.name,
+                            strength,
+                        })
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+            Err(e) => HttpResult::Err(HttpError::Database(e)),
+        },
+        Method::POST =>
+        {
+            let creator = match caller(&req, &state).await
+            {
+                Ok(id) => id,
+                Err(e) => return HttpResult::Err(e),
+            };
+            match get_body::<BotChallenge>(req).await
+            {
+                Some(challenge) => challenge_bot(creator, challenge, state).await,
+                None => HttpResult::Err(HttpError::Serialize),
+            }
+        },
+        _ => HttpResult::Err(HttpError::MethodNotAllowed),
+    }
+}
+
+async fn challenge_bot(creator: ObjectId, challenge: BotChallenge, state: State) -> HttpResult
+{
+    let bot = match state.db().get_bots().await
+    {
+        Ok(bots) => match bots.into_iter().find(|user| user.name == challenge.bot)
+        {
+            Some(bot) => bot._id,
+            None => return HttpResult::Err(HttpError::NotFound),
+        },
+        Err(e) => return HttpResult::Err(HttpError::Database(e)),
+    };
+
+    let game = match state.db().create_game(creator, state.config.max_create_game).await
+    {
+        Ok(game) => game,
+        Err(e) => return HttpResult::Err(HttpError::Database(e)),
+    };
+
+    let (reply, rx) = oneshot::channel();
+    let msg = Challenge {
+        creator,
+        game,
+        bot,
+        reply,
+    };
+    if let Err(e) = state.bots.send(msg).await
+    {
+        return HttpResult::Err(HttpError::Channel(Box::new(e)));
+    }
+
+    match rx.await
+    {
+        Ok(Ok(accept)) => HttpResult::new(HttpResult::Create, accept),
+        Ok(Err(e)) => HttpResult::Err(HttpError::Database(e)),
+        Err(e) => HttpResult::Err(HttpError::Channel(Box::new(e))),
+    }
+}
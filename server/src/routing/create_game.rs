@@ -1,37 +1,120 @@
 use hyper::{Body, Method, Request};
+use mongodb::bson::oid::ObjectId;
+use shared::model::{CreateGameChallenge, CreateGameFormResponse, Page};
 
-use super::{get_body, HttpError, HttpResult};
-use crate::{database, State};
+use super::{caller, get_body, listing, HttpError, HttpResult};
+use crate::State;
 
 
 /*
- * Function for handling game invites:
- *     POST: create a new game invite. Takes an AcceptGame struct in the body
- *     PUT : Accept a game invite.     Takes the ObjectId of the invite in
- * the body.
+ * The old `/api/create-game`, see `challenges` and `challenge` for the
+ * routes that replace it. Game invites are made and accepted by the caller:
+ *     POST: create a new game invite.
+ *     PUT : Accept a game invite. Takes a CreateGameFormResponse.
  */
 pub async fn create_game(req: Request<Body>, state: State) -> HttpResult
 {
+    let user = match caller(&req, &state).await
+    {
+        Ok(id) => id,
+        Err(e) => return HttpResult::Err(e),
+    };
+
     match *req.method()
     {
         Method::PUT => match get_body(req).await
         {
-            Some(ag) => match database::accept_game(state.db(), ag).await
+            Some(ag) => match state.db().accept_game(user, ag).await
             {
                 Ok(res) => HttpResult::new(HttpResult::Ok, res),
                 Err(e) => HttpResult::Err(HttpError::Database(e)),
             },
             None => HttpResult::Err(HttpError::Serialize),
         },
-        Method::POST => match get_body(req).await
+        Method::POST => match state.db().create_game(user, state.config.max_create_game).await
+        {
+            Ok(res) => HttpResult::new(HttpResult::Ok, res),
+            Err(e) => HttpResult::Err(HttpError::Database(e)),
+        },
+        _ => HttpResult::Err(HttpError::MethodNotAllowed),
+    }
+}
+
+/*
+ * The open challenges, `/api/challenges`:
+ *     GET : a page of the challenges the caller can accept, see ListQuery
+ *     POST: create a new challenge
+ * Everyone's challenges are listed when the caller is not logged in.
+ */
+pub async fn challenges(req: Request<Body>, state: State) -> HttpResult
+{
+    match *req.method()
+    {
+        Method::GET =>
         {
-            Some(ag) => match database::create_game(state.db(), ag).await
+            let user = caller(&req, &state).await.unwrap_or_else(|_| ObjectId::new());
+            let listing = match listing(&req, false)
             {
-                Ok(res) => HttpResult::new(HttpResult::Ok, res),
+                Ok(Some(listing)) => listing,
+                Ok(None) =>
+                {
+                    return HttpResult::new(HttpResult::Ok, Page::<CreateGameChallenge>::default())
+                },
+                Err(e) => return HttpResult::Err(e),
+            };
+            match state.db().find_challenges(user, &listing).await
+            {
+                Ok(page) => HttpResult::new(HttpResult::Ok, page),
                 Err(e) => HttpResult::Err(HttpError::Database(e)),
-            },
-            None => HttpResult::Err(HttpError::Serialize),
+            }
+        },
+        Method::POST =>
+        {
+            let user = match caller(&req, &state).await
+            {
+                Ok(id) => id,
+                Err(e) => return HttpResult::Err(e),
+            };
+            match state.db().create_game(user, state.config.max_create_game).await
+            {
+                Ok(res) => HttpResult::new(HttpResult::Create, res),
+                Err(e) => HttpResult::Err(HttpError::Database(e)),
+            }
         },
         _ => HttpResult::Err(HttpError::MethodNotAllowed),
     }
 }
+
+/*
+ * One challenge, `/api/challenges/{id}`:
+ *     PUT: accept it, the game that starts is returned
+ */
+pub async fn challenge(req: Request<Body>, state: State, id: ObjectId) -> HttpResult
+{
+    if req.method() != Method::PUT
+    {
+        return HttpResult::Err(HttpError::MethodNotAllowed);
+    }
+    let user = match caller(&req, &state).await
+    {
+        Ok(id) => id,
+        Err(e) => return HttpResult::Err(e),
+    };
+
+    // The caller's own challenges are not listed, so they can not accept them
+    let form = match state.db().find_challenge_creator(id).await
+    {
+        Ok(creator) if creator != user => CreateGameFormResponse {
+            game: id,
+            creator,
+        },
+        Ok(_) => return HttpResult::Err(HttpError::NotFound),
+        Err(e) => return HttpResult::Err(HttpError::Database(e)),
+    };
+
+    match state.db().accept_game(user, form).await
+    {
+        Ok(res) => HttpResult::new(HttpResult::Ok, res),
+        Err(e) => HttpResult::Err(HttpError::Database(e)),
+    }
+}
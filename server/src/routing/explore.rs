@@ -0,0 +1,36 @@
+use hyper::{Body, Method, Request};
+use shared::model::PositionRequest;
+
+use super::{analyze::position, get_body, HttpError, HttpResult};
+use crate::State;
+
+
+/*
+ * Function for the opening explorer:
+ *     POST: Takes a PositionRequest in the body, returns the moves played
+ * from that position in completed games as a list of ExplorerMove.
+ */
+pub async fn explore(req: Request<Body>, state: State) -> HttpResult
+{
+    match *req.method()
+    {
+        Method::POST => match get_body::<PositionRequest>(req).await
+        {
+            Some(req) =>
+            {
+                let pos = match position(req, &state).await
+                {
+                    Ok(pos) => pos,
+                    Err(e) => return HttpResult::Err(e),
+                };
+                match state.db().explore(pos.board()).await
+                {
+                    Ok(moves) => HttpResult::new(HttpResult::Ok, moves),
+                    Err(e) => HttpResult::Err(HttpError::Database(e)),
+                }
+            },
+            None => HttpResult::Err(HttpError::Serialize),
+        },
+        _ => HttpResult::Err(HttpError::MethodNotAllowed),
+    }
+}
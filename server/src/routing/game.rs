@@ -1,179 +1,307 @@
 use hyper::{Body, Method, Request};
 use mongodb::bson::oid::ObjectId;
-use shared::model::Move;
-
-use super::{get_body, HttpError, HttpResult};
-use crate::{
-    database::{
-        complete_game, get_active_games, get_game_by_id, get_old_games, get_users_games, play_move,
-        DatabaseError::GameNotComplete,
-    },
-    State,
+use serde::Deserialize;
+use shared::{
+    model::{Move, OnGoingGame, Page},
+    record::Record,
 };
 
+use super::{caller, get_body, listing, router::query, HttpError, HttpResult};
+use crate::{database::DatabaseError::GameNotComplete, State};
+
 /*
- * Function for games. The http methods are:
- *     GET   : get game/games
- *     POST  : play a move on a game
- *     DElETE: delete a finished game
+ * The games, `/api/games`:
+ *     GET: a page of the ongoing games, see ListQuery
+ * The finished games are listed with `?state=complete`.
+ */
+pub async fn games(req: Request<Body>, state: State) -> HttpResult
+{
+    if req.method() != Method::GET
+    {
+        return HttpResult::Err(HttpError::MethodNotAllowed);
+    }
+    find_games(&req, &state, None).await
+}
+
+/*
+ * One game, `/api/games/{id}`:
+ *     GET   : the game
+ *     DELETE: complete a finished game
  */
-pub async fn game(req: Request<Body>, state: State) -> HttpResult
+pub async fn game(req: Request<Body>, state: State, id: ObjectId) -> HttpResult
 {
     match *req.method()
     {
-        Method::GET => get(req, state).await,
-        Method::POST => post(req, state).await,
-        Method::DELETE => delete(req, state).await,
+        Method::GET => get(state, id).await,
+        Method::DELETE => delete(state, id).await,
         _ => HttpResult::Err(HttpError::MethodNotAllowed),
     }
 }
 
 /*
- * Get game/games based on the query in the URL, See Query::from_str for more
- * info
+ * The moves of a game, `/api/games/{id}/moves`:
+ *     POST: play a move on the game, the body is the Move
+ */
+pub async fn moves(req: Request<Body>, state: State, id: ObjectId) -> HttpResult
+{
+    match *req.method()
+    {
+        Method::POST => post(req, state, Some(id)).await,
+        _ => HttpResult::Err(HttpError::MethodNotAllowed),
+    }
+}
+
+/*
+ * The games of a user, `/api/users/{name}/games`, in pages like `games`.
+ */
+pub async fn user_games(req: Request<Body>, state: State, name: String) -> HttpResult
+{
+    if req.method() != Method::GET
+    {
+        return HttpResult::Err(HttpError::MethodNotAllowed);
+    }
+
+    match state.db().find_user_by_name(&name).await
+    {
+        Ok(user) => find_games(&req, &state, Some(user.name)).await,
+        Err(e) => HttpResult::Err(HttpError::Database(e)),
+    }
+}
+
+// The page of games the query asks for, with `player` in them if given
+async fn find_games(req: &Request<Body>, state: &State, player: Option<String>) -> HttpResult
+{
+    let complete = match query::<GamesQuery>(req)
+    {
+        Ok(query) => query.state == GameState::Complete,
+        Err(e) => return HttpResult::Err(e),
+    };
+    let mut listing = match listing(req, complete)
+    {
+        Ok(Some(listing)) => listing,
+        Ok(None) => return HttpResult::new(HttpResult::Ok, Page::<OnGoingGame>::default()),
+        Err(e) => return HttpResult::Err(e),
+    };
+    if player.is_some()
+    {
+        listing.player = player;
+    }
+
+    match state.db().find_games(&listing).await
+    {
+        Ok(page) => HttpResult::new(HttpResult::Ok, page),
+        Err(e) => HttpResult::Err(HttpError::Database(e)),
+    }
+}
+
+/*
+ * The old `/api/game`, kept for clients that have not moved to the routes
+ * above. The http methods are:
+ *     GET   : get game/games, see LegacyQuery
+ *     POST  : play a move on a game
+ *     DElETE: delete a finished game, the id is in the body
  */
-async fn get(req: Request<Body>, state: State) -> HttpResult
+pub async fn legacy_game(req: Request<Body>, state: State) -> HttpResult
 {
-    match req.uri().query().and_then(|uri| Query::from_str(uri).ok())
+    match *req.method()
     {
-        Some(q) => match q
+        Method::GET => legacy_get(req, state).await,
+        Method::POST => post(req, state, None).await,
+        Method::DELETE => match get_body(req).await
         {
-            Query::All => match get_active_games(state.db()).await
-            {
-                Ok(res) => HttpResult::new(HttpResult::Ok, res),
-                Err(e) => HttpResult::Err(HttpError::Database(e)),
-            },
+            Some(id) => delete(state, id).await,
+            None => HttpResult::Err(HttpError::Serialize),
+        },
+        _ => HttpResult::Err(HttpError::MethodNotAllowed),
+    }
+}
 
-            Query::Old => match get_old_games(state.db()).await
-            {
-                Ok(res) => HttpResult::new(HttpResult::Ok, res),
-                Err(e) => HttpResult::Err(HttpError::Database(e)),
-            },
+async fn legacy_get(req: Request<Body>, state: State) -> HttpResult
+{
+    let q = match query::<LegacyQuery>(&req)
+    {
+        Ok(query) => query.q,
+        Err(e) => return HttpResult::Err(e),
+    };
 
-            Query::Id(object_id) => match get_game_by_id(state.db(), object_id).await
+    let res = match q.as_str()
+    {
+        "all" => state.db().get_active_games().await,
+        "old" => state.db().get_old_games().await,
+        q => match q.strip_prefix("user(").and_then(|q| q.strip_suffix(')'))
+        {
+            Some(id) => match ObjectId::parse_str(id)
             {
-                Ok(res) => HttpResult::new(HttpResult::Ok, res),
-                Err(e) => HttpResult::Err(HttpError::Database(e)),
+                Ok(id) => state.db().get_users_games(id).await,
+                Err(_) => return HttpResult::Err(HttpError::BadRequest("bad user id".into())),
             },
-            Query::User(object_id) => match get_users_games(state.db(), object_id).await
+            None => match ObjectId::parse_str(q)
             {
-                Ok(res) => HttpResult::new(HttpResult::Ok, res),
-                Err(e) => HttpResult::Err(HttpError::Database(e)),
+                Ok(id) => return get(state, id).await,
+                Err(_) => return HttpResult::Err(HttpError::BadRequest("bad game id".into())),
             },
         },
-        _ => HttpResult::Err(HttpError::Serialize),
+    };
+    match res
+    {
+        Ok(res) => HttpResult::new(HttpResult::Ok, res),
+        Err(e) => HttpResult::Err(HttpError::Database(e)),
+    }
+}
+
+async fn get(state: State, id: ObjectId) -> HttpResult
+{
+    match state.db().get_game_by_id(id).await
+    {
+        Ok(res) => HttpResult::new(HttpResult::Ok, res),
+        Err(e) => HttpResult::Err(HttpError::Database(e)),
     }
 }
 
 
 /*
  * Function for handling moves.
- * Expects a Move struct in the request body.
- * Additionally sends the move to the websocket server to send to the
- * spectators.
+ * Expects a Move struct in the request body, played by the caller. The move
+ * has to be for `game` when the route names one.
  */
-async fn post(req: Request<Body>, state: State) -> HttpResult
+async fn post(req: Request<Body>, state: State, game: Option<ObjectId>) -> HttpResult
 {
+    let player_id = match caller(&req, &state).await
+    {
+        Ok(id) => id,
+        Err(e) => return HttpResult::Err(e),
+    };
+
     match get_body::<Move>(req).await
     {
-        Some(r#move) => match play_move(state.db(), r#move.clone()).await
+        Some(r#move) if game.is_some_and(|id| id != r#move.game_id) =>
         {
-            Ok(()) =>
-            {
-                let msg = crate::websocket::Message {
-                    r#move,
-                };
-                match state.tx.send(msg).await
-                {
-                    Ok(_) => HttpResult::new(HttpResult::Ok, ()),
-                    Err(e) => HttpResult::Err(HttpError::Channel(Box::new(e))),
-                }
-            },
-            Err(e) => HttpResult::Err(HttpError::Database(e)),
+            HttpResult::Err(HttpError::BadRequest("the move is for another game".into()))
+        },
+        Some(r#move) => match submit_move(&state, player_id, r#move).await
+        {
+            Ok(()) => HttpResult::new(HttpResult::Ok, ()),
+            Err(e) => HttpResult::Err(e),
         },
         _ => HttpResult::Err(HttpError::Serialize),
     }
 }
 
+/*
+ * Validate and play a move, then send it to the websocket server for the
+ * spectators and to the bots. Both human and bot moves go through here.
+ */
+pub async fn submit_move(state: &State, player_id: ObjectId, r#move: Move)
+    -> Result<(), HttpError>
+{
+    state
+        .db()
+        .play_move(player_id, r#move.clone())
+        .await
+        .map_err(HttpError::Database)?;
+
+    let msg = crate::websocket::Message {
+        r#move,
+    };
+    state.tx.send(msg).map(|_| ()).map_err(|e| HttpError::Channel(Box::new(e)))
+}
+
 /*
  * Function for completing a game.
- * Expects the ObjectId of the game being deleted in the Body.
+ * The game is reviewed, mined for puzzles and added to the opening index
+ * in the background afterwards.
  */
-async fn delete(req: Request<Body>, state: State) -> HttpResult
+async fn delete(state: State, id: ObjectId) -> HttpResult
 {
-    match get_body(req).await
+    match state.db().get_game_by_id(id).await
     {
-        Some(id) => match get_game_by_id(state.db(), id).await
+        Ok(game) =>
         {
-            Ok(game) =>
+            if game.board.is_complete()
             {
-                if game.board.is_complete()
-                {
-                    complete_game(state.db(), id).await.unwrap();
-                    HttpResult::new(HttpResult::Ok, ())
-                }
-                else
-                {
-                    HttpResult::Err(HttpError::Database(GameNotComplete))
-                }
-            },
-            Err(e) => HttpResult::Err(HttpError::Database(e)),
+                state.db().complete_game(id).await.unwrap();
+                crate::explorer::spawn_index(state.clone());
+                crate::puzzle::spawn_mining(state.clone(), id);
+                crate::review::spawn_review(state, id);
+                HttpResult::new(HttpResult::Ok, ())
+            }
+            else
+            {
+                HttpResult::Err(HttpError::Database(GameNotComplete))
+            }
         },
-        _ => HttpResult::Err(HttpError::Serialize),
+        Err(e) => HttpResult::Err(HttpError::Database(e)),
     }
 }
 
-enum Query
-{
-    All,
-    Old,
-    Id(ObjectId),
-    User(ObjectId),
-}
-
-
 /*
- * Every URL starts with q=
- *     user(<id>) gets all the users (ongoing) games
- *     all        fetches all games
- *     old        fetches all COMPLETE games
- *     <id>       fetches the game with the corresponding id
+ * The game as a text record to download, see `shared::record`. Finished
+ * games are annotated with their review, imported ones get back what their
+ * record said.
  */
-use std::str::FromStr;
-impl FromStr for Query
+pub async fn export(req: Request<Body>, state: State, id: ObjectId) -> HttpResult
 {
-    type Err = ();
+    use chrono::prelude::*;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err>
+    if req.method() != Method::GET
     {
-        // HAS to start with q= atleast
-        if s.len() <= 2
-        {
-            return Err(());
-        }
+        return HttpResult::Err(HttpError::MethodNotAllowed);
+    }
 
-        if s.starts_with("q=user")
+    match state.db().get_game_by_id(id).await
+    {
+        Ok(game) =>
         {
-            // get the string s within "q=user(s)""
-            let id = &s[7..s.len() - 1];
-            match ObjectId::parse_str(id)
+            let mut record = Record::new(game.players, &game.move_list);
+            if let Some(info) = &game.record
             {
-                Ok(id) => Ok(Query::User(id)),
-                _ => Err(()),
+                record.set_info(info);
             }
-        }
-        else
-        {
-            match &s[2..]
+            // The board does not show a resignation
+            if let Some(result) = game.result
             {
-                "all" => Ok(Query::All),
-                "old" => Ok(Query::Old),
-                s => match ObjectId::parse_str(s)
-                {
-                    Ok(id) => Ok(Query::Id(id)),
-                    _ => Err(()),
-                },
+                record.result = Some(result.into());
             }
-        }
+            if record.date.is_none()
+            {
+                let created = Utc.timestamp_millis(game._id.timestamp().timestamp_millis());
+                record.date = Some(created.format("%Y-%m-%d").to_string());
+            }
+            if let Some(review) = &game.review
+            {
+                record.annotate(review);
+            }
+            HttpResult::Download(format!("{id}.hive"), record.to_string())
+        },
+        Err(e) => HttpResult::Err(HttpError::Database(e)),
     }
 }
+
+#[derive(Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum GameState
+{
+    #[default]
+    Active,
+    Complete,
+}
+
+#[derive(Deserialize)]
+struct GamesQuery
+{
+    #[serde(default)]
+    state: GameState,
+}
+
+/*
+ * The old query, `?q=` followed by
+ *     user(<id>) gets all the users (ongoing) games
+ *     all        fetches all games
+ *     old        fetches all COMPLETE games
+ *     <id>       fetches the game with the corresponding id
+ */
+#[derive(Deserialize)]
+struct LegacyQuery
+{
+    q: String,
+}
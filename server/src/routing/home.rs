@@ -1,13 +1,13 @@
 use hyper::{Body, Method, Request};
 use mongodb::bson::oid::ObjectId;
 
-use super::{get_body, HttpError, HttpResult};
-use crate::{database, State};
+use super::{caller, HttpError, HttpResult};
+use crate::State;
 
 /*
  * Function for getting home page information.
- * Takes the ObjectId of the user and returns available game invites.
- * Probably going do remove this at some point.
+ * Returns the game invites available to the caller.
+ * The old name of `GET /api/challenges`.
  */
 pub async fn home(req: Request<Body>, state: State) -> HttpResult
 {
@@ -15,11 +15,11 @@ pub async fn home(req: Request<Body>, state: State) -> HttpResult
     {
         Method::POST =>
         {
-            // Just generate and use a new ObjectId if none was received, i.e,
-            // user was not logged in
-            let object_id = get_body::<ObjectId>(req).await.unwrap_or_else(ObjectId::new);
+            // Just generate and use a new ObjectId if the user is not logged
+            // in, they can see every invite
+            let object_id = caller(&req, &state).await.unwrap_or_else(|_| ObjectId::new());
 
-            match database::home(state.db(), object_id).await
+            match state.db().home(object_id).await
             {
                 Ok(bundle) => HttpResult::new(HttpResult::Ok, bundle),
                 Err(e) => HttpResult::Err(HttpError::Database(e)),
@@ -0,0 +1,79 @@
+use hyper::{Body, Method, Request};
+use shared::{
+    engine::play_turn,
+    model::{Board, GameResult, ImportGame},
+    record::Record,
+    sgf,
+};
+
+use super::{get_body, HttpError, HttpResult};
+use crate::{model::game::Game, State};
+
+
+/*
+ * Function for importing games:
+ *     POST: Takes an ImportGame with a BoardSpace SGF record or an exported
+ *           record, the game is stored as finished and its id returned
+ */
+pub async fn import(req: Request<Body>, state: State) -> HttpResult
+{
+    match *req.method()
+    {
+        Method::POST => match get_body::<ImportGame>(req).await
+        {
+            Some(form) => import_game(form, state).await,
+            None => HttpResult::Err(HttpError::Serialize),
+        },
+        _ => HttpResult::Err(HttpError::MethodNotAllowed),
+    }
+}
+
+async fn import_game(form: ImportGame, state: State) -> HttpResult
+{
+    let bad_request =
+        |e: &dyn std::fmt::Display| HttpResult::Err(HttpError::BadRequest(e.to_string()));
+
+    let game = match form
+    {
+        ImportGame::Sgf(s) => match sgf::parse(&s)
+        {
+            Ok(game) =>
+            {
+                let board = game
+                    .moves
+                    .iter()
+                    .fold(Board::default(), |board, r#move| play_turn(&board, r#move));
+                Game::imported(game.players, board, game.moves)
+            },
+            Err(e) => return bad_request(&e),
+        },
+        // A record can end by resignation or on time, its result is kept
+        // along with the date, times and comments
+        ImportGame::Record(s) => match s.parse::<Record>()
+        {
+            Ok(record) =>
+            {
+                let board = record.board();
+                let info = record.info();
+                let moves = record.moves.into_iter().map(|m| m.r#move).collect();
+                let mut game = Game::imported(record.players, board, moves);
+                game.result = record.result.map(GameResult::from).or(game.result);
+                game.record = Some(info);
+                game
+            },
+            Err(e) => return bad_request(&e),
+        },
+    };
+
+    match state.db().import_game(game).await
+    {
+        Ok(id) =>
+        {
+            crate::explorer::spawn_index(state.clone());
+            crate::puzzle::spawn_mining(state.clone(), id);
+            crate::review::spawn_review(state, id);
+            HttpResult::new(HttpResult::Create, id)
+        },
+        Err(e) => HttpResult::Err(HttpError::Database(e)),
+    }
+}
@@ -1,14 +1,18 @@
 use hyper::{Body, Method, Request};
+use shared::model::{Session, UserCredentials};
 
-use super::{get_body, HttpError, HttpResult};
-use crate::{database, State};
+use super::{get_body, token, HttpError, HttpResult};
+use crate::{
+    database::{DatabaseError, DatabaseResult},
+    State,
+};
 
 
 /*
  * Function for logging in a user to the site.
  * The functions expects a UserCredentials struct inside the request.
  * The function checks whether a user is registered with said credentails
- * and returns the object id on success.
+ * and starts a session for them on success.
  */
 pub async fn login(req: Request<Body>, state: State) -> HttpResult
 {
@@ -16,9 +20,10 @@ pub async fn login(req: Request<Body>, state: State) -> HttpResult
     {
         Method::POST => match get_body(req).await
         {
-            Some(cred) => match database::login(state.db(), cred).await
+            Some(cred) => match login_session(state, cred).await
             {
-                Ok(id) => HttpResult::new(HttpResult::Ok, id),
+                Ok(session) => HttpResult::new(HttpResult::Ok, session),
+                Err(DatabaseError::UserDontExist) => HttpResult::Err(HttpError::InvalidLogin),
                 Err(e) => HttpResult::Err(HttpError::Database(e)),
             },
             None => HttpResult::Err(HttpError::Serialize),
@@ -26,3 +31,31 @@ pub async fn login(req: Request<Body>, state: State) -> HttpResult
         _ => HttpResult::Err(HttpError::MethodNotAllowed),
     }
 }
+
+/*
+ * Ends the session of the token the request is made with, the token can not
+ * be used again.
+ */
+pub async fn logout(req: Request<Body>, state: State) -> HttpResult
+{
+    if req.method() != Method::POST
+    {
+        return HttpResult::Err(HttpError::MethodNotAllowed);
+    }
+
+    match token(&req)
+    {
+        Some(token) => match state.db().delete_session(token).await
+        {
+            Ok(()) => HttpResult::new(HttpResult::Ok, ()),
+            Err(e) => HttpResult::Err(HttpError::Database(e)),
+        },
+        None => HttpResult::Err(HttpError::Unauthorized),
+    }
+}
+
+async fn login_session(state: State, cred: UserCredentials) -> DatabaseResult<Session>
+{
+    let user = state.db().login(cred).await?;
+    state.db().create_session(user).await
+}
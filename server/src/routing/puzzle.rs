@@ -0,0 +1,106 @@
+use hyper::{Body, Method, Request};
+use mongodb::bson::oid::ObjectId;
+use serde::Deserialize;
+use shared::{
+    engine::{check_line, is_legal_turn, play_turn},
+    model::{Board, PuzzleAttempt},
+};
+use tokio::task;
+
+use super::{get_body, router::query, HttpError, HttpResult};
+use crate::State;
+
+
+/*
+ * Function for puzzles:
+ *     GET : q=<id> gets that puzzle, without a query a random one
+ *     POST: Takes a PuzzleAttempt in the body, returns a PuzzleAnswer
+ */
+pub async fn puzzle(req: Request<Body>, state: State) -> HttpResult
+{
+    match *req.method()
+    {
+        Method::GET =>
+        {
+            let q = match query::<PuzzleQuery>(&req)
+            {
+                Ok(query) => query.q,
+                Err(e) => return HttpResult::Err(e),
+            };
+            let res = match q.as_deref().map(ObjectId::parse_str)
+            {
+                Some(Ok(id)) => state.db().get_puzzle(id).await,
+                Some(Err(_)) =>
+                {
+                    return HttpResult::Err(HttpError::BadRequest("bad puzzle id".into()))
+                },
+                None => state.db().random_puzzle().await,
+            };
+            match res
+            {
+                Ok(puzzle) => HttpResult::new(HttpResult::Ok, puzzle),
+                Err(e) => HttpResult::Err(HttpError::Database(e)),
+            }
+        },
+        Method::POST => match get_body::<PuzzleAttempt>(req).await
+        {
+            Some(attempt) => answer(attempt, state).await,
+            None => HttpResult::Err(HttpError::Serialize),
+        },
+        _ => HttpResult::Err(HttpError::MethodNotAllowed),
+    }
+}
+
+async fn answer(attempt: PuzzleAttempt, state: State) -> HttpResult
+{
+    let puzzle = match state.db().get_puzzle(attempt.puzzle).await
+    {
+        Ok(puzzle) => puzzle,
+        Err(e) => return HttpResult::Err(HttpError::Database(e)),
+    };
+    // The solver moves every other ply from the puzzle position
+    let played = attempt.played.len();
+    if played % 2 != 0 || played as u32 / 2 >= puzzle.length
+    {
+        return HttpResult::Err(HttpError::BadRequest("not the solver's move".into()));
+    }
+
+    let mut board = Board::default();
+    for r#move in &puzzle.moves
+    {
+        match is_legal_turn(&board, r#move)
+        {
+            true => board = play_turn(&board, r#move),
+            false =>
+            {
+                return HttpResult::Err(HttpError::BadRequest("the moves can not be played".into()))
+            },
+        }
+    }
+
+    // The answer is searched for like an analysis, and shares its workers
+    let permit = match state.analysis.clone().try_acquire_owned()
+    {
+        Ok(permit) => permit,
+        Err(_) => return HttpResult::Err(HttpError::Busy),
+    };
+    let res = task::spawn_blocking(move || {
+        let _permit = permit;
+        check_line(&board, &attempt.played, &attempt.r#move, puzzle.length)
+    })
+    .await;
+
+    match res
+    {
+        Ok(Some(answer)) => HttpResult::new(HttpResult::Ok, answer),
+        Ok(None) => HttpResult::Err(HttpError::BadRequest("not the puzzle's line".into())),
+        Err(e) => HttpResult::Err(HttpError::Channel(Box::new(e))),
+    }
+}
+
+
+#[derive(Deserialize)]
+struct PuzzleQuery
+{
+    q: Option<String>,
+}
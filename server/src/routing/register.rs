@@ -1,13 +1,14 @@
 use hyper::{Body, Method, Request};
 
 use super::{get_body, HttpError, HttpResult};
-use crate::{database::register_user, State};
+use crate::State;
 
 
 /*
  * Function for registering user to the site.
  * The functions expects a UserCredentials struct inside the request.
- * Returns the ObjectId of the newly registered user on success.
+ * Returns a session for the newly registered user on success, so they are
+ * logged in right away.
  */
 pub async fn register(req: Request<Body>, state: State) -> HttpResult
 {
@@ -15,9 +16,13 @@ pub async fn register(req: Request<Body>, state: State) -> HttpResult
     {
         Method::POST => match get_body(req).await
         {
-            Some(cred) => match register_user(state.db(), cred).await
+            Some(cred) => match state.db().register_user(cred).await
             {
-                Ok(id) => HttpResult::new(HttpResult::Create, id),
+                Ok(id) => match state.db().create_session(id).await
+                {
+                    Ok(session) => HttpResult::new(HttpResult::Create, session),
+                    Err(e) => HttpResult::Err(HttpError::Database(e)),
+                },
                 Err(e) => HttpResult::Err(HttpError::Database(e)),
             },
 
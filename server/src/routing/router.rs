@@ -0,0 +1,156 @@
+use std::str::FromStr;
+
+use hyper::{Body, Request};
+use percent_encoding::percent_decode_str;
+use serde::de::DeserializeOwned;
+
+use super::HttpError;
+
+
+/*
+ * The parts of a path that matched a `{name}` segment in the route, percent
+ * decoded.
+ */
+#[derive(Debug, Default)]
+pub struct Params(Vec<(&'static str, String)>);
+
+impl Params
+{
+    /*
+     * The parameter parsed as a T, a parameter that does not parse is a bad
+     * request. Panics if the route has no parameter with the name.
+     */
+    pub fn get<T: FromStr>(&self, name: &str) -> Result<T, HttpError>
+    {
+        let (_, value) = self
+            .0
+            .iter()
+            .find(|(key, _)| *key == name)
+            .unwrap_or_else(|| panic!("the route has no {{{}}}", name));
+
+        value.parse().map_err(|_| HttpError::BadRequest(format!("bad {name}: {value}")))
+    }
+}
+
+
+/*
+ * Matches a path like `games/1234/moves` against a pattern like
+ * `games/{id}/moves`. Every segment has to match, so `games/{id}` does not
+ * match `games/1234/moves`.
+ */
+pub fn matches(pattern: &'static str, path: &str) -> Option<Params>
+{
+    let mut params = Params::default();
+    let mut segments = path.trim_end_matches('/').split('/');
+
+    for part in pattern.split('/')
+    {
+        let segment = segments.next()?;
+        match part.strip_prefix('{').and_then(|part| part.strip_suffix('}'))
+        {
+            Some(name) if !segment.is_empty() =>
+            {
+                let value = percent_decode_str(segment).decode_utf8_lossy();
+                params.0.push((name, value.into_owned()))
+            },
+            None if part == segment => (),
+            _ => return None,
+        }
+    }
+
+    match segments.next()
+    {
+        Some(_) => None,
+        None => Some(params),
+    }
+}
+
+/*
+ * The first route in the table that matches the path, and its parameters.
+ */
+pub fn route<R: Copy>(routes: &[(&'static str, R)], path: &str) -> Option<(R, Params)>
+{
+    routes
+        .iter()
+        .find_map(|(pattern, route)| matches(pattern, path).map(|params| (*route, params)))
+}
+
+/*
+ * The query string of the URL deserialized as a T, a missing query is read
+ * as an empty one so that a T with only optional fields always works.
+ */
+pub fn query<T: DeserializeOwned>(req: &Request<Body>) -> Result<T, HttpError>
+{
+    serde_urlencoded::from_str(req.uri().query().unwrap_or(""))
+        .map_err(|e| HttpError::BadRequest(e.to_string()))
+}
+
+
+#[cfg(test)]
+mod test
+{
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[test]
+    fn test_segments_must_match()
+    {
+        assert!(matches("games", "games").is_some());
+        assert!(matches("games", "games/").is_some());
+        assert!(matches("games", "game").is_none());
+        assert!(matches("games/{id}", "games").is_none());
+        assert!(matches("games/{id}", "games/").is_none());
+        assert!(matches("games/{id}", "games/abc/moves").is_none());
+        assert!(matches("games/{id}/moves", "games/abc/export").is_none());
+    }
+
+    #[test]
+    fn test_params_are_parsed()
+    {
+        let params = matches("users/{name}/games/{n}", "users/sofie/games/3").unwrap();
+        assert_eq!(params.get::<String>("name").unwrap(), "sofie");
+        assert_eq!(params.get::<u32>("n").unwrap(), 3);
+
+        let params = matches("users/{name}/games", "users/s%C3%B8r%20en/games").unwrap();
+        assert_eq!(params.get::<String>("name").unwrap(), "s\u{f8}r en");
+
+        let params = matches("games/{id}", "games/three").unwrap();
+        assert!(matches!(params.get::<u32>("id"), Err(HttpError::BadRequest(_))));
+    }
+
+    #[test]
+    fn test_first_matching_route_wins()
+    {
+        let routes = [("games/export", 1), ("games/{id}", 2), ("games/{id}/moves", 3)];
+        assert_eq!(route(&routes, "games/export").unwrap().0, 1);
+        assert_eq!(route(&routes, "games/abc").unwrap().0, 2);
+        assert_eq!(route(&routes, "games/abc/moves").unwrap().0, 3);
+        assert!(route(&routes, "users").is_none());
+    }
+
+    #[derive(Deserialize)]
+    struct Page
+    {
+        state: Option<String>,
+        #[serde(default)]
+        limit: u32,
+    }
+
+    #[test]
+    fn test_query_is_typed()
+    {
+        let req = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+
+        let page: Page = query(&req("/api/games?state=complete&limit=10")).unwrap();
+        assert_eq!(page.state.as_deref(), Some("complete"));
+        assert_eq!(page.limit, 10);
+
+        let page: Page = query(&req("/api/games")).unwrap();
+        assert_eq!(page.state, None);
+        assert_eq!(page.limit, 0);
+
+        let page = query::<Page>(&req("/api/games?limit=ten"));
+        assert!(matches!(page, Err(HttpError::BadRequest(_))));
+    }
+}
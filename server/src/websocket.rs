@@ -7,7 +7,7 @@ use futures::{
 use tokio::{
     net::{TcpListener, TcpStream},
     select,
-    sync::mpsc,
+    sync::{broadcast, mpsc},
     time,
 };
 use tokio_tungstenite::{accept_hdr_async, WebSocketStream};
@@ -18,7 +18,7 @@ use tungstenite::{
 };
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Message
 {
     pub r#move: Move,
@@ -73,9 +73,9 @@ async fn get_websocket_and_uri(
     Ok((ws_stream, uri))
 }
 
-pub async fn spawn_web_socket_server(mut rx: mpsc::Receiver<Message>)
+pub async fn spawn_web_socket_server(mut rx: broadcast::Receiver<Message>, port: u16)
 {
-    let addr = "0.0.0.0:5001";
+    let addr = format!("0.0.0.0:{port}");
     let listener = TcpListener::bind(&addr).await.expect("Can't listen");
     println!("spawning websockert addr at {}", addr);
 
@@ -85,7 +85,7 @@ pub async fn spawn_web_socket_server(mut rx: mpsc::Receiver<Message>)
     {
         select! {
            msg = rx.recv() => {
-               if let Some(msg) = msg {
+               if let Ok(msg) = msg {
                 state.send_updates(msg.r#move).await;
                }
             },
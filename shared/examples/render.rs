@@ -0,0 +1,61 @@
+use std::{io, io::Read, process};
+
+use shared::{render, uhp::Position};
+
+
+const USAGE: &str = "usage: render [--svg] [game string]
+
+Draws the position after a UHP game string, read from stdin when not given.";
+
+fn main()
+{
+    let mut svg = false;
+    let mut game = None;
+    for arg in std::env::args().skip(1)
+    {
+        match arg.as_str()
+        {
+            "--svg" => svg = true,
+            "-h" | "--help" =>
+            {
+                println!("{USAGE}");
+                return;
+            },
+            _ => game = Some(arg),
+        }
+    }
+
+    let game = match game
+    {
+        Some(game) => game,
+        None =>
+        {
+            let mut s = String::new();
+            if let Err(e) = io::stdin().read_to_string(&mut s)
+            {
+                eprintln!("{e}");
+                process::exit(1);
+            }
+            s
+        },
+    };
+
+    let pos: Position = match game.trim().parse()
+    {
+        Ok(pos) => pos,
+        Err(e) =>
+        {
+            eprintln!("{e}\n\n{USAGE}");
+            process::exit(1);
+        },
+    };
+
+    if svg
+    {
+        println!("{}", render::svg::render(pos.board()));
+    }
+    else
+    {
+        print!("{}", render::ascii::render(pos.board()));
+    }
+}
@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use shared::model::Board;
+
+mod common;
+
+// Boards as the server reads them from a request body. Whatever deserializes
+// has to make it through the rules.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(board) = serde_json::from_slice::<Board>(data)
+    {
+        common::check_rules(&board);
+    }
+});
@@ -0,0 +1,27 @@
+use shared::{
+    engine::{generate_moves, PIECES},
+    model::*,
+    r#move::legal_moves,
+};
+
+// Runs the rules for every piece that could be placed or moved
+pub fn check_rules(board: &Board)
+{
+    board.is_complete();
+    generate_moves(board);
+
+    for color in [Color::White, Color::Black]
+    {
+        for (r#type, _) in PIECES
+        {
+            legal_moves(&Piece::new(r#type, color), board, None);
+        }
+    }
+    for (sq, bs) in board.iter()
+    {
+        if let Some(piece) = bs.top()
+        {
+            legal_moves(piece, board, Some(*sq));
+        }
+    }
+}
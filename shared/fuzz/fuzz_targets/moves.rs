@@ -0,0 +1,22 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use shared::model::{Board, InternalMove};
+
+mod common;
+
+// A board and moves to play on it. Moves that do not fit are refused, and
+// the board stays valid either way.
+fuzz_target!(|data: &[u8]| {
+    if let Ok((mut board, moves)) = serde_json::from_slice::<(Board, Vec<InternalMove>)>(data)
+    {
+        for r#move in moves
+        {
+            if board.play_move_(r#move).is_ok()
+            {
+                assert_eq!(board.validate(), Ok(()));
+                common::check_rules(&board);
+            }
+        }
+    }
+});
@@ -0,0 +1,239 @@
+use std::time::Instant;
+
+use super::*;
+
+// Nodes searched between looks at the clock, it is slow to read on some
+// platforms
+const CLOCK_INTERVAL: u64 = 1024;
+
+// Classical fixed depth negamax search with alpha-beta pruning, optionally
+// backed by a transposition table
+#[derive(Clone, Debug)]
+pub struct AlphaBeta
+{
+    pub depth:    u32,
+    pub weights:  Weights,
+    pub table:    Option<TranspositionTable>,
+    // The search gives up when this is passed, see `aborted`
+    pub deadline: Option<Instant>,
+
+    nodes:   u64,
+    aborted: bool,
+}
+
+impl AlphaBeta
+{
+    pub fn new(depth: u32) -> Self
+    {
+        Self {
+            depth,
+            weights: Weights::default(),
+            table: None,
+            deadline: None,
+            nodes: 0,
+            aborted: false,
+        }
+    }
+
+    // The last search ran out of time, its result is not to be used
+    pub fn aborted(&self) -> bool
+    {
+        self.aborted
+    }
+
+    fn negamax(
+        &mut self,
+        board: &Board,
+        depth: u32,
+        ply: u32,
+        mut alpha: Score,
+        beta: Score,
+        pv: &mut Vec<InternalMove>,
+    ) -> Score
+    {
+        self.nodes += 1;
+        pv.clear();
+
+        if self.nodes % CLOCK_INTERVAL == 0
+            && matches!(self.deadline, Some(deadline) if Instant::now() >= deadline)
+        {
+            self.aborted = true;
+        }
+        if self.aborted
+        {
+            return 0;
+        }
+
+        if depth == 0 || outcome(board).is_some()
+        {
+            return match evaluate(board, &self.weights)
+            {
+                WIN => WIN - ply as Score,
+                s if s == -WIN => -WIN + ply as Score,
+                s => s,
+            };
+        }
+
+        let key = board.hash_key();
+        let mut table_move = None;
+
+        if let Some(entry) = self.table.as_ref().and_then(|table| table.get(key))
+        {
+            // Only entries searched to exactly this depth are used for cutoffs,
+            // deeper entries would make the result of a fixed depth search
+            // depend on the move order
+            if ply > 0 && entry.depth == depth
+            {
+                let score = from_table(entry.score, ply);
+                let cutoff = match entry.bound
+                {
+                    Bound::Exact => true,
+                    Bound::Lower => score >= beta,
+                    Bound::Upper => score <= alpha,
+                };
+
+                if cutoff
+                {
+                    pv.extend(entry.best_move.clone());
+                    return score;
+                }
+            }
+            table_move = entry.best_move.clone();
+        }
+
+        let mut moves = generate_moves(board);
+        if moves.is_empty()
+        {
+            let mut child_pv = Vec::new();
+            return -self.negamax(&pass(board), depth - 1, ply + 1, -beta, -alpha, &mut child_pv);
+        }
+
+        // Search the best move from the table first
+        if let Some(i) = table_move.and_then(|m| moves.iter().position(|r#move| *r#move == m))
+        {
+            moves[..=i].rotate_right(1);
+        }
+
+        let original_alpha = alpha;
+        let mut best = -WIN - 1;
+        let mut child_pv = Vec::new();
+
+        for r#move in moves
+        {
+            let child = apply(board, &r#move);
+            let score = -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha, &mut child_pv);
+            if self.aborted
+            {
+                return 0;
+            }
+
+            if score > best
+            {
+                best = score;
+                pv.clear();
+                pv.push(r#move);
+                pv.append(&mut child_pv);
+            }
+
+            alpha = alpha.max(score);
+            if alpha >= beta
+            {
+                break;
+            }
+        }
+
+        if let Some(table) = self.table.as_mut()
+        {
+            let bound = match best
+            {
+                s if s <= original_alpha => Bound::Upper,
+                s if s >= beta => Bound::Lower,
+                _ => Bound::Exact,
+            };
+
+            table.insert(Entry {
+                key,
+                depth,
+                score: to_table(best, ply),
+                bound,
+                best_move: pv.first().cloned(),
+            });
+        }
+
+        best
+    }
+}
+
+impl Engine for AlphaBeta
+{
+    fn search(&mut self, board: &Board) -> SearchResult
+    {
+        self.nodes = 0;
+        self.aborted = false;
+
+        let mut pv = Vec::new();
+        let score = self.negamax(board, self.depth, 0, -WIN - 1, WIN + 1, &mut pv);
+
+        SearchResult {
+            best_move: pv.first().cloned(),
+            score,
+            pv,
+            nodes: self.nodes,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::engine::test::queen_surround_in_one;
+
+    #[test]
+    fn test_finds_queen_surround()
+    {
+        let board = queen_surround_in_one();
+        let res = AlphaBeta::new(2).search(&board);
+
+        let best = res.best_move.unwrap();
+        assert_eq!(best.sq, (0, 1, -1));
+        assert_eq!(res.score, WIN - 1);
+        assert_eq!(res.pv.len(), 1);
+    }
+
+    #[test]
+    fn test_pv_is_playable()
+    {
+        let mut board = Board::default();
+        board
+            .place_piece(Piece::new(BoardPiece::Queen, Color::White), (0, 0, 0), None)
+            .unwrap();
+        board
+            .place_piece(Piece::new(BoardPiece::Queen, Color::Black), (1, -1, 0), None)
+            .unwrap();
+
+        let res = AlphaBeta::new(2).search(&board);
+        assert_eq!(res.pv.len(), 2);
+
+        for r#move in res.pv
+        {
+            assert!(generate_moves(&board).contains(&r#move));
+            board = apply(&board, &r#move);
+        }
+    }
+    #[test]
+    fn test_deadline_aborts()
+    {
+        let board = queen_surround_in_one();
+
+        let mut engine = AlphaBeta::new(3);
+        engine.deadline = Some(Instant::now());
+        engine.search(&board);
+        assert!(engine.aborted());
+
+        engine.deadline = None;
+        assert_eq!(engine.search(&board).score, WIN - 1);
+        assert!(!engine.aborted());
+    }
+}
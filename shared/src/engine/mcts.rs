@@ -0,0 +1,297 @@
+use std::time::Instant;
+
+use super::*;
+
+// How a freshly expanded node is scored
+#[derive(Clone, Copy, Debug)]
+pub enum Playout
+{
+    // Play random moves until the game ends or `max_moves` have been played,
+    // unfinished games are scored with the static evaluation
+    Random
+    {
+        max_moves: usize,
+    },
+    // Score the node with the static evaluation straight away
+    Evaluation,
+}
+
+// Monte Carlo tree search using UCT for the selection step
+#[derive(Clone, Debug)]
+pub struct Mcts
+{
+    pub iterations:  u32,
+    pub exploration: f64,
+    pub playout:     Playout,
+    pub weights:     Weights,
+    pub seed:        u64,
+    // Stops early when this is passed, the result is the best one so far
+    pub deadline:    Option<Instant>,
+
+    visits: Vec<(InternalMove, u32)>,
+}
+
+struct Node
+{
+    r#move:   Option<InternalMove>,
+    parent:   Option<usize>,
+    children: Vec<usize>,
+    // `None` is a pass, only used when there are no legal moves
+    untried:  Vec<Option<InternalMove>>,
+    board:    Board,
+    // The player who made the move leading to this node, the rewards are
+    // from this players point of view
+    mover:    Color,
+    visits:   u32,
+    reward:   f64,
+}
+
+impl Node
+{
+    fn new(board: Board, r#move: Option<InternalMove>, parent: Option<usize>) -> Self
+    {
+        let untried = match (outcome(&board), generate_moves(&board))
+        {
+            (Some(_), _) => Vec::new(),
+            (None, moves) if moves.is_empty() => vec![None],
+            (None, moves) => moves.into_iter().map(Some).collect(),
+        };
+
+        Self {
+            r#move,
+            parent,
+            children: Vec::new(),
+            untried,
+            mover: side_to_move(&board).opposite(),
+            board,
+            visits: 0,
+            reward: 0.,
+        }
+    }
+
+    fn uct(&self, parent_visits: u32, exploration: f64) -> f64
+    {
+        let n = self.visits as f64;
+        self.reward / n + exploration * ((parent_visits as f64).ln() / n).sqrt()
+    }
+}
+
+// Squash a score into the probability of winning
+fn win_probability(score: Score) -> f64
+{
+    1. / (1. + (-score as f64 / 400.).exp())
+}
+
+fn to_score(p: f64) -> Score
+{
+    let p = p.clamp(0.001, 0.999);
+    (-400. * (1. / p - 1.).ln()) as Score
+}
+
+impl Mcts
+{
+    pub fn new(iterations: u32, seed: u64) -> Self
+    {
+        Self {
+            iterations,
+            exploration: std::f64::consts::SQRT_2,
+            playout: Playout::Evaluation,
+            weights: Weights::default(),
+            seed,
+            deadline: None,
+            visits: Vec::new(),
+        }
+    }
+
+    // Visit count of every root move from the last search
+    pub fn visit_counts(&self) -> &[(InternalMove, u32)]
+    {
+        &self.visits
+    }
+
+    // Result of the playout from White's point of view
+    fn playout(&self, board: &Board, rng: &mut Rng) -> f64
+    {
+        let white = |board: &Board, score: Score| match side_to_move(board)
+        {
+            Color::White => win_probability(score),
+            Color::Black => 1. - win_probability(score),
+        };
+
+        match self.playout
+        {
+            Playout::Evaluation => white(board, evaluate(board, &self.weights)),
+            Playout::Random {
+                max_moves,
+            } =>
+            {
+                let mut board = board.clone();
+                for _ in 0..max_moves
+                {
+                    if outcome(&board).is_some()
+                    {
+                        break;
+                    }
+
+                    let moves = generate_moves(&board);
+                    board = match moves.len()
+                    {
+                        0 => pass(&board),
+                        n => apply(&board, &moves[rng.below(n)]),
+                    };
+                }
+                white(&board, evaluate(&board, &self.weights))
+            },
+        }
+    }
+
+    fn select(&self, tree: &[Node]) -> usize
+    {
+        let mut idx = 0;
+        while tree[idx].untried.is_empty() && !tree[idx].children.is_empty()
+        {
+            let parent_visits = tree[idx].visits;
+            idx = *tree[idx]
+                .children
+                .iter()
+                .max_by(|a, b| {
+                    let a = tree[**a].uct(parent_visits, self.exploration);
+                    let b = tree[**b].uct(parent_visits, self.exploration);
+                    a.total_cmp(&b)
+                })
+                .unwrap();
+        }
+        idx
+    }
+
+    fn most_visited(tree: &[Node], idx: usize) -> Option<usize>
+    {
+        // Ties go to the first child so the result stays reproducible
+        tree[idx].children.iter().copied().rev().max_by_key(|c| tree[*c].visits)
+    }
+}
+
+impl Engine for Mcts
+{
+    fn search(&mut self, board: &Board) -> SearchResult
+    {
+        let mut rng = Rng::new(self.seed);
+        let mut tree = vec![Node::new(board.clone(), None, None)];
+
+        for i in 0..self.iterations
+        {
+            // Only every so often, the clock is slow to read on some platforms
+            if i % 64 == 63 && matches!(self.deadline, Some(deadline) if Instant::now() >= deadline)
+            {
+                break;
+            }
+
+            let mut idx = self.select(&tree);
+
+            if !tree[idx].untried.is_empty()
+            {
+                let n = tree[idx].untried.len();
+                let r#move = tree[idx].untried.swap_remove(rng.below(n));
+                let board = match &r#move
+                {
+                    Some(r#move) => apply(&tree[idx].board, r#move),
+                    None => pass(&tree[idx].board),
+                };
+
+                tree.push(Node::new(board, r#move, Some(idx)));
+                let child = tree.len() - 1;
+                tree[idx].children.push(child);
+                idx = child;
+            }
+
+            let white = self.playout(&tree[idx].board, &mut rng);
+
+            let mut node = Some(idx);
+            while let Some(idx) = node
+            {
+                let n = &mut tree[idx];
+                n.visits += 1;
+                n.reward += match n.mover
+                {
+                    Color::White => white,
+                    Color::Black => 1. - white,
+                };
+                node = n.parent;
+            }
+        }
+
+        self.visits = tree[0]
+            .children
+            .iter()
+            .filter_map(|c| tree[*c].r#move.clone().map(|m| (m, tree[*c].visits)))
+            .collect();
+
+        let mut pv = Vec::new();
+        let mut idx = 0;
+        while let Some(child) = Self::most_visited(&tree, idx)
+        {
+            match &tree[child].r#move
+            {
+                Some(r#move) => pv.push(r#move.clone()),
+                None => break,
+            }
+            idx = child;
+        }
+
+        let score = match Self::most_visited(&tree, 0)
+        {
+            Some(c) => to_score(tree[c].reward / tree[c].visits as f64),
+            None => evaluate(board, &self.weights),
+        };
+
+        SearchResult {
+            best_move: pv.first().cloned(),
+            score,
+            pv,
+            nodes: tree[0].visits as u64,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::engine::test::queen_surround_in_one;
+
+    #[test]
+    fn test_finds_queen_surround()
+    {
+        let board = queen_surround_in_one();
+        let mut mcts = Mcts::new(400, 7);
+        let res = mcts.search(&board);
+
+        assert_eq!(res.best_move.unwrap().sq, (0, 1, -1));
+        assert_eq!(res.nodes, 400);
+        assert_eq!(mcts.visit_counts().iter().map(|(_, v)| *v as u64).sum::<u64>(), 400);
+    }
+
+    #[test]
+    fn test_same_seed_same_search()
+    {
+        let mut board = Board::default();
+        board
+            .place_piece(Piece::new(BoardPiece::Queen, Color::White), (0, 0, 0), None)
+            .unwrap();
+        board
+            .place_piece(Piece::new(BoardPiece::Queen, Color::Black), (1, -1, 0), None)
+            .unwrap();
+
+        let search = |seed| {
+            let mut mcts = Mcts::new(100, seed);
+            mcts.playout = Playout::Random {
+                max_moves: 4
+            };
+            let res = mcts.search(&board);
+            (res.pv, mcts.visit_counts().to_vec())
+        };
+
+        assert_eq!(search(42), search(42));
+    }
+}
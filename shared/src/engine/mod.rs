@@ -0,0 +1,404 @@
+use crate::{
+    model::*,
+    r#move::{legal_moves, neighbors},
+};
+
+mod alpha_beta;
+pub use alpha_beta::*;
+
+mod mcts;
+pub use mcts::*;
+
+mod puzzle;
+pub use puzzle::*;
+
+mod review;
+pub use review::*;
+
+mod rng;
+pub use rng::*;
+
+mod symmetry;
+pub use symmetry::*;
+
+mod transposition;
+pub use transposition::*;
+
+
+pub type Score = i32;
+
+// Score of a surrounded queen, the distance to the win is subtracted so
+// faster wins are preferred
+pub const WIN: Score = 1_000_000;
+
+// Every piece a player starts the game with
+pub const PIECES: [(BoardPiece, usize); 5] = [
+    (BoardPiece::Queen, 1),
+    (BoardPiece::Ant, 3),
+    (BoardPiece::Grasshopper, 3),
+    (BoardPiece::Spider, 2),
+    (BoardPiece::Beetle, 2),
+];
+
+
+#[derive(Clone, Debug, Default)]
+pub struct SearchResult
+{
+    pub best_move: Option<InternalMove>,
+    pub score:     Score,
+    pub pv:        Vec<InternalMove>,
+    pub nodes:     u64,
+}
+
+// Everything that can pick a move for the side to move
+pub trait Engine
+{
+    fn search(&mut self, board: &Board) -> SearchResult;
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome
+{
+    Win(Color),
+    Draw,
+}
+
+impl From<Outcome> for GameResult
+{
+    fn from(outcome: Outcome) -> Self
+    {
+        match outcome
+        {
+            Outcome::Win(Color::White) => GameResult::White,
+            Outcome::Win(Color::Black) => GameResult::Black,
+            Outcome::Draw => GameResult::Draw,
+        }
+    }
+}
+
+impl From<GameResult> for Outcome
+{
+    fn from(result: GameResult) -> Self
+    {
+        match result
+        {
+            GameResult::White => Outcome::Win(Color::White),
+            GameResult::Black => Outcome::Win(Color::Black),
+            GameResult::Draw => Outcome::Draw,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Weights
+{
+    // Per piece next to a queen, counted positive around the enemy queen
+    pub queen_neighbor: Score,
+    // Per legal move, left at 0 by default since it doubles the move
+    // generation at every leaf
+    pub mobility:       Score,
+}
+
+impl Default for Weights
+{
+    fn default() -> Self
+    {
+        Self {
+            queen_neighbor: 100, mobility: 0
+        }
+    }
+}
+
+
+pub fn side_to_move(board: &Board) -> Color
+{
+    match board.turns % 2
+    {
+        0 => Color::White,
+        _ => Color::Black,
+    }
+}
+
+pub fn pieces_in_hand(board: &Board, color: Color) -> Vec<BoardPiece>
+{
+    PIECES
+        .into_iter()
+        .filter(|(r#type, count)| {
+            let placed = board
+                .values()
+                .flat_map(|bs| bs.pieces.iter())
+                .filter(|p| p.color == color && p.r#type == *r#type)
+                .count();
+            placed < *count
+        })
+        .map(|(r#type, _)| r#type)
+        .collect()
+}
+
+/*
+ * All the moves the side to move can play, in a deterministic order.
+ * An empty list means the player has to pass, unless the game is over.
+ */
+pub fn generate_moves(board: &Board) -> Vec<InternalMove>
+{
+    if board.is_complete()
+    {
+        return Vec::new();
+    }
+
+    let color = side_to_move(board);
+    let mut moves = Vec::new();
+
+    for r#type in pieces_in_hand(board, color)
+    {
+        let piece = Piece::new(r#type, color);
+        moves.extend(legal_moves(&piece, board, None).into_iter().map(|sq| InternalMove {
+            piece,
+            sq,
+            old_sq: None,
+        }));
+    }
+
+    // Pieces on the board can not move before the queen is placed
+    if board.queens[color as usize].is_some()
+    {
+        for (&old_sq, &piece) in board
+            .iter()
+            .filter_map(|(sq, bs)| bs.top().map(|p| (sq, p)))
+            .filter(|(_, p)| p.color == color)
+        {
+            moves.extend(legal_moves(&piece, board, Some(old_sq)).into_iter().map(|sq| {
+                InternalMove {
+                    piece,
+                    sq,
+                    old_sq: Some(old_sq),
+                }
+            }));
+        }
+    }
+
+    moves.sort_by_key(|m| (m.old_sq, m.sq, m.piece.r#type as u8));
+    moves.dedup();
+    moves
+}
+
+// Only for moves from generate_moves, which always fit the board
+pub fn apply(board: &Board, r#move: &InternalMove) -> Board
+{
+    let mut board = board.clone();
+    board.play_move_(r#move.clone()).expect("generated moves can be played");
+    board
+}
+
+pub fn pass(board: &Board) -> Board
+{
+    let mut board = board.clone();
+    board.turns += 1;
+    board
+}
+
+// A pass is only allowed to a side without any move in a game still going
+pub fn is_legal_turn(board: &Board, r#move: &Option<InternalMove>) -> bool
+{
+    let moves = generate_moves(board);
+    match r#move
+    {
+        Some(r#move) => moves.contains(r#move),
+        None => moves.is_empty() && !board.is_complete(),
+    }
+}
+
+// A turn from a game's move list, None is a pass
+pub fn play_turn(board: &Board, r#move: &Option<InternalMove>) -> Board
+{
+    match r#move
+    {
+        Some(r#move) => apply(board, r#move),
+        None => pass(board),
+    }
+}
+
+fn queen_neighbors(board: &Board, color: Color) -> Option<usize>
+{
+    board.queens[color as usize]
+        .map(|sq| neighbors(&sq).into_iter().filter(|sq| board.contains_key(sq)).count())
+}
+
+pub fn outcome(board: &Board) -> Option<Outcome>
+{
+    let surrounded = |color| queen_neighbors(board, color) == Some(6);
+
+    match (surrounded(Color::White), surrounded(Color::Black))
+    {
+        (true, true) => Some(Outcome::Draw),
+        (true, false) => Some(Outcome::Win(Color::Black)),
+        (false, true) => Some(Outcome::Win(Color::White)),
+        _ => None,
+    }
+}
+
+// Static evaluation from the point of view of the side to move
+pub fn evaluate(board: &Board, weights: &Weights) -> Score
+{
+    let color = side_to_move(board);
+
+    match outcome(board)
+    {
+        Some(Outcome::Win(c)) if c == color => WIN,
+        Some(Outcome::Win(_)) => -WIN,
+        Some(Outcome::Draw) => 0,
+        None =>
+        {
+            let pressure = |color| queen_neighbors(board, color).unwrap_or(0) as Score;
+            let mut score = weights.queen_neighbor * (pressure(color.opposite()) - pressure(color));
+
+            if weights.mobility != 0
+            {
+                let mine = generate_moves(board).len() as Score;
+                let theirs = generate_moves(&pass(board)).len() as Score;
+                score += weights.mobility * (mine - theirs);
+            }
+            score
+        },
+    }
+}
+
+
+#[cfg(test)]
+pub(crate) mod test
+{
+    use super::*;
+
+    // Black queen surrounded on five sides, white ant can close the last gap
+    // at (0, 1, -1)
+    pub fn queen_surround_in_one() -> Board
+    {
+        let mut board = Board::default();
+        let pos = [
+            ((0, 0, 0), Piece::new(BoardPiece::Queen, Color::Black)),
+            ((1, -1, 0), Piece::new(BoardPiece::Spider, Color::Black)),
+            ((0, -1, 1), Piece::new(BoardPiece::Ant, Color::Black)),
+            ((-1, 0, 1), Piece::new(BoardPiece::Beetle, Color::Black)),
+            ((-1, 1, 0), Piece::new(BoardPiece::Queen, Color::White)),
+            ((1, 0, -1), Piece::new(BoardPiece::Grasshopper, Color::White)),
+            ((2, -1, -1), Piece::new(BoardPiece::Ant, Color::White)),
+        ];
+
+        for (sq, piece) in pos
+        {
+            board.place_piece(piece, sq, None).unwrap();
+        }
+        board.turns = 8;
+        board
+    }
+
+    pub fn place(r#type: BoardPiece, color: Color, sq: Square) -> InternalMove
+    {
+        InternalMove {
+            piece: Piece::new(r#type, color),
+            sq,
+            old_sq: None,
+        }
+    }
+
+    // Reaches `queen_surround_in_one` moved one step, since the first piece
+    // goes in the middle, with an extra white spider out of the way. Then
+    // white looks away.
+    pub fn missed_queen_surround() -> Vec<Option<InternalMove>>
+    {
+        use BoardPiece::*;
+        use Color::*;
+
+        [
+            place(Queen, White, (0, 0, 0)),
+            place(Queen, Black, (1, -1, 0)),
+            place(Grasshopper, White, (2, -1, -1)),
+            place(Spider, Black, (2, -2, 0)),
+            place(Ant, White, (3, -2, -1)),
+            place(Ant, Black, (1, -2, 1)),
+            place(Spider, White, (-1, 1, 0)),
+            place(Beetle, Black, (0, -1, 1)),
+            place(Beetle, White, (-2, 2, 0)),
+        ]
+        .into_iter()
+        .map(Some)
+        .collect()
+    }
+
+    // Some legal game, long enough for pieces to move and climb
+    pub fn legal_game(plies: usize) -> Vec<Option<InternalMove>>
+    {
+        let mut board = Board::default();
+        let mut moves = Vec::new();
+        for i in 0..plies
+        {
+            let legal = generate_moves(&board);
+            if legal.is_empty() || board.is_complete()
+            {
+                break;
+            }
+            let r#move = legal[i * 7 % legal.len()].clone();
+            board = apply(&board, &r#move);
+            moves.push(Some(r#move));
+        }
+        moves
+    }
+
+    #[test]
+    fn test_first_moves()
+    {
+        let board = Board::default();
+        let moves = generate_moves(&board);
+
+        assert_eq!(moves.len(), PIECES.len());
+        assert!(moves.iter().all(|m| m.sq == (0, 0, 0) && m.piece.color == Color::White));
+
+        let board = apply(&board, &moves[0]);
+        let moves = generate_moves(&board);
+
+        assert_eq!(moves.len(), PIECES.len() * 6);
+        assert!(moves.iter().all(|m| m.piece.color == Color::Black));
+    }
+
+    #[test]
+    fn test_no_movement_before_queen()
+    {
+        let mut board = Board::default();
+        board
+            .place_piece(Piece::new(BoardPiece::Ant, Color::White), (0, 0, 0), None)
+            .unwrap();
+        board
+            .place_piece(Piece::new(BoardPiece::Ant, Color::Black), (1, -1, 0), None)
+            .unwrap();
+
+        assert!(generate_moves(&board).iter().all(|m| m.old_sq.is_none()));
+
+        board
+            .place_piece(Piece::new(BoardPiece::Queen, Color::White), (-1, 1, 0), None)
+            .unwrap();
+        board
+            .place_piece(Piece::new(BoardPiece::Queen, Color::Black), (2, -2, 0), None)
+            .unwrap();
+
+        assert!(generate_moves(&board).iter().any(|m| m.old_sq.is_some()));
+    }
+
+    #[test]
+    fn test_outcome()
+    {
+        let board = queen_surround_in_one();
+        assert_eq!(outcome(&board), None);
+
+        let r#move = InternalMove {
+            piece:  Piece::new(BoardPiece::Ant, Color::White),
+            sq:     (0, 1, -1),
+            old_sq: Some((2, -1, -1)),
+        };
+        assert!(generate_moves(&board).contains(&r#move));
+
+        let board = apply(&board, &r#move);
+        assert_eq!(outcome(&board), Some(Outcome::Win(Color::White)));
+        assert_eq!(evaluate(&board, &Weights::default()), -WIN);
+    }
+}
@@ -0,0 +1,203 @@
+use super::*;
+
+
+// Every position the side to move can reach, a pass when it has no moves
+fn children(board: &Board) -> Vec<Board>
+{
+    let moves = generate_moves(board);
+    if moves.is_empty()
+    {
+        return vec![pass(board)];
+    }
+    moves.iter().map(|r#move| apply(board, r#move)).collect()
+}
+
+/*
+ * Whether the side to move can surround the enemy queen within `n` of its
+ * own moves whatever the other side answers. Searches every line, so only
+ * small `n` are practical.
+ */
+pub fn wins_within(board: &Board, n: u32) -> bool
+{
+    if n == 0 || outcome(board).is_some()
+    {
+        return false;
+    }
+
+    let color = side_to_move(board);
+    children(board).iter().any(|after| wins_after(after, color, n))
+}
+
+// After a move by `color`: either it won, or every answer still loses
+fn wins_after(after: &Board, color: Color, n: u32) -> bool
+{
+    match outcome(after)
+    {
+        Some(Outcome::Win(c)) => c == color,
+        Some(Outcome::Draw) => false,
+        None => n > 1 && children(after).iter().all(|board| wins_within(board, n - 1)),
+    }
+}
+
+/*
+ * Finds the first position in a game where the side to move could force a
+ * queen surround within `max` moves. The engine finds the candidates, and
+ * they are kept only if the full search of every defence agrees.
+ * Returns the number of moves before the position and the length of the win.
+ */
+pub fn find_puzzle(moves: &[Option<InternalMove>], max: u32) -> Option<(usize, u32)>
+{
+    let plies = 2 * max.max(1) - 1;
+    let mut engine = AlphaBeta::new(plies);
+    engine.table = Some(TranspositionTable::new(1 << 16));
+
+    let mut board = Board::default();
+    for (i, r#move) in moves.iter().enumerate()
+    {
+        if outcome(&board).is_some()
+        {
+            break;
+        }
+
+        // Every move adds at most one piece around the queen
+        let pressure = queen_neighbors(&board, side_to_move(&board).opposite()).unwrap_or(0);
+        if pressure + max as usize >= 6
+        {
+            let res = engine.search(&board);
+            if res.score >= WIN - plies as Score
+            {
+                let n = (WIN - res.score + 1) as u32 / 2;
+                if wins_within(&board, n)
+                {
+                    return Some((i, n));
+                }
+            }
+        }
+        board = play_turn(&board, r#move);
+    }
+    None
+}
+
+/*
+ * Checks a move in a position where the side to move should win within `n`
+ * moves. A move that keeps the win is answered with the defence lasting the
+ * longest.
+ */
+pub fn check_answer(board: &Board, r#move: &InternalMove, n: u32) -> PuzzleAnswer
+{
+    if !generate_moves(board).contains(r#move)
+    {
+        return PuzzleAnswer::Wrong;
+    }
+
+    let color = side_to_move(board);
+    let after = apply(board, r#move);
+    if outcome(&after) == Some(Outcome::Win(color))
+    {
+        return PuzzleAnswer::Solved;
+    }
+    if !wins_after(&after, color, n)
+    {
+        return PuzzleAnswer::Wrong;
+    }
+
+    let mut engine = AlphaBeta::new(2 * n - 2);
+    PuzzleAnswer::Reply(engine.search(&after).best_move)
+}
+
+/*
+ * Checks the next move of a puzzle that is partly solved. `played` is every
+ * move of the solver so far, each followed by the reply `check_answer` gave
+ * to it. None if it is not, so the solver can not pick easier replies.
+ */
+pub fn check_line(
+    board: &Board,
+    played: &[Option<InternalMove>],
+    r#move: &InternalMove,
+    n: u32,
+) -> Option<PuzzleAnswer>
+{
+    let mut board = board.clone();
+    let mut n = n;
+    for pair in played.chunks(2)
+    {
+        let (mine, reply) = match pair
+        {
+            [Some(mine), reply] => (mine, reply),
+            _ => return None,
+        };
+        if check_answer(&board, mine, n) != PuzzleAnswer::Reply(reply.clone())
+        {
+            return None;
+        }
+
+        let after = apply(&board, mine);
+        board = match reply
+        {
+            Some(reply) => apply(&after, reply),
+            None => pass(&after),
+        };
+        // A reply is only given while there are moves left after it
+        n -= 1;
+    }
+    Some(check_answer(&board, r#move, n))
+}
+
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::engine::test::*;
+
+    #[test]
+    fn test_surround_in_one()
+    {
+        let board = queen_surround_in_one();
+        assert!(wins_within(&board, 1));
+        assert!(!wins_within(&pass(&board), 1));
+
+        let win = InternalMove {
+            piece:  Piece::new(BoardPiece::Ant, Color::White),
+            sq:     (0, 1, -1),
+            old_sq: Some((2, -1, -1)),
+        };
+        assert_eq!(check_answer(&board, &win, 1), PuzzleAnswer::Solved);
+
+        let other = generate_moves(&board).into_iter().find(|m| m.sq != (0, 1, -1)).unwrap();
+        assert_eq!(check_answer(&board, &other, 1), PuzzleAnswer::Wrong);
+    }
+
+    #[test]
+    fn test_line_is_checked()
+    {
+        let board = queen_surround_in_one();
+        let win = InternalMove {
+            piece:  Piece::new(BoardPiece::Ant, Color::White),
+            sq:     (0, 1, -1),
+            old_sq: Some((2, -1, -1)),
+        };
+        let other = generate_moves(&board).into_iter().find(|m| m.sq != (0, 1, -1)).unwrap();
+
+        assert_eq!(check_line(&board, &[], &win, 1), Some(PuzzleAnswer::Solved));
+        assert_eq!(check_line(&board, &[], &other, 1), Some(PuzzleAnswer::Wrong));
+
+        // Every move of the solver has to be answered, and by the reply the
+        // check gave, which a move that loses the win never gets
+        assert_eq!(check_line(&board, &[Some(other.clone())], &win, 2), None);
+        let after = apply(&board, &other);
+        for reply in generate_moves(&after).into_iter().take(3)
+        {
+            assert_eq!(check_line(&board, &[Some(other.clone()), Some(reply)], &win, 2), None);
+        }
+        assert_eq!(check_line(&board, &[None, None], &win, 2), None);
+    }
+
+    #[test]
+    fn test_find_puzzle()
+    {
+        let moves = missed_queen_surround();
+        assert_eq!(find_puzzle(&moves, 1), Some((8, 1)));
+        assert_eq!(find_puzzle(&moves[..8], 1), None);
+    }
+}
@@ -0,0 +1,116 @@
+use super::*;
+
+// Losing this much compared to the best move is a blunder, three more pieces
+// around the queen
+pub const BLUNDER: Score = 300;
+
+fn is_win(score: Score) -> bool
+{
+    score > WIN / 2
+}
+
+/*
+ * A move that still wins, even if slower than the best one, is not a missed
+ * win.
+ */
+fn annotate(best: Score, played: Score) -> Option<Annotation>
+{
+    if is_win(best) && !is_win(played)
+    {
+        Some(Annotation::MissedWin)
+    }
+    else if !is_win(best) && best - played >= BLUNDER
+    {
+        Some(Annotation::Blunder)
+    }
+    else
+    {
+        None
+    }
+}
+
+/*
+ * Score every move of a game against the engine's choice, searching both to
+ * the same depth. A pass, None, is the only move there was.
+ */
+pub fn review(moves: &[Option<InternalMove>], depth: u32) -> Review
+{
+    let depth = depth.max(1);
+    let mut engine = AlphaBeta::new(depth);
+    engine.table = Some(TranspositionTable::new(1 << 16));
+
+    let mut board = Board::default();
+    let mut reviews = Vec::with_capacity(moves.len());
+
+    for r#move in moves
+    {
+        engine.depth = depth;
+        let best = engine.search(&board);
+
+        let after = play_turn(&board, r#move);
+        engine.depth = depth - 1;
+        // The reply is searched from one ply further, count its wins and
+        // losses from here
+        let played = match -engine.search(&after).score
+        {
+            s if is_win(s) => s - 1,
+            s if is_win(-s) => s + 1,
+            s => s,
+        };
+
+        let annotation = annotate(best.score, played);
+
+        let score = match side_to_move(&board)
+        {
+            Color::White => played,
+            Color::Black => -played,
+        };
+
+        reviews.push(MoveReview {
+            score,
+            best_move: best.best_move,
+            annotation,
+        });
+        board = after;
+    }
+
+    Review {
+        depth,
+        moves: reviews,
+    }
+}
+
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::engine::test::missed_queen_surround;
+
+    #[test]
+    fn test_missed_queen_surround()
+    {
+        let moves = missed_queen_surround();
+        let review = review(&moves, 2);
+        assert_eq!(review.moves.len(), moves.len());
+
+        let last = review.moves.last().unwrap();
+        assert_eq!(last.annotation, Some(Annotation::MissedWin));
+        assert_eq!(last.best_move.as_ref().unwrap().sq, (1, 0, -1));
+
+        // Black closing the fifth side lets white surround the queen
+        assert_eq!(review.moves[7].annotation, Some(Annotation::Blunder));
+        assert!(is_win(review.moves[7].score));
+        assert!(review.moves[..7].iter().all(|m| m.annotation.is_none()));
+    }
+
+    #[test]
+    fn test_slower_win_is_not_missed()
+    {
+        assert_eq!(annotate(WIN - 1, WIN - 3), None);
+        assert_eq!(annotate(WIN - 1, 0), Some(Annotation::MissedWin));
+        assert_eq!(annotate(WIN - 1, -WIN + 2), Some(Annotation::MissedWin));
+        assert_eq!(annotate(0, -BLUNDER), Some(Annotation::Blunder));
+        assert_eq!(annotate(0, -BLUNDER + 1), None);
+    }
+}
@@ -0,0 +1,33 @@
+// Small splitmix64 generator. The engines only need cheap, reproducible
+// randomness, and this keeps the crate free of platform specific entropy
+// sources (the frontend compiles this crate to wasm).
+#[derive(Clone, Debug)]
+pub struct Rng
+{
+    state: u64,
+}
+
+impl Rng
+{
+    pub fn new(seed: u64) -> Self
+    {
+        Self {
+            state: seed
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64
+    {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in 0..n, n has to be non-zero
+    pub fn below(&mut self, n: usize) -> usize
+    {
+        (self.next_u64() % n as u64) as usize
+    }
+}
@@ -0,0 +1,193 @@
+use super::*;
+
+// One of the 12 rotations and reflections of the grid, followed by a
+// translation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Symmetry
+{
+    rotation: u8,
+    reflect:  bool,
+    offset:   Square,
+}
+
+fn rotate((q, r, s): Square) -> Square
+{
+    (-r, -s, -q)
+}
+
+fn reflect((q, r, s): Square) -> Square
+{
+    (q, s, r)
+}
+
+impl Symmetry
+{
+    fn turn(&self, sq: Square) -> Square
+    {
+        let sq = if self.reflect { reflect(sq) } else { sq };
+        (0..self.rotation).fold(sq, |sq, _| rotate(sq))
+    }
+
+    pub fn apply(&self, sq: Square) -> Square
+    {
+        let (q, r, s) = self.turn(sq);
+        (q + self.offset.0, r + self.offset.1, s + self.offset.2)
+    }
+
+    pub fn invert(&self, (q, r, s): Square) -> Square
+    {
+        let sq = (q - self.offset.0, r - self.offset.1, s - self.offset.2);
+        let sq = (self.rotation..6).fold(sq, |sq, _| rotate(sq));
+        if self.reflect
+        {
+            reflect(sq)
+        }
+        else
+        {
+            sq
+        }
+    }
+
+    pub fn apply_move(&self, r#move: &InternalMove) -> InternalMove
+    {
+        InternalMove {
+            piece:  r#move.piece,
+            sq:     self.apply(r#move.sq),
+            old_sq: r#move.old_sq.map(|sq| self.apply(sq)),
+        }
+    }
+
+    pub fn invert_move(&self, r#move: &InternalMove) -> InternalMove
+    {
+        InternalMove {
+            piece:  r#move.piece,
+            sq:     self.invert(r#move.sq),
+            old_sq: r#move.old_sq.map(|sq| self.invert(sq)),
+        }
+    }
+
+    pub fn apply_board(&self, board: &Board) -> Board
+    {
+        let mut res = Board::default();
+        for (sq, bs) in board.iter()
+        {
+            res.insert(self.apply(*sq), bs.clone());
+        }
+        res.queens = board.queens.map(|queen| queen.map(|sq| self.apply(sq)));
+        res.turns = board.turns;
+        res
+    }
+}
+
+/*
+ * A key that is the same for every rotation, reflection and translation of
+ * the position, and the symmetry taking the board to the position the key
+ * was made from. Moves stored under the key should go through the symmetry.
+ */
+pub fn canonical(board: &Board) -> (u64, Symmetry)
+{
+    frames(board).into_iter().min_by_key(|(key, _)| *key).unwrap()
+}
+
+/*
+ * The move from `board` in the frame of `canonical`. Moves that are the same
+ * up to a symmetry of the position, like the six places next to the first
+ * piece, get the same one, so the opening index counts them together.
+ */
+pub fn canonical_move(board: &Board, r#move: &InternalMove) -> (u64, InternalMove)
+{
+    let frames = frames(board);
+    let key = frames.iter().map(|(key, _)| *key).min().unwrap();
+    let r#move = frames
+        .iter()
+        .filter(|(k, _)| *k == key)
+        .map(|(_, sym)| sym.apply_move(r#move))
+        .min_by_key(|r#move| (r#move.sq, r#move.old_sq))
+        .unwrap();
+    (key, r#move)
+}
+
+// The key of the board in each of the 12 turns, moved to the middle
+fn frames(board: &Board) -> Vec<(u64, Symmetry)>
+{
+    (0..12)
+        .map(|i| {
+            let turn = Symmetry {
+                rotation: i % 6, reflect: i >= 6, offset: (0, 0, 0)
+            };
+
+            // The smallest square goes to the middle
+            let (q, r, s) = board.iter().map(|(sq, _)| turn.apply(*sq)).min().unwrap_or((0, 0, 0));
+            let sym = Symmetry {
+                offset: (-q, -r, -s),
+                ..turn
+            };
+            (sym.apply_board(board).hash_key(), sym)
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::engine::test::place;
+
+    #[test]
+    fn test_canonical_key()
+    {
+        let board = crate::engine::test::queen_surround_in_one();
+        let (key, sym) = canonical(&board);
+
+        for i in 0..12
+        {
+            let other = Symmetry {
+                rotation: i % 6, reflect: i >= 6, offset: (3, -1, -2)
+            };
+            let moved = other.apply_board(&board);
+            assert_eq!(canonical(&moved).0, key);
+
+            let sq = (2, -1, -1);
+            assert_eq!(other.invert(other.apply(sq)), sq);
+        }
+
+        // Moves keep their meaning through the symmetry
+        let r#move = generate_moves(&board).into_iter().next().unwrap();
+        let stored = sym.apply_move(&r#move);
+        assert!(generate_moves(&sym.apply_board(&board)).contains(&stored));
+        assert_eq!(sym.invert_move(&stored), r#move);
+
+        // The side to move is part of the position
+        assert_ne!(canonical(&pass(&board)).0, key);
+    }
+
+    #[test]
+    fn test_symmetric_moves_are_the_same()
+    {
+        let first = place(BoardPiece::Ant, Color::White, (0, 0, 0));
+        let board = apply(&Board::default(), &first);
+
+        let (key, r#move) = canonical_move(&Board::default(), &first);
+        assert_eq!(key, canonical(&Board::default()).0);
+        assert_eq!(r#move.sq, (0, 0, 0));
+
+        // Every place next to the first piece is the same move
+        let moves = generate_moves(&board);
+        assert_eq!(moves.len(), 6 * 5);
+        let stored: Vec<_> = moves.iter().map(|r#move| canonical_move(&board, r#move)).collect();
+        for (i, (key, r#move)) in stored.iter().enumerate()
+        {
+            assert_eq!(*key, canonical(&board).0);
+            assert_eq!(stored.iter().filter(|(_, other)| other == r#move).count(), 6, "{i}");
+        }
+
+        // Whatever frame the board is in
+        let other = Symmetry {
+            rotation: 2, reflect: true, offset: (1, 2, -3)
+        };
+        let turned = other.apply_board(&board);
+        let r#move = other.apply_move(&moves[0]);
+        assert_eq!(canonical_move(&turned, &r#move), stored[0]);
+    }
+}
@@ -0,0 +1,226 @@
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound
+{
+    Exact,
+    // The score is at least this good, the search failed high
+    Lower,
+    // The score is at most this good, the search failed low
+    Upper,
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry
+{
+    pub key:       u64,
+    pub depth:     u32,
+    pub score:     Score,
+    pub bound:     Bound,
+    pub best_move: Option<InternalMove>,
+}
+
+// Fixed size table of searched positions, keyed by `Board::hash_key`
+#[derive(Debug, Clone)]
+pub struct TranspositionTable
+{
+    entries: Vec<Option<Entry>>,
+}
+
+impl TranspositionTable
+{
+    pub fn new(size: usize) -> Self
+    {
+        Self {
+            entries: vec![None; size.max(1)]
+        }
+    }
+
+    fn index(&self, key: u64) -> usize
+    {
+        (key % self.entries.len() as u64) as usize
+    }
+
+    pub fn get(&self, key: u64) -> Option<&Entry>
+    {
+        self.entries[self.index(key)].as_ref().filter(|e| e.key == key)
+    }
+
+    // Depth preferred replacement, a slot is only overwritten by the same
+    // position or by a search that went at least as deep
+    pub fn insert(&mut self, entry: Entry)
+    {
+        let idx = self.index(entry.key);
+        let replace = match &self.entries[idx]
+        {
+            Some(old) => old.key == entry.key || entry.depth >= old.depth,
+            None => true,
+        };
+
+        if replace
+        {
+            self.entries[idx] = Some(entry);
+        }
+    }
+
+    pub fn clear(&mut self)
+    {
+        self.entries.iter_mut().for_each(|e| *e = None);
+    }
+}
+
+// Wins are stored relative to the position rather than the root, so they
+// stay correct when the position is reached at another ply
+const WIN_BOUND: Score = WIN - 1000;
+
+pub(crate) fn to_table(score: Score, ply: u32) -> Score
+{
+    match score
+    {
+        s if s >= WIN_BOUND => s + ply as Score,
+        s if s <= -WIN_BOUND => s - ply as Score,
+        s => s,
+    }
+}
+
+pub(crate) fn from_table(score: Score, ply: u32) -> Score
+{
+    match score
+    {
+        s if s >= WIN_BOUND => s - ply as Score,
+        s if s <= -WIN_BOUND => s + ply as Score,
+        s => s,
+    }
+}
+
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    fn entry(key: u64, depth: u32) -> Entry
+    {
+        Entry {
+            key,
+            depth,
+            score: 0,
+            bound: Bound::Exact,
+            best_move: None,
+        }
+    }
+
+    #[test]
+    fn test_depth_preferred_replacement()
+    {
+        let mut table = TranspositionTable::new(4);
+
+        table.insert(entry(1, 3));
+        table.insert(entry(5, 2));
+        assert_eq!(table.get(1).unwrap().depth, 3);
+        assert!(table.get(5).is_none());
+
+        table.insert(entry(5, 3));
+        assert!(table.get(1).is_none());
+        assert_eq!(table.get(5).unwrap().depth, 3);
+
+        table.insert(entry(5, 1));
+        assert_eq!(table.get(5).unwrap().depth, 1);
+    }
+
+    #[test]
+    fn test_hash_key_transposition()
+    {
+        let a = Piece::new(BoardPiece::Ant, Color::White);
+        let b = Piece::new(BoardPiece::Beetle, Color::Black);
+
+        let mut board1 = Board::default();
+        board1.place_piece(a, (0, 0, 0), None).unwrap();
+        board1.place_piece(b, (1, -1, 0), None).unwrap();
+
+        let mut board2 = Board::default();
+        board2.place_piece(b, (1, -1, 0), None).unwrap();
+        board2.place_piece(a, (0, 0, 0), None).unwrap();
+
+        assert_eq!(board1.hash_key(), board2.hash_key());
+        assert_ne!(board1.hash_key(), pass(&board1).hash_key());
+
+        // Same pieces, different stacking order
+        board1.place_piece(a, (2, -2, 0), None).unwrap();
+        board1.place_piece(b, (2, -2, 0), None).unwrap();
+        board2.place_piece(b, (2, -2, 0), None).unwrap();
+        board2.place_piece(a, (2, -2, 0), None).unwrap();
+        assert_ne!(board1.hash_key(), board2.hash_key());
+    }
+
+    #[test]
+    fn test_hash_key_far_out()
+    {
+        let a = Piece::new(BoardPiece::Ant, Color::White);
+        let key = |sq: Square| {
+            let mut board = Board::default();
+            board.place_piece(a, sq, None).unwrap();
+            board.hash_key()
+        };
+
+        assert_ne!(key((MAX_COORD, -MAX_COORD, 0)), key((-MAX_COORD, MAX_COORD, 0)));
+        assert_ne!(key((MAX_COORD, 0, -MAX_COORD)), key((-MAX_COORD, 0, MAX_COORD)));
+
+        let mut board = Board::default();
+        let off = (MAX_COORD + 1, -MAX_COORD - 1, 0);
+        assert_eq!(board.place_piece(a, off, None), Err(BoardError::OffGrid(off)));
+    }
+
+    #[test]
+    fn test_cached_search_is_the_same()
+    {
+        let mut board = Board::default();
+        board
+            .place_piece(Piece::new(BoardPiece::Queen, Color::White), (0, 0, 0), None)
+            .unwrap();
+        board
+            .place_piece(Piece::new(BoardPiece::Queen, Color::Black), (1, -1, 0), None)
+            .unwrap();
+        board
+            .place_piece(Piece::new(BoardPiece::Ant, Color::White), (-1, 1, 0), None)
+            .unwrap();
+        board
+            .place_piece(Piece::new(BoardPiece::Spider, Color::Black), (2, -2, 0), None)
+            .unwrap();
+
+        for depth in 1..=3
+        {
+            let uncached = AlphaBeta::new(depth).search(&board);
+
+            let mut engine = AlphaBeta::new(depth);
+            engine.table = Some(TranspositionTable::new(1 << 12));
+            let cached = engine.search(&board);
+
+            assert_eq!(cached.score, uncached.score);
+
+            // The best move may differ between equal moves, but it has to
+            // reach the same score
+            if depth > 1
+            {
+                let child = apply(&board, cached.best_move.as_ref().unwrap());
+                assert_eq!(-AlphaBeta::new(depth - 1).search(&child).score, cached.score);
+            }
+
+            // Searching again with a warm table gives the same result
+            assert_eq!(engine.search(&board).score, uncached.score);
+        }
+    }
+
+    #[test]
+    fn test_cached_search_finds_queen_surround()
+    {
+        let board = crate::engine::test::queen_surround_in_one();
+
+        let mut engine = AlphaBeta::new(3);
+        engine.table = Some(TranspositionTable::new(1 << 10));
+
+        let res = engine.search(&board);
+        assert_eq!(res.best_move.unwrap().sq, (0, 1, -1));
+        assert_eq!(res.score, WIN - 1);
+    }
+}
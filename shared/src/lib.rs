@@ -1,4 +1,9 @@
+pub mod engine;
 pub mod model;
 pub mod r#move;
+pub mod record;
+pub mod render;
+pub mod sgf;
+pub mod uhp;
 
 pub use bson::oid::ObjectId;
@@ -0,0 +1,28 @@
+use bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use crate::model::game::InternalMove;
+
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum PositionRequest
+{
+    // The position after the first `moves` moves of a game
+    Game
+    {
+        game:  ObjectId,
+        moves: usize,
+    },
+    // A UHP game string
+    Position(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Analysis
+{
+    // From white's point of view
+    pub score:     i32,
+    pub depth:     u32,
+    pub best_move: Option<InternalMove>,
+    pub pv:        Vec<InternalMove>,
+}
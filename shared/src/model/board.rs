@@ -1,10 +1,55 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt};
 
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 
 use crate::model::*;
 
+// Squares further out than this are refused, so neighbours never overflow and
+// every coordinate fits in the 16 bits `hash_key` gives it
+pub const MAX_COORD: isize = 1 << 14;
+// Far more turns than any real game, and it fits a usize on 32 bit targets
+pub const MAX_TURNS: usize = u32::MAX as usize;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BoardError
+{
+    // No piece to move from the square
+    EmptySquare(Square),
+    // The coordinates do not add up to zero, or are too far out
+    OffGrid(Square),
+    // The piece is not on top of the square it moves from
+    NotOnTop(Square),
+    // `queens` does not match the queens on the board
+    QueenMismatch(Color),
+    TooManyTurns,
+}
+
+impl fmt::Display for BoardError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        use BoardError::*;
+        match self
+        {
+            EmptySquare(sq) => write!(f, "no piece at {sq:?}"),
+            OffGrid(sq) => write!(f, "{sq:?} is not on the grid"),
+            NotOnTop(sq) => write!(f, "the piece is not on top at {sq:?}"),
+            QueenMismatch(color) => write!(f, "the {color:?} queen is out of place"),
+            TooManyTurns => write!(f, "too many turns"),
+        }
+    }
+}
+
+pub type BoardResult<T> = Result<T, BoardError>;
+
+fn on_grid(sq: Square) -> bool
+{
+    let (q, r, s) = sq;
+    let near = |n: isize| (-MAX_COORD..=MAX_COORD).contains(&n);
+    near(q) && near(r) && near(s) && q + r + s == 0
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BoardSquare
 {
@@ -30,9 +75,9 @@ impl BoardSquare
         self.pieces.pop()
     }
 
-    pub fn top(&self) -> &Piece
+    pub fn top(&self) -> Option<&Piece>
     {
-        self.pieces.last().unwrap()
+        self.pieces.last()
     }
 }
 
@@ -40,6 +85,7 @@ impl BoardSquare
 
 #[serde_as]
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(try_from = "RawBoard")]
 pub struct Board
 {
     #[serde_as(as = "Vec<(_, _)>")]
@@ -49,6 +95,40 @@ pub struct Board
     pub turns:  usize,
 }
 
+// A board as it comes over the wire, checked before it becomes a Board
+#[serde_as]
+#[derive(Deserialize)]
+struct RawBoard
+{
+    #[serde_as(as = "Vec<(_, _)>")]
+    board:  HashMap<Square, BoardSquare>,
+    queens: [Option<Square>; 2],
+    turns:  usize,
+}
+
+impl TryFrom<RawBoard> for Board
+{
+    type Error = BoardError;
+
+    fn try_from(raw: RawBoard) -> BoardResult<Self>
+    {
+        let board = Board {
+            board: raw.board, queens: raw.queens, turns: raw.turns
+        };
+        board.validate()?;
+        Ok(board)
+    }
+}
+
+// The ASCII picture, handy in test failures
+impl fmt::Display for Board
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        f.write_str(&crate::render::ascii::render(self))
+    }
+}
+
 impl Board
 {
     pub fn get(&self, sq: &Square) -> Option<&BoardSquare>
@@ -116,63 +196,105 @@ impl Board
         }
     }
 
-    pub fn play_move(&mut self, r#move: Move)
+    pub fn play_move(&mut self, r#move: Move) -> BoardResult<()>
     {
-        self.play_move_(r#move.into());
+        match r#move.r#move
+        {
+            Some(r#move) => self.play_move_(r#move),
+            None if self.turns >= MAX_TURNS => Err(BoardError::TooManyTurns),
+            None =>
+            {
+                self.turns += 1;
+                Ok(())
+            },
+        }
     }
 
-    pub fn play_move_(&mut self, r#move: InternalMove)
+    pub fn play_move_(&mut self, r#move: InternalMove) -> BoardResult<()>
     {
-        self.place_piece(r#move.piece, r#move.sq, r#move.old_sq);
+        self.place_piece(r#move.piece, r#move.sq, r#move.old_sq)
     }
 
-    pub fn unplay_move(&mut self, r#move: InternalMove)
+    pub fn unplay_move(&mut self, r#move: InternalMove) -> BoardResult<()>
     {
-        let bs = self.board.get_mut(&r#move.sq).unwrap();
-        let p = bs.remove_piece().unwrap();
+        let p = self.take_top(r#move.sq)?;
 
         if let Some(old) = r#move.old_sq
         {
-            if let Some(ref mut bs) = self.board.get_mut(&old)
-            {
-                bs.place_piece(p);
-            }
-            else
-            {
-                self.board.insert(old, BoardSquare::new(p));
-            }
+            self.board
+                .entry(old)
+                .and_modify(|bs| bs.place_piece(p))
+                .or_insert_with(|| BoardSquare::new(p));
         }
+        Ok(())
     }
 
-    pub fn un_play_from_to(&mut self, from: Square, to: Square)
+    pub fn un_play_from_to(&mut self, from: Square, to: Square) -> BoardResult<()>
     {
-        if let Some(bs) = self.board.get_mut(&to)
-        {
-            let p = unsafe
-            {
-                bs.remove_piece().unwrap_unchecked()
-            };
-            self.board.entry(from).and_modify(|bs| bs.place_piece(p));
-        }
+        let p = self.take_top(to)?;
+        self.board.entry(from).and_modify(|bs| bs.place_piece(p));
+        Ok(())
     }
 
-    pub fn play_from_to(&mut self, from: Square, to: Square)
+    pub fn play_from_to(&mut self, from: Square, to: Square) -> BoardResult<()>
     {
-        let bs = self.board.get_mut(&from).unwrap();
-        let old = bs.remove_piece().unwrap();
-        if bs.pieces.is_empty()
+        if !on_grid(to)
         {
-            self.board.remove(&from).unwrap();
+            return Err(BoardError::OffGrid(to));
         }
+        let old = self.take_top(from)?;
 
         self.board
             .entry(to)
             .and_modify(|bs| bs.place_piece(old))
             .or_insert_with(|| BoardSquare::new(old));
+        Ok(())
     }
 
+    // Removes the top piece of a square, and the square once it is empty
+    fn take_top(&mut self, sq: Square) -> BoardResult<Piece>
+    {
+        let bs = self.board.get_mut(&sq).ok_or(BoardError::EmptySquare(sq))?;
+        let piece = bs.remove_piece().ok_or(BoardError::EmptySquare(sq))?;
+        if bs.pieces.is_empty()
+        {
+            self.board.remove(&sq);
+        }
+        Ok(piece)
+    }
+
+    /*
+     * Places `piece` on `sq`, moving it from `old` if it is on the board. The
+     * board is left as it was if the move can not be played.
+     */
     pub fn place_piece(&mut self, piece: Piece, sq: Square, old: Option<Square>)
+        -> BoardResult<()>
     {
+        if !on_grid(sq)
+        {
+            return Err(BoardError::OffGrid(sq));
+        }
+        if self.turns >= MAX_TURNS
+        {
+            return Err(BoardError::TooManyTurns);
+        }
+        // Each side has a single queen
+        if piece.r#type == BoardPiece::Queen
+            && old.is_none()
+            && self.queens[piece.color as usize].is_some()
+        {
+            return Err(BoardError::QueenMismatch(piece.color));
+        }
+        if let Some(old) = old
+        {
+            match self.get(&old).and_then(BoardSquare::top)
+            {
+                Some(top) if *top == piece => self.take_top(old).map(|_| ())?,
+                Some(_) => return Err(BoardError::NotOnTop(old)),
+                None => return Err(BoardError::EmptySquare(old)),
+            }
+        }
+
         if piece.r#type == BoardPiece::Queen
         {
             let idx = piece.color as usize;
@@ -184,17 +306,70 @@ impl Board
             .and_modify(|bs| bs.place_piece(piece))
             .or_insert_with(|| BoardSquare::new(piece));
 
-        if let Some(sq) = old
+        self.turns += 1;
+        Ok(())
+    }
+
+    /*
+     * Checks what the rules code takes for granted: every square is on the
+     * grid and holds a piece, and `queens` points at the queens.
+     */
+    pub fn validate(&self) -> BoardResult<()>
+    {
+        if self.turns > MAX_TURNS
+        {
+            return Err(BoardError::TooManyTurns);
+        }
+
+        for (sq, bs) in self.board.iter()
         {
-            let bs = self.board.get_mut(&sq).unwrap();
-            bs.remove_piece();
+            if !on_grid(*sq)
+            {
+                return Err(BoardError::OffGrid(*sq));
+            }
             if bs.pieces.is_empty()
             {
-                self.board.remove(&sq).unwrap();
+                return Err(BoardError::EmptySquare(*sq));
             }
         }
 
-        self.turns += 1;
+        for color in [Color::White, Color::Black]
+        {
+            let queen = Piece::new(BoardPiece::Queen, color);
+            let mut found = self.board.iter().filter(|(_, bs)| bs.pieces.contains(&queen));
+            let at = found.next().map(|(sq, _)| *sq);
+
+            if found.next().is_some() || self.queens[color as usize] != at
+            {
+                return Err(BoardError::QueenMismatch(color));
+            }
+        }
+        Ok(())
+    }
+
+    /*
+     * Zobrist style hash of the position, every piece is keyed on its square,
+     * height and type, and the side to move is mixed in. XOR makes the hash
+     * independent of the HashMap iteration order.
+     */
+    pub fn hash_key(&self) -> u64
+    {
+        let side = match self.turns % 2
+        {
+            0 => 0,
+            _ => mix(u64::MAX),
+        };
+
+        self.board.iter().fold(side, |acc, (sq, bs)| {
+            bs.pieces.iter().enumerate().fold(acc, |acc, (height, piece)| {
+                let key = (sq.0 as u16 as u64)
+                    | (sq.1 as u16 as u64) << 16
+                    | (height as u64) << 32
+                    | (piece.r#type as u64) << 40
+                    | (piece.color as u64) << 48;
+                acc ^ mix(key)
+            })
+        })
     }
 
     pub fn is_complete(&self) -> bool
@@ -210,8 +385,16 @@ impl Board
     }
 }
 
+// splitmix64 finalizer
+fn mix(key: u64) -> u64
+{
+    let mut z = key.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BoardPiece
 {
     Queen,
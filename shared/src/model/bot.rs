@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Strength
+{
+    Easy,
+    Medium,
+    Hard,
+    // An external UHP engine
+    External,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Bot
+{
+    pub name:     String,
+    pub strength: Strength,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BotChallenge
+{
+    pub bot: String,
+}
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+use crate::model::game::InternalMove;
+
+
+// A move played from a position in completed games, and how those games ended
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ExplorerMove
+{
+    pub r#move: InternalMove,
+    pub white:  u32,
+    pub black:  u32,
+    pub draws:  u32,
+}
+
+impl ExplorerMove
+{
+    pub fn games(&self) -> u32
+    {
+        self.white + self.black + self.draws
+    }
+}
@@ -1,7 +1,7 @@
 use bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
 
-use crate::model::board::*;
+use crate::model::{board::*, listing::GameResult, review::Review};
 
 pub type Square = (isize, isize, isize);
 type Name = String;
@@ -10,14 +10,15 @@ type Name = String;
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Move
 {
-    pub player_id: ObjectId,
-    pub game_id:   ObjectId,
-    pub piece:     Piece,
-    pub sq:        Square,
-    pub old_sq:    Option<Square>,
+    pub game_id:     ObjectId,
+    // None is a pass, only allowed when the player has no legal move
+    pub r#move:      Option<InternalMove>,
+    // Moves played in the game before this one. The server turns the move
+    // down if the game has moved on since the client saw it
+    pub move_number: usize,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct InternalMove
 {
     pub piece:  Piece,
@@ -25,16 +26,6 @@ pub struct InternalMove
     pub old_sq: Option<Square>,
 }
 
-impl From<Move> for InternalMove
-{
-    fn from(r#move: Move) -> Self
-    {
-        Self {
-            piece: r#move.piece, sq: r#move.sq, old_sq: r#move.old_sq
-        }
-    }
-}
-
 
 #[derive(Serialize, Deserialize)]
 pub struct OnGoingGame
@@ -50,17 +41,58 @@ pub struct GameResource
     pub _id:       ObjectId,
     pub players:   [Name; 2],
     pub board:     Board,
-    pub move_list: Vec<InternalMove>,
+    // None is a pass
+    pub move_list: Vec<Option<InternalMove>>,
+    // Made by the server once the game is over
+    #[serde(default)]
+    pub review:    Option<Review>,
+    // How the game ended, also when it did not end on the board
+    #[serde(default)]
+    pub result:    Option<GameResult>,
+    // Kept from the record an imported game came from
+    #[serde(default)]
+    pub record:    Option<RecordInfo>,
+}
+
+// What a record says about a game besides the moves, see `shared::record`
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct RecordInfo
+{
+    pub date:     Option<String>,
+    // By move
+    pub times:    Vec<Option<String>>,
+    pub comments: Vec<Option<String>>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+// A finished game to import, as a BoardSpace SGF record or one of our own
+// exported records
+#[derive(Serialize, Deserialize)]
+pub enum ImportGame
+{
+    Sgf(String),
+    Record(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Color
 {
     White,
     Black,
 }
 
-#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
+impl Color
+{
+    pub fn opposite(self) -> Self
+    {
+        match self
+        {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
 pub struct Piece
 {
     pub r#type: BoardPiece,
@@ -84,7 +116,6 @@ pub struct CreateGameFormResponse
 {
     pub game:    ObjectId,
     pub creator: ObjectId,
-    pub user:    ObjectId,
 }
 
 
@@ -105,7 +136,7 @@ pub struct CreateGameChallengeBundle
 }
 
 
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct AcceptGame
 {
     pub object_id: ObjectId,
@@ -1,29 +1,120 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
-type HttpStatusCode = u32;
-#[derive(Serialize, Deserialize)]
-pub struct ResponseBody
+/*
+ * The body of every answer from the api that is not a success, sent with a
+ * status code to match. Successful answers are the value itself as json.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiError
 {
-    pub status: HttpStatusCode,
-    pub body:   String,
+    pub code:    ErrorCode,
+    // Meant for the user
+    pub message: String,
+    // What exactly was wrong, if there is more to tell
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
 }
 
-impl ResponseBody
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode
 {
-    pub fn to_body(status: HttpStatusCode, body: String) -> String
+    // The body could not be read as what the endpoint expects
+    InvalidBody,
+    BadRequest,
+    Unauthorized,
+    // Wrong name or password
+    InvalidLogin,
+    NotFound,
+    MethodNotAllowed,
+    UserAlreadyExists,
+    TooManyGames,
+    IllegalMove,
+    NotYourTurn,
+    GameNotComplete,
+    // What was acted on has changed, fetch it again
+    Conflict,
+    Busy,
+    Internal,
+    // Never sent by the server, the client could not reach it or did not
+    // understand the answer
+    Network,
+}
+
+impl ErrorCode
+{
+    pub fn status(self) -> u16
     {
-        let resp = Self {
-            status,
-            body,
-        };
+        use ErrorCode::*;
+        match self
+        {
+            InvalidBody | BadRequest | IllegalMove | NotYourTurn | GameNotComplete => 400,
+            Unauthorized | InvalidLogin => 401,
+            NotFound => 404,
+            MethodNotAllowed => 405,
+            UserAlreadyExists | Conflict => 409,
+            TooManyGames => 429,
+            Busy => 503,
+            Internal | Network => 500,
+        }
+    }
 
-        serde_json::to_string(&resp).unwrap()
+    // What the user is told when there is nothing more specific to say
+    pub fn message(self) -> &'static str
+    {
+        use ErrorCode::*;
+        match self
+        {
+            InvalidBody => "the request could not be read",
+            BadRequest => "bad request",
+            Unauthorized => "you need to log in",
+            InvalidLogin => "wrong name or password",
+            NotFound => "not found",
+            MethodNotAllowed => "method not allowed",
+            UserAlreadyExists => "the name is taken",
+            TooManyGames => "you have too many open challenges",
+            IllegalMove => "the move is not legal",
+            NotYourTurn => "it is not your turn",
+            GameNotComplete => "the game is not over",
+            Conflict => "it changed in the meantime, try again",
+            Busy => "the server is busy, try again later",
+            Internal => "something went wrong on the server",
+            Network => "could not reach the server",
+        }
     }
+}
 
-    pub fn get_body<T>(self) -> T
-    where
-        T: serde::de::DeserializeOwned,
+impl ApiError
+{
+    pub fn new(code: ErrorCode) -> Self
     {
-        serde_json::from_str(self.body.as_str()).unwrap()
+        Self {
+            code,
+            message: code.message().to_string(),
+            details: None,
+        }
+    }
+
+    pub fn with_details<S: Into<String>>(code: ErrorCode, details: S) -> Self
+    {
+        Self {
+            details: Some(details.into()), ..Self::new(code)
+        }
+    }
+}
+
+impl fmt::Display for ApiError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match &self.details
+        {
+            Some(details) => write!(f, "{}: {details}", self.message),
+            None => write!(f, "{}", self.message),
+        }
     }
 }
+
+impl std::error::Error for ApiError {}
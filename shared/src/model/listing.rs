@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+
+/*
+ * Narrows down and orders a listing of games or challenges, it is sent as
+ * the query string. Every field can be left out.
+ */
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct ListQuery
+{
+    // Games with this player in them, or challenges made by them
+    pub player:  Option<String>,
+    // Only narrows down games
+    pub result:  Option<GameResult>,
+    // Days as `YYYY-MM-DD`, both ends are included
+    pub from:    Option<String>,
+    pub to:      Option<String>,
+    // Like in UHP, `Base` is the only one played here
+    pub ruleset: Option<String>,
+    #[serde(default)]
+    pub sort:    SortOrder,
+    // Where the last page stopped, see `Page::next`
+    pub cursor:  Option<String>,
+    pub limit:   Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GameResult
+{
+    White,
+    Black,
+    Draw,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder
+{
+    #[default]
+    Newest,
+    Oldest,
+}
+
+/*
+ * One page of a listing. `next` is the cursor for the page after this one,
+ * there are no more pages when it is None.
+ */
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Page<T>
+{
+    pub items: Vec<T>,
+    pub next:  Option<String>,
+}
+
+impl<T> Default for Page<T>
+{
+    fn default() -> Self
+    {
+        Self {
+            items: Vec::new(), next: None
+        }
+    }
+}
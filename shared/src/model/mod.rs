@@ -1,5 +1,5 @@
 pub mod user;
-pub use user::UserCredentials;
+pub use user::{Session, UserCredentials};
 
 pub mod http;
 pub use http::*;
@@ -9,3 +9,21 @@ pub use game::*;
 
 pub mod board;
 pub use board::*;
+
+pub mod bot;
+pub use bot::*;
+
+pub mod analysis;
+pub use analysis::*;
+
+pub mod review;
+pub use review::*;
+
+pub mod explorer;
+pub use explorer::*;
+
+pub mod puzzle;
+pub use puzzle::*;
+
+pub mod listing;
+pub use listing::*;
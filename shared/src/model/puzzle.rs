@@ -0,0 +1,37 @@
+use bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use crate::model::game::InternalMove;
+
+
+// A position from a finished game where the side to move can force a win
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PuzzleResource
+{
+    pub _id:    ObjectId,
+    // The game the position comes from
+    pub game:   ObjectId,
+    // The moves of the game before the position, None is a pass
+    pub moves:  Vec<Option<InternalMove>>,
+    // Number of moves the side to move needs to surround the queen
+    pub length: u32,
+}
+
+// The moves played since the puzzle position, followed by the move to check.
+// None is a pass
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PuzzleAttempt
+{
+    pub puzzle: ObjectId,
+    pub played: Vec<Option<InternalMove>>,
+    pub r#move: InternalMove,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum PuzzleAnswer
+{
+    Wrong,
+    // The move keeps the win, the other side answers. None is a pass
+    Reply(Option<InternalMove>),
+    Solved,
+}
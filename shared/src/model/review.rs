@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+use crate::model::game::InternalMove;
+
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Annotation
+{
+    Blunder,
+    // A win was there to take, but the move played no longer wins
+    MissedWin,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MoveReview
+{
+    // Evaluation after the move, from white's point of view
+    pub score:      i32,
+    // The engine's choice in the position before the move
+    pub best_move:  Option<InternalMove>,
+    pub annotation: Option<Annotation>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Review
+{
+    pub depth: u32,
+    pub moves: Vec<MoveReview>,
+}
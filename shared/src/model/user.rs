@@ -1,3 +1,4 @@
+use bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
 
 
@@ -7,3 +8,12 @@ pub struct UserCredentials
     pub name:     String,
     pub password: String,
 }
+
+// Handed out on login, the token goes in the Authorization header as
+// `Bearer <token>`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Session
+{
+    pub token: String,
+    pub user:  ObjectId,
+}
@@ -65,8 +65,10 @@ mod test
         let beetle_square = (0, 0, 0);
         let beetle = Piece::new(BoardPiece::Beetle, Color::White);
 
-        board.place_piece(Piece::new(BoardPiece::Ant, Color::Black), beetle_square, None);
-        board.place_piece(beetle, beetle_square, None);
+        board
+            .place_piece(Piece::new(BoardPiece::Ant, Color::Black), beetle_square, None)
+            .unwrap();
+        board.place_piece(beetle, beetle_square, None).unwrap();
 
 
         board.turns = 3;
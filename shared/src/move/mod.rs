@@ -15,6 +15,9 @@ use queen::*;
 mod grasshopper;
 use grasshopper::*;
 
+#[cfg(test)]
+mod properties;
+
 pub fn legal_moves(p: &Piece, board: &Board, board_pos: Option<Square>) -> Vec<Square>
 {
     if board.is_complete()
@@ -83,7 +86,7 @@ fn legal_new_piece_moves(piece: &Piece, board: &Board) -> Vec<Square>
         neighbors(sq).into_iter().all(|sq| match board.get(&sq)
         {
             None => true,
-            Some(s) => s.top().color == piece.color,
+            Some(s) => s.top().map_or(true, |top| top.color == piece.color),
         })
     };
 
@@ -94,7 +97,7 @@ fn legal_new_piece_moves(piece: &Piece, board: &Board) -> Vec<Square>
     board
         .iter()
         .filter_map(|(sq, bp)| {
-            (bp.top().color == piece.color).then(|| {
+            (bp.top().map(|top| top.color) == Some(piece.color)).then(|| {
                 neighbors(sq)
                     .into_iter()
                     .filter_map(|sq| not_touching_other_color(sq).then(|| sq))
@@ -109,16 +112,7 @@ pub fn neighbors(sq: &Square) -> [Square; 6]
     const CUBE_DIR_VEC: [(isize, isize, isize); 6] =
         [(1, 0, -1), (1, -1, 0), (0, -1, 1), (-1, 0, 1), (-1, 1, 0), (0, 1, -1)];
 
-    let mut iter = CUBE_DIR_VEC.into_iter().map(|d| (sq.0 + d.0, sq.1 + d.1, sq.2 + d.2));
-
-    [
-        iter.next().unwrap(),
-        iter.next().unwrap(),
-        iter.next().unwrap(),
-        iter.next().unwrap(),
-        iter.next().unwrap(),
-        iter.next().unwrap(),
-    ]
+    CUBE_DIR_VEC.map(|d| (sq.0 + d.0, sq.1 + d.1, sq.2 + d.2))
 }
 
 
@@ -135,7 +129,12 @@ fn create_island(board: &Board, from: Square) -> bool
         Some(bs) => !bs.pieces.is_empty(),
         _ => false,
     });
-    let first = iter.next().unwrap();
+    // A piece with no neighbours splits nothing off by leaving
+    let first = match iter.next()
+    {
+        Some(sq) => sq,
+        None => return false,
+    };
 
     create_set(&board, first, &mut global);
 
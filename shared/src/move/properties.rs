@@ -0,0 +1,160 @@
+use std::collections::HashSet;
+
+use proptest::{prelude::*, sample::Index};
+
+use super::*;
+use crate::engine::{apply, generate_moves, pass};
+
+/*
+ * Random legal games. Each index picks one of the legal moves, so shrinking
+ * the indices shrinks the game down to the moves needed for a failure.
+ */
+fn games() -> impl Strategy<Value = Vec<Index>>
+{
+    prop::collection::vec(any::<Index>(), 1..60)
+}
+
+fn height(board: &Board, sq: &Square) -> usize
+{
+    board.get(sq).map_or(0, |bs| bs.pieces.len())
+}
+
+fn connected(board: &Board) -> bool
+{
+    let start = match board.iter().next()
+    {
+        Some((sq, _)) => *sq,
+        None => return true,
+    };
+
+    let mut seen = HashSet::from([start]);
+    let mut stack = vec![start];
+    while let Some(sq) = stack.pop()
+    {
+        for sq in neighbors(&sq)
+        {
+            if board.contains_key(&sq) && seen.insert(sq)
+            {
+                stack.push(sq);
+            }
+        }
+    }
+    seen.len() == board.len()
+}
+
+fn queens_in_sync(board: &Board) -> bool
+{
+    [Color::White, Color::Black].into_iter().all(|color| {
+        let queen = Piece::new(BoardPiece::Queen, color);
+        let found = board.iter().find(|(_, bs)| bs.pieces.contains(&queen)).map(|(sq, _)| *sq);
+        board.queens[color as usize] == found
+    })
+}
+
+// Every square a piece at `from` can reach by sliding without passing
+// through a gate, keeping in touch with the hive
+fn slide_reach(board: &Board, from: Square) -> HashSet<Square>
+{
+    let mut board = board.clone();
+    board.remove_top(from);
+
+    let mut seen = HashSet::new();
+    let mut stack = vec![from];
+    while let Some(sq) = stack.pop()
+    {
+        for next in neighbors(&sq)
+        {
+            if !board.contains_key(&next)
+                && square_has_neighbors(next, &board, from)
+                && can_fit(sq, next, &board)
+                && seen.insert(next)
+            {
+                stack.push(next);
+            }
+        }
+    }
+    seen
+}
+
+fn check_move(before: &Board, r#move: &InternalMove, after: &Board) -> Result<(), TestCaseError>
+{
+    prop_assert!(after.validate().is_ok(), "invalid board: {:?}", after.validate());
+    prop_assert!(connected(after), "the hive split");
+    prop_assert!(queens_in_sync(after), "queens out of sync: {:?}", after.queens);
+
+    let from = r#move.old_sq;
+    let to = r#move.sq;
+
+    // Only beetles stack, everything else moves to and from the ground
+    if r#move.piece.r#type != BoardPiece::Beetle
+    {
+        prop_assert_eq!(height(before, &to), 0);
+        if let Some(from) = from
+        {
+            prop_assert_eq!(height(before, &from), 1);
+        }
+    }
+    prop_assert_eq!(height(after, &to), height(before, &to) + 1);
+    if let Some(from) = from
+    {
+        prop_assert_eq!(height(after, &from), height(before, &from) - 1);
+    }
+    for (sq, _) in before.iter().filter(|(sq, _)| Some(**sq) != from && **sq != to)
+    {
+        prop_assert_eq!(height(after, sq), height(before, sq));
+    }
+
+    // Sliding pieces can not squeeze through closed gates
+    if let (Some(from), BoardPiece::Ant | BoardPiece::Queen | BoardPiece::Spider) =
+        (from, r#move.piece.r#type)
+    {
+        prop_assert!(slide_reach(before, from).contains(&to), "slid through a gate to {:?}", to);
+    }
+    Ok(())
+}
+
+fn check_position(board: &Board) -> Result<(), TestCaseError>
+{
+    for (sq, piece) in board.iter().filter_map(|(sq, bs)| bs.top().map(|p| (sq, p)))
+    {
+        let moves = legal_moves(piece, board, Some(*sq));
+        prop_assert!(!moves.contains(sq), "{:?} can move to its own square", piece);
+    }
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn test_random_games_keep_invariants(choices in games())
+    {
+        let mut board = Board::default();
+        let mut played = Vec::new();
+        for choice in choices
+        {
+            if board.is_complete()
+            {
+                break;
+            }
+
+            let moves = generate_moves(&board);
+            if moves.is_empty()
+            {
+                board = pass(&board);
+                continue;
+            }
+
+            let r#move = moves[choice.index(moves.len())].clone();
+            let after = apply(&board, &r#move);
+            played.push(r#move.clone());
+
+            // Name the moves, the indices alone do not say much
+            if let Err(e) = check_move(&board, &r#move, &after).and_then(|_| check_position(&after))
+            {
+                return Err(TestCaseError::fail(format!("{e}\n{board}moves: {played:#?}")));
+            }
+            board = after;
+        }
+    }
+}
@@ -0,0 +1,453 @@
+use std::{fmt, str::FromStr};
+
+use crate::{
+    engine::Outcome,
+    model::*,
+    uhp::{Position, UhpError, GAME_TYPE},
+};
+
+/*
+ * Our text format for keeping games outside the database. Tags on top like
+ * in PGN, then one move per line in UHP notation. A move can be followed by
+ * the time it was played in braces and a comment after a `;`:
+ *
+ *     [GameType "Base"]
+ *     [Date "2026-10-19"]
+ *     [White "alice"]
+ *     [Black "bob"]
+ *     [Result "WhiteWins"]
+ *
+ *     1. wS1
+ *     2. bG1 -wS1 {2026-10-19T10:01:02Z}
+ *     3. wQ wS1/ ; Blunder, wA1 bG1- was better
+ *
+ * The result is one of the UHP game states, so a game can be won by
+ * resignation even though no queen is surrounded. Other tags are skipped.
+ */
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RecordError
+{
+    InvalidTag(String),
+    MissingTag(&'static str),
+    UnsupportedGameType(String),
+    InvalidResult(String),
+    InvalidLine(String),
+    IllegalMove(usize, UhpError),
+}
+
+impl fmt::Display for RecordError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        use RecordError::*;
+        match self
+        {
+            InvalidTag(s) => write!(f, "invalid tag '{s}'"),
+            MissingTag(s) => write!(f, "missing the {s} tag"),
+            UnsupportedGameType(s) => write!(f, "unsupported game type '{s}'"),
+            InvalidResult(s) => write!(f, "invalid result '{s}'"),
+            InvalidLine(s) => write!(f, "invalid line '{s}'"),
+            IllegalMove(n, e) => write!(f, "move {n}: {e}"),
+        }
+    }
+}
+
+pub type RecordResult<T> = Result<T, RecordError>;
+
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RecordMove
+{
+    pub r#move:  Option<InternalMove>,
+    pub time:    Option<String>,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record
+{
+    // By color, like in `GameResource`
+    pub players: [String; 2],
+    pub date:    Option<String>,
+    // `None` while the game is still going
+    pub result:  Option<Outcome>,
+    pub moves:   Vec<RecordMove>,
+}
+
+fn result_str(result: Option<Outcome>) -> &'static str
+{
+    match result
+    {
+        Some(Outcome::Win(Color::White)) => "WhiteWins",
+        Some(Outcome::Win(Color::Black)) => "BlackWins",
+        Some(Outcome::Draw) => "Draw",
+        None => "InProgress",
+    }
+}
+
+fn parse_result(s: &str) -> RecordResult<Option<Outcome>>
+{
+    match s
+    {
+        "WhiteWins" => Ok(Some(Outcome::Win(Color::White))),
+        "BlackWins" => Ok(Some(Outcome::Win(Color::Black))),
+        "Draw" => Ok(Some(Outcome::Draw)),
+        "InProgress" | "NotStarted" => Ok(None),
+        _ => Err(RecordError::InvalidResult(s.to_string())),
+    }
+}
+
+// `[Name "value"]`, with `\"` and `\\` in the value
+fn parse_tag(line: &str) -> RecordResult<(&str, String)>
+{
+    let err = || RecordError::InvalidTag(line.to_string());
+
+    let inner = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')).ok_or_else(err)?;
+    let (name, value) = inner.split_once(' ').ok_or_else(err)?;
+    let value = value
+        .trim()
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or_else(err)?;
+
+    let mut res = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next()
+    {
+        match c
+        {
+            '\\' => res.push(chars.next().ok_or_else(err)?),
+            '"' => return Err(err()),
+            c => res.push(c),
+        }
+    }
+    Ok((name, res))
+}
+
+// Neither can hold a line break, and the time ends at the first `}` or `;`
+fn one_line(s: &str) -> String
+{
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+impl Record
+{
+    // The record of a game played here, with the result the board shows
+    pub fn new(players: [String; 2], moves: &[Option<InternalMove>]) -> Self
+    {
+        let pos = Position::from_moves(moves);
+        Self {
+            players,
+            date: None,
+            result: crate::engine::outcome(pos.board()),
+            moves: moves
+                .iter()
+                .map(|r#move| RecordMove {
+                    r#move: r#move.clone(),
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+
+    // Comments on the moves the review found mistakes in, a move that has a
+    // comment already keeps it
+    pub fn annotate(&mut self, review: &Review)
+    {
+        let mut pos = Position::new();
+        for (r#move, reviewed) in self.moves.iter_mut().zip(&review.moves)
+        {
+            if r#move.comment.is_some()
+            {
+                pos.play(r#move.r#move.clone());
+                continue;
+            }
+
+            let what = match reviewed.annotation
+            {
+                Some(Annotation::Blunder) => Some("Blunder"),
+                Some(Annotation::MissedWin) => Some("Missed a win"),
+                None => None,
+            };
+            if let Some(what) = what
+            {
+                r#move.comment = Some(match &reviewed.best_move
+                {
+                    Some(best) => format!("{what}, {} was better", pos.move_string(Some(best))),
+                    None => what.to_string(),
+                });
+            }
+            pos.play(r#move.r#move.clone());
+        }
+    }
+
+    // The date, times and comments, to keep when the moves are stored
+    pub fn info(&self) -> RecordInfo
+    {
+        RecordInfo {
+            date:     self.date.clone(),
+            times:    self.moves.iter().map(|r#move| r#move.time.clone()).collect(),
+            comments: self.moves.iter().map(|r#move| r#move.comment.clone()).collect(),
+        }
+    }
+
+    pub fn set_info(&mut self, info: &RecordInfo)
+    {
+        self.date = info.date.clone();
+        for (i, r#move) in self.moves.iter_mut().enumerate()
+        {
+            r#move.time = info.times.get(i).cloned().flatten();
+            r#move.comment = info.comments.get(i).cloned().flatten();
+        }
+    }
+
+    pub fn board(&self) -> Board
+    {
+        let mut pos = Position::new();
+        for r#move in &self.moves
+        {
+            pos.play(r#move.r#move.clone());
+        }
+        pos.board().clone()
+    }
+}
+
+impl fmt::Display for Record
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        let tag = |name: &str, value: &str| {
+            format!("[{name} \"{}\"]\n", value.replace('\\', "\\\\").replace('"', "\\\""))
+        };
+
+        f.write_str(&tag("GameType", GAME_TYPE))?;
+        if let Some(date) = &self.date
+        {
+            f.write_str(&tag("Date", date))?;
+        }
+        f.write_str(&tag("White", &self.players[0]))?;
+        f.write_str(&tag("Black", &self.players[1]))?;
+        f.write_str(&tag("Result", result_str(self.result)))?;
+        writeln!(f)?;
+
+        let mut pos = Position::new();
+        for (i, r#move) in self.moves.iter().enumerate()
+        {
+            write!(f, "{}. {}", i + 1, pos.move_string(r#move.r#move.as_ref()))?;
+            if let Some(time) = &r#move.time
+            {
+                write!(f, " {{{}}}", one_line(time).replace(['}', ';'], ""))?;
+            }
+            if let Some(comment) = &r#move.comment
+            {
+                write!(f, " ; {}", one_line(comment))?;
+            }
+            writeln!(f)?;
+            pos.play(r#move.r#move.clone());
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Record
+{
+    type Err = RecordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        let mut players = [None, None];
+        let mut date = None;
+        let mut result = None;
+        let mut moves = Vec::new();
+        let mut pos = Position::new();
+
+        for line in s.lines().map(str::trim).filter(|line| !line.is_empty())
+        {
+            if line.starts_with('[')
+            {
+                let (name, value) = parse_tag(line)?;
+                match name
+                {
+                    "GameType" if value != GAME_TYPE =>
+                    {
+                        return Err(RecordError::UnsupportedGameType(value))
+                    },
+                    "Date" => date = Some(value),
+                    "White" => players[0] = Some(value),
+                    "Black" => players[1] = Some(value),
+                    "Result" => result = Some(parse_result(&value)?),
+                    _ => (),
+                }
+                continue;
+            }
+
+            let err = || RecordError::InvalidLine(line.to_string());
+
+            let (line, comment) = match line.split_once(';')
+            {
+                Some((line, comment)) => (line, Some(comment.trim().to_string())),
+                None => (line, None),
+            };
+            let (line, time) = match line.split_once('{')
+            {
+                Some((line, time)) =>
+                {
+                    let time = time.trim_end().strip_suffix('}').ok_or_else(err)?;
+                    (line, Some(time.trim().to_string()))
+                },
+                None => (line, None),
+            };
+
+            let n = moves.len() + 1;
+            let r#move = match line.split_once(". ")
+            {
+                Some((i, r#move)) if i.parse() == Ok(n) => r#move.trim(),
+                _ => return Err(err()),
+            };
+            pos.play_str(r#move).map_err(|e| RecordError::IllegalMove(n, e))?;
+
+            moves.push(RecordMove {
+                r#move: pos.history().last().cloned().flatten(),
+                time,
+                comment,
+            });
+        }
+
+        let [white, black] = players;
+        Ok(Self {
+            players: [
+                white.ok_or(RecordError::MissingTag("White"))?,
+                black.ok_or(RecordError::MissingTag("Black"))?,
+            ],
+            date,
+            result: result.ok_or(RecordError::MissingTag("Result"))?,
+            moves,
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::engine::test::{legal_game, place};
+
+    const RECORD: &str = r#"[GameType "Base"]
+[Date "2026-10-19"]
+[White "alice \"the bee\""]
+[Black "bob"]
+[Event "skipped"]
+[Result "BlackWins"]
+
+1. wS1
+2. bG1 -wS1 {2026-10-19T10:01:02Z}
+3. wQ wS1/ ; Blunder, wA1 bG1- was better
+4. bQ -bG1 {2026-10-19T10:02:00Z} ; Resigns after this
+"#;
+
+    #[test]
+    fn test_parse()
+    {
+        let record: Record = RECORD.parse().unwrap();
+        assert_eq!(record.players, [String::from("alice \"the bee\""), String::from("bob")]);
+        assert_eq!(record.date.as_deref(), Some("2026-10-19"));
+        assert_eq!(record.result, Some(Outcome::Win(Color::Black)));
+
+        let placed = |r#type, color, sq| Some(place(r#type, color, sq));
+        let moves: Vec<_> = record.moves.iter().map(|m| m.r#move.clone()).collect();
+        assert_eq!(moves, vec![
+            placed(BoardPiece::Spider, Color::White, (0, 0, 0)),
+            placed(BoardPiece::Grasshopper, Color::Black, (-1, 0, 1)),
+            placed(BoardPiece::Queen, Color::White, (1, -1, 0)),
+            placed(BoardPiece::Queen, Color::Black, (-2, 0, 2)),
+        ]);
+        assert_eq!(record.moves[1].time.as_deref(), Some("2026-10-19T10:01:02Z"));
+        assert_eq!(record.moves[2].comment.as_deref(), Some("Blunder, wA1 bG1- was better"));
+        assert_eq!(record.moves[3].comment.as_deref(), Some("Resigns after this"));
+
+        // Written back the same, apart from the unknown tag
+        assert_eq!(record.to_string(), RECORD.replace("[Event \"skipped\"]\n", ""));
+    }
+
+    #[test]
+    fn test_round_trip()
+    {
+        let players = [String::from("white"), String::from("black")];
+        let moves = legal_game(40);
+        let mut record = Record::new(players, &moves);
+        record.moves[0].comment = Some(String::from("two\nlines"));
+
+        let parsed: Record = record.to_string().parse().unwrap();
+        assert_eq!(parsed.moves[0].comment.as_deref(), Some("two lines"));
+        record.moves[0].comment = parsed.moves[0].comment.clone();
+        assert_eq!(parsed, record);
+        assert_eq!(parsed.board().hash_key(), Position::from_moves(&moves).board().hash_key());
+
+        // The moves and what else the record says can be stored apart
+        let mut rebuilt = Record::new(parsed.players.clone(), &moves);
+        rebuilt.set_info(&parsed.info());
+        assert_eq!(rebuilt, record);
+    }
+
+    #[test]
+    fn test_annotate()
+    {
+        let mut record: Record = RECORD.parse().unwrap();
+        let reviewed = |annotation, best_move| MoveReview {
+            score: 0,
+            best_move,
+            annotation,
+        };
+        let review = Review {
+            depth: 2,
+            moves: vec![
+                reviewed(
+                    Some(Annotation::Blunder),
+                    Some(place(BoardPiece::Ant, Color::White, (0, 0, 0))),
+                ),
+                reviewed(Some(Annotation::MissedWin), None),
+                reviewed(Some(Annotation::Blunder), None),
+            ],
+        };
+        record.annotate(&review);
+
+        let comments: Vec<_> = record.moves.iter().map(|m| m.comment.as_deref()).collect();
+        assert_eq!(comments, [
+            Some("Blunder, wA1 was better"),
+            Some("Missed a win"),
+            Some("Blunder, wA1 bG1- was better"),
+            Some("Resigns after this"),
+        ]);
+    }
+
+    #[test]
+    fn test_invalid_records()
+    {
+        let parse = |s: &str| s.parse::<Record>();
+        let tags = "[White \"a\"]\n[Black \"b\"]\n[Result \"InProgress\"]\n";
+
+        assert_eq!(parse(tags).map(|r| r.moves.len()), Ok(0));
+        assert_eq!(
+            parse("[White \"a\"]\n[Result \"Draw\"]"),
+            Err(RecordError::MissingTag("Black"))
+        );
+        assert_eq!(
+            parse("[Result Draw]"),
+            Err(RecordError::InvalidTag(String::from("[Result Draw]")))
+        );
+        assert_eq!(
+            parse("[GameType \"Base+MLP\"]"),
+            Err(RecordError::UnsupportedGameType(String::from("Base+MLP")))
+        );
+        assert_eq!(
+            parse(&format!("{tags}2. wS1")),
+            Err(RecordError::InvalidLine(String::from("2. wS1")))
+        );
+        assert!(matches!(
+            parse(&format!("{tags}1. wS1\n2. bS1 wS1")),
+            Err(RecordError::IllegalMove(2, _))
+        ));
+    }
+}
@@ -0,0 +1,92 @@
+use std::fmt::Write;
+
+use super::*;
+
+// Every cell is this wide, half a cell between neighbouring rows
+const CELL: usize = 6;
+
+/*
+ * Hexes pointy side up, the same way UHP move strings read: `wA1-` is to the
+ * right and `wA1/` up to the right. Each row of the board is a line of text,
+ * and every other line is shifted half a cell. Empty spots in between pieces
+ * are drawn as `.`, stacks as the top piece and the height, e.g. `wB:2`,
+ * with the whole stack listed below the board.
+ */
+pub fn render(board: &Board) -> String
+{
+    let squares = squares(board);
+    if squares.is_empty()
+    {
+        return String::from(".\n");
+    }
+
+    // Doubled columns, so that neighbours on the same row are two apart
+    let col = |sq: &Square| 2 * sq.0 + sq.1;
+    let (cmin, cmax) = squares
+        .iter()
+        .fold((isize::MAX, isize::MIN), |(min, max), (sq, _)| (min.min(col(sq)), max.max(col(sq))));
+    let (rmin, rmax) = (squares[0].0 .1, squares[squares.len() - 1].0 .1);
+
+    let mut out = String::new();
+    for r in rmin..=rmax
+    {
+        let mut line = String::new();
+        let mut c = cmin;
+        while c <= cmax
+        {
+            if (c - r) % 2 != 0
+            {
+                line.push_str(&" ".repeat(CELL / 2));
+                c += 1;
+                continue;
+            }
+
+            let q = (c - r) / 2;
+            let cell = match board.get(&(q, r, -q - r)).map(|bs| (bs.top(), bs.pieces.len()))
+            {
+                Some((Some(piece), 1)) => label(piece),
+                Some((Some(piece), n)) => format!("{}:{n}", label(piece)),
+                _ => String::from("."),
+            };
+            let _ = write!(line, "{cell:CELL$}");
+            c += 2;
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+
+    for (sq, bs) in squares.iter().filter(|(_, bs)| bs.pieces.len() > 1)
+    {
+        let stack: Vec<_> = bs.pieces.iter().map(label).collect();
+        let _ = writeln!(out, "{sq:?}: {}", stack.join(" "));
+    }
+    out
+}
+
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::engine::test::place;
+
+    #[test]
+    fn test_render_ascii()
+    {
+        let mut board = Board::default();
+        for r#move in [
+            place(BoardPiece::Queen, Color::White, (0, 0, 0)),
+            place(BoardPiece::Queen, Color::Black, (1, 0, -1)),
+            place(BoardPiece::Ant, Color::White, (0, 1, -1)),
+            place(BoardPiece::Beetle, Color::Black, (1, -1, 0)),
+            place(BoardPiece::Spider, Color::White, (2, 1, -3)),
+        ]
+        {
+            board.play_move_(r#move).unwrap();
+        }
+        board.play_from_to((1, -1, 0), (1, 0, -1)).unwrap();
+
+        let expected = "wQ    bB:2  .\n   wA    .     wS\n(1, 0, -1): bQ bB\n";
+        assert_eq!(render(&board), expected, "\n{}", render(&board));
+    }
+}
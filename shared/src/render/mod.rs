@@ -0,0 +1,39 @@
+/*
+ * Pictures of a board without the frontend. `ascii` is for terminals and
+ * test failures, `svg` for images served by the server.
+ */
+pub mod ascii;
+pub mod svg;
+
+use crate::model::*;
+
+// Piece names as in UHP, without the number since the board does not know it
+pub fn label(piece: &Piece) -> String
+{
+    let color = match piece.color
+    {
+        Color::White => 'w',
+        Color::Black => 'b',
+    };
+    format!("{color}{}", letter(piece.r#type))
+}
+
+pub fn letter(r#type: BoardPiece) -> char
+{
+    match r#type
+    {
+        BoardPiece::Queen => 'Q',
+        BoardPiece::Ant => 'A',
+        BoardPiece::Grasshopper => 'G',
+        BoardPiece::Spider => 'S',
+        BoardPiece::Beetle => 'B',
+    }
+}
+
+// Occupied squares in a fixed order, so the output does not depend on hashing
+fn squares(board: &Board) -> Vec<(Square, &BoardSquare)>
+{
+    let mut squares: Vec<_> = board.iter().map(|(sq, bs)| (*sq, bs)).collect();
+    squares.sort_by_key(|(sq, _)| (sq.1, sq.0));
+    squares
+}
@@ -0,0 +1,126 @@
+use std::fmt::Write;
+
+use super::*;
+
+// Distance from the middle of a hex to its corners
+const SIZE: f32 = 10.0;
+const STROKE: &str = "rgb(50, 50, 50)";
+
+/*
+ * Hexes flat side up, laid out like the board in the frontend. The picture
+ * is scaled through the view box, so it fits whatever size it is shown at.
+ * Pieces are drawn as their letter, with the stack height in the corner.
+ */
+pub fn render(board: &Board) -> String
+{
+    let squares = squares(board);
+
+    let centers: Vec<_> = squares.iter().map(|(sq, _)| center(sq)).collect();
+    let (x0, y0, x1, y1) = match centers.split_first()
+    {
+        Some((&(x, y), rest)) => rest.iter().fold((x, y, x, y), |(x0, y0, x1, y1), &(x, y)| {
+            (x0.min(x), y0.min(y), x1.max(x), y1.max(y))
+        }),
+        None => (0.0, 0.0, 0.0, 0.0),
+    };
+
+    let mut out = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+        round(x0 - SIZE),
+        round(y0 - SIZE),
+        round(x1 - x0 + 2.0 * SIZE),
+        round(y1 - y0 + 2.0 * SIZE),
+    );
+
+    let points = corners();
+    for ((_, bs), (x, y)) in squares.iter().zip(centers)
+    {
+        let piece = match bs.top()
+        {
+            Some(piece) => piece,
+            None => continue,
+        };
+        let (fill, ink) = match piece.color
+        {
+            Color::White => ("rgb(244, 236, 216)", "rgb(34, 34, 34)"),
+            Color::Black => ("rgb(34, 34, 34)", "rgb(244, 236, 216)"),
+        };
+
+        let _ = write!(
+            out,
+            r#"<g transform="translate({} {})" fill="{ink}" text-anchor="middle" dominant-baseline="central">"#,
+            round(x),
+            round(y)
+        );
+        let _ = write!(
+            out,
+            r#"<polygon points="{points}" fill="{fill}" stroke="{STROKE}" stroke-width="0.5"/>"#
+        );
+        let _ = write!(out, r#"<text font-size="8">{}</text>"#, letter(piece.r#type));
+        if bs.pieces.len() > 1
+        {
+            let _ = write!(out, r#"<text x="4" y="-5" font-size="4">{}</text>"#, bs.pieces.len());
+        }
+        out.push_str("</g>");
+    }
+    out.push_str("</svg>");
+    out
+}
+
+// Same as `Hex::to_pixels` in the frontend, without the offset
+fn center(sq: &Square) -> (f32, f32)
+{
+    let (q, r) = (sq.0 as f32, sq.1 as f32);
+    let x = 1.5 * q * SIZE;
+    let y = (3.0_f32.sqrt() / 2.0 * q + 3.0_f32.sqrt() * r) * SIZE;
+    (x, y)
+}
+
+// Two decimals is plenty, and keeps `10` from turning into `10.00`. Adding
+// zero turns `-0` into `0`.
+fn round(v: f32) -> f32
+{
+    (v * 100.0).round() / 100.0 + 0.0
+}
+
+fn corners() -> String
+{
+    (0..6)
+        .map(|i| {
+            let angle = (60.0 * i as f32).to_radians();
+            format!("{},{}", round(SIZE * angle.cos()), round(SIZE * angle.sin()))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::engine::test::place;
+
+    #[test]
+    fn test_render_svg()
+    {
+        assert_eq!(
+            render(&Board::default()),
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="-10 -10 20 20"></svg>"#
+        );
+
+        let mut board = Board::default();
+        board.play_move_(place(BoardPiece::Queen, Color::White, (0, 0, 0))).unwrap();
+        board.play_move_(place(BoardPiece::Beetle, Color::Black, (1, 0, -1))).unwrap();
+        board.play_from_to((1, 0, -1), (0, 0, 0)).unwrap();
+        board.play_move_(place(BoardPiece::Ant, Color::Black, (0, 1, -1))).unwrap();
+
+        let svg = render(&board);
+        assert!(svg
+            .starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="-10 -10 20 37.32""#));
+        assert_eq!(svg.matches("<polygon").count(), 2);
+        assert!(svg
+            .contains(r#"<text font-size="8">B</text><text x="4" y="-5" font-size="4">2</text>"#));
+        assert!(svg.contains(r#"<text font-size="8">A</text></g>"#));
+    }
+}
@@ -0,0 +1,531 @@
+use std::fmt;
+
+use crate::{
+    model::*,
+    uhp::{Position, UhpError},
+};
+
+/*
+ * Game records in the SGF dialect of boardspace.net. A record is a list of
+ * nodes, the first with the players and the game type, the rest with one
+ * action each:
+ *
+ *     (;GM[27]SU[hive]P0[id "white"]P1[id "black"]
+ *     ;P0[0 Start P0]
+ *     ;P0[1 dropb wA1 N 13 .];P0[2 done]
+ *     ;P1[3 dropb bA1 O 13 wA1-];P1[4 done]
+ *     ;P0[5 move W wA1 N 12 /bA1];P0[6 done])
+ *
+ * A piece goes to a column and row on their grid, followed by the same
+ * position relative to another piece as in UHP. Columns go to the right and
+ * rows upwards, so `O 14` is above and to the right of `N 13`. Only the
+ * base game is supported, records with the mosquito, ladybug or pillbug are
+ * refused.
+ */
+
+const GAME_TYPE: &str = "hive";
+
+// Where the first piece goes when writing a record
+const ORIGIN: (u8, usize) = (b'N', 13);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SgfError
+{
+    Syntax(usize),
+    UnsupportedGameType(String),
+    UnsupportedPiece(String),
+    InvalidAction(String),
+    IllegalMove(String, UhpError),
+    OffGrid(Square),
+}
+
+impl fmt::Display for SgfError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        use SgfError::*;
+        match self
+        {
+            Syntax(i) => write!(f, "syntax error at byte {i}"),
+            UnsupportedGameType(s) => write!(f, "unsupported game type '{s}'"),
+            UnsupportedPiece(s) => write!(f, "unsupported piece '{s}'"),
+            InvalidAction(s) => write!(f, "invalid action '{s}'"),
+            IllegalMove(s, e) => write!(f, "'{s}' can not be played: {e}"),
+            OffGrid(sq) => write!(f, "{sq:?} does not fit on the grid"),
+        }
+    }
+}
+
+pub type SgfResult<T> = Result<T, SgfError>;
+
+
+// Players by color like in `GameResource`, passes are `None`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SgfGame
+{
+    pub players: [String; 2],
+    pub moves:   Vec<Option<InternalMove>>,
+}
+
+type Node = Vec<(String, String)>;
+
+/*
+ * Only the main line is read, variations are skipped along with the
+ * brackets around them.
+ */
+fn nodes(s: &str) -> SgfResult<Vec<Node>>
+{
+    let bytes = s.as_bytes();
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut depth = 0;
+    let mut i = 0;
+
+    while i < bytes.len()
+    {
+        match bytes[i]
+        {
+            b'(' => depth += 1,
+            b')' if depth > 1 => depth -= 1,
+            b')' => break,
+            b';' if depth == 1 => nodes.push(Vec::new()),
+            b';' => (),
+            c if c.is_ascii_whitespace() => (),
+            c if c.is_ascii_alphanumeric() =>
+            {
+                let start = i;
+                while i < bytes.len() && bytes[i].is_ascii_alphanumeric()
+                {
+                    i += 1;
+                }
+                let name = &s[start..i];
+
+                // One or more values after the name
+                let mut values = Vec::new();
+                while i < bytes.len() && bytes[i] == b'['
+                {
+                    let mut value = String::new();
+                    let mut chars = s[i + 1..].char_indices();
+                    loop
+                    {
+                        match chars.next()
+                        {
+                            Some((_, '\\')) => match chars.next()
+                            {
+                                Some((_, c)) => value.push(c),
+                                None => return Err(SgfError::Syntax(i)),
+                            },
+                            Some((j, ']')) =>
+                            {
+                                i += j + 2;
+                                break;
+                            },
+                            Some((_, c)) => value.push(c),
+                            None => return Err(SgfError::Syntax(i)),
+                        }
+                    }
+                    values.push(value);
+                    while i < bytes.len() && bytes[i].is_ascii_whitespace()
+                    {
+                        i += 1;
+                    }
+                }
+
+                if values.is_empty()
+                {
+                    return Err(SgfError::Syntax(i));
+                }
+                if depth == 1
+                {
+                    let node = nodes.last_mut().ok_or(SgfError::Syntax(start))?;
+                    node.extend(values.into_iter().map(|v| (name.to_string(), v)));
+                }
+                continue;
+            },
+            _ => return Err(SgfError::Syntax(i)),
+        }
+        i += 1;
+    }
+    Ok(nodes)
+}
+
+// A piece dropped on the board, by name, grid position and relative position
+struct Drop
+{
+    id:       String,
+    grid:     Option<(u8, usize)>,
+    relative: Option<String>,
+}
+
+impl Drop
+{
+    fn parse(action: &str, args: &[&str]) -> SgfResult<Self>
+    {
+        let err = || SgfError::InvalidAction(action.to_string());
+
+        // Moves on the board name the color first
+        let args = match args
+        {
+            ["W" | "B", rest @ ..] => rest,
+            _ => args,
+        };
+        let (id, grid, relative) = match args
+        {
+            [id, col, row] => (id, Some((col, row)), None),
+            [id, col, row, relative] => (id, Some((col, row)), Some(relative)),
+            _ => return Err(err()),
+        };
+
+        // The expansion pieces
+        if matches!(id.chars().nth(1), Some('M' | 'L' | 'P'))
+        {
+            return Err(SgfError::UnsupportedPiece(id.to_string()));
+        }
+
+        let grid = match grid
+        {
+            Some((col, row)) => match (col.as_bytes(), row.parse())
+            {
+                ([c], Ok(row)) if c.is_ascii_uppercase() => Some((*c, row)),
+                _ => return Err(err()),
+            },
+            None => None,
+        };
+
+        Ok(Self {
+            // Their queen is sometimes numbered
+            id: id.strip_suffix("Q1").map_or(id.to_string(), |c| format!("{c}Q")),
+            grid,
+            relative: relative.filter(|r| **r != ".").map(|r| r.to_string()),
+        })
+    }
+
+    fn play(&self, pos: &mut Position, origin: &mut Option<(u8, usize)>) -> SgfResult<()>
+    {
+        let first = origin.is_none();
+        if first
+        {
+            *origin = self.grid;
+        }
+
+        let s = match &self.relative
+        {
+            Some(relative) => format!("{} {relative}", self.id),
+            None if first => self.id.clone(),
+            None =>
+            {
+                let sq = match (self.grid, *origin)
+                {
+                    (Some(grid), Some(origin)) => from_grid(grid, origin),
+                    _ => return Err(SgfError::InvalidAction(self.id.clone())),
+                };
+                return self.play_on(pos, sq);
+            },
+        };
+
+        let r#move = pos.parse_move(&s).map_err(|e| SgfError::IllegalMove(s.clone(), e))?;
+
+        // Picking a piece up and putting it back down is not a move
+        if matches!(&r#move, Some(m) if m.old_sq == Some(m.sq))
+        {
+            return Ok(());
+        }
+        pos.play_str(&s).map_err(|e| SgfError::IllegalMove(s, e))
+    }
+
+    // Play the legal move of this piece to `sq`, for records without
+    // relative positions
+    fn play_on(&self, pos: &mut Position, sq: Square) -> SgfResult<()>
+    {
+        let r#move = pos.legal_moves().into_iter().flatten().find(|m| {
+            m.sq == sq && pos.move_string(Some(m)).split(' ').next() == Some(self.id.as_str())
+        });
+
+        match r#move
+        {
+            Some(r#move) =>
+            {
+                pos.play(Some(r#move));
+                Ok(())
+            },
+            None => Err(SgfError::IllegalMove(
+                self.id.clone(),
+                UhpError::IllegalMove(format!("{} {sq:?}", self.id)),
+            )),
+        }
+    }
+}
+
+fn from_grid((col, row): (u8, usize), (c0, r0): (u8, usize)) -> Square
+{
+    let q = col as isize - c0 as isize;
+    let r = r0 as isize - row as isize;
+    (q, r, -q - r)
+}
+
+fn to_grid(sq: Square) -> SgfResult<(char, usize)>
+{
+    let col = ORIGIN.0 as isize + sq.0;
+    let row = ORIGIN.1 as isize - sq.1;
+
+    if (b'A' as isize..=b'Z' as isize).contains(&col) && row > 0
+    {
+        Ok((col as u8 as char, row as usize))
+    }
+    else
+    {
+        Err(SgfError::OffGrid(sq))
+    }
+}
+
+/*
+ * Read a record into its moves. Pieces can be picked up and put down again
+ * before the player is done, so a drop is only played once the player is
+ * done or the other player acts.
+ */
+pub fn parse(s: &str) -> SgfResult<SgfGame>
+{
+    let mut players = [String::new(), String::new()];
+    let mut white = 0;
+    let mut pos = Position::new();
+    let mut origin = None;
+    let mut pending: Option<(usize, Drop)> = None;
+
+    for (name, value) in nodes(s)?.into_iter().flatten()
+    {
+        let player = match name.as_str()
+        {
+            "SU" if value != GAME_TYPE => return Err(SgfError::UnsupportedGameType(value)),
+            "P0" => 0,
+            "P1" => 1,
+            _ => continue,
+        };
+
+        let args: Vec<_> = value.split_whitespace().collect();
+        match args.as_slice()
+        {
+            ["id", name @ ..] =>
+            {
+                players[player] = name.join(" ").trim_matches('"').to_string();
+                continue;
+            },
+            // Everything else with a name are times and such
+            [n, ..] if n.parse::<usize>().is_ok() => (),
+            _ => continue,
+        }
+
+        if matches!(&pending, Some((p, _)) if *p != player)
+        {
+            let (_, drop) = pending.take().unwrap();
+            drop.play(&mut pos, &mut origin)?;
+        }
+
+        let action = args[1..].join(" ");
+        match args.get(1).map(|s| s.to_ascii_lowercase()).as_deref()
+        {
+            Some("start") => white = if args.get(2) == Some(&"P1") { 1 } else { 0 },
+            Some("dropb" | "pdropb" | "move" | "pmove") =>
+            {
+                pending = Some((player, Drop::parse(&action, &args[2..])?))
+            },
+            Some("done") =>
+            {
+                if let Some((_, drop)) = pending.take()
+                {
+                    drop.play(&mut pos, &mut origin)?;
+                }
+            },
+            Some("pass") => pos.play_str("pass").map_err(|e| SgfError::IllegalMove(action, e))?,
+            Some(
+                "pick" | "pickb" | "drop" | "resign" | "offerdraw" | "acceptdraw" | "declinedraw",
+            ) =>
+            {},
+            _ => return Err(SgfError::InvalidAction(action)),
+        }
+    }
+
+    if let Some((_, drop)) = pending.take()
+    {
+        drop.play(&mut pos, &mut origin)?;
+    }
+
+    let [p0, p1] = players;
+    Ok(SgfGame {
+        players: if white == 0 { [p0, p1] } else { [p1, p0] },
+        moves:   pos.history().cloned().collect(),
+    })
+}
+
+// Write a record that `parse` and boardspace.net can read back
+pub fn write(game: &SgfGame) -> SgfResult<String>
+{
+    let escape = |s: &str| s.replace('\\', "\\\\").replace(']', "\\]");
+
+    let mut out = format!(
+        "(;\nGM[27]VV[1]\nSU[{GAME_TYPE}]\nP0[id \"{}\"]\nP1[id \"{}\"]\n; P0[0 Start P0]\n",
+        escape(&game.players[0]),
+        escape(&game.players[1]),
+    );
+
+    let mut pos = Position::new();
+    let mut n = 1;
+    for (i, r#move) in game.moves.iter().enumerate()
+    {
+        let player = i % 2;
+        let s = pos.move_string(r#move.as_ref());
+
+        let action = match r#move
+        {
+            Some(r#move) =>
+            {
+                let (id, relative) = s.split_once(' ').unwrap_or((s.as_str(), "."));
+                let (col, row) = to_grid(r#move.sq)?;
+                match r#move.old_sq
+                {
+                    Some(_) =>
+                    {
+                        let color = if player == 0 { 'W' } else { 'B' };
+                        format!("move {color} {id} {col} {row} {relative}")
+                    },
+                    None => format!("dropb {id} {col} {row} {relative}"),
+                }
+            },
+            None => String::from("pass"),
+        };
+
+        out.push_str(&format!("; P{player}[{n} {}]\n", escape(&action)));
+        out.push_str(&format!("; P{player}[{} done]\n", n + 1));
+        n += 2;
+        pos.play(r#move.clone());
+    }
+
+    out.push(')');
+    Ok(out)
+}
+
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::engine::test::legal_game;
+
+    const RECORD: &str = r#"(;
+GM[27]VV[1]
+SU[hive]
+P0[id "alice"]
+P1[id "bob"]
+P0[time 0:00:00 ]
+; P0[0 Start P0]
+; P0[1 pdropb wS1 N 13 .]
+; P0[2 done]
+; P1[3 pdropb bG1 M 13 -wS1]
+; P1[4 done]
+; P0[5 pdropb wQ O 13 wS1-]
+; P0[6 pickb O 13 wQ]
+; P0[7 dropb wQ O 14 wS1/]
+; P0[8 done]
+; P1[9 pdropb bQ L 13 -bG1]
+; P1[10 done]
+; P0[11 pmove W wQ O 13 wS1-]
+; P0[12 done]
+)"#;
+
+    fn play(moves: &[&str]) -> Vec<Option<InternalMove>>
+    {
+        let mut pos = Position::new();
+        for r#move in moves
+        {
+            pos.play_str(r#move).unwrap();
+        }
+        pos.history().cloned().collect()
+    }
+
+    // Leave out the relative positions, like older records
+    fn without_relative(record: &str) -> String
+    {
+        record
+            .lines()
+            .map(|line| match line.rsplit_once(' ')
+            {
+                Some((action, _)) if line.contains("drop") || line.contains("move") =>
+                {
+                    format!("{action}]\n")
+                },
+                _ => format!("{line}\n"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse()
+    {
+        let game = parse(RECORD).unwrap();
+        assert_eq!(game.players, [String::from("alice"), String::from("bob")]);
+        assert_eq!(game.moves, play(&["wS1", "bG1 -wS1", "wQ wS1/", "bQ -bG1", "wQ wS1-"]));
+
+        // The same game from the grid positions alone
+        assert_eq!(parse(&without_relative(RECORD)).unwrap(), game);
+    }
+
+    // A whole record as boardspace.net writes them, with times, a piece put
+    // back down and a resignation
+    #[test]
+    fn test_parse_boardspace()
+    {
+        let game = parse(include_str!("../fixtures/HV-alice-bob-2023-05-02-1812.sgf")).unwrap();
+        assert_eq!(game.players, [String::from("alice"), String::from("bob")]);
+
+        let moves = [
+            "wG1",
+            "bG1 -wG1",
+            "wQ wG1/",
+            "bQ \\bG1",
+            "wA1 wQ-",
+            "bA1 -bG1",
+            "wA1 -bA1",
+            "bB1 /bG1",
+            "wB1 wG1\\",
+            "bB1 bG1",
+            "wS1 wQ/",
+            "bB1 wG1",
+            "wB1 bB1",
+            "bA2 /bG1",
+            "wS1 bQ/",
+            "bA2 wS1-",
+            "wA1 bA2-",
+        ];
+        assert_eq!(game.moves, play(&moves));
+    }
+
+    #[test]
+    fn test_round_trip()
+    {
+        let game = SgfGame {
+            players: [String::from("white [1]"), String::from("black")],
+            moves:   legal_game(40),
+        };
+        assert!(game.moves.iter().flatten().any(|m| m.old_sq.is_some()));
+
+        let record = write(&game).unwrap();
+        assert_eq!(parse(&record), Ok(game.clone()), "\n{record}");
+        assert_eq!(parse(&without_relative(&record)), Ok(game), "\n{record}");
+    }
+
+    #[test]
+    fn test_invalid_records()
+    {
+        assert_eq!(
+            parse("(;SU[hive-plm];P0[1 dropb wM N 13 .])"),
+            Err(SgfError::UnsupportedGameType(String::from("hive-plm")))
+        );
+        assert_eq!(
+            parse("(;SU[hive];P0[1 dropb wM N 13 .])"),
+            Err(SgfError::UnsupportedPiece(String::from("wM")))
+        );
+        assert!(matches!(
+            parse("(;SU[hive];P0[1 dropb wA1 N 13 .];P1[2 dropb bA1 P 13 wA1--])"),
+            Err(SgfError::IllegalMove(..))
+        ));
+        assert_eq!(parse("(;SU[hive];P0[1 dropb wA1 N 13 .)"), Err(SgfError::Syntax(13)));
+    }
+}
@@ -0,0 +1,647 @@
+use std::{collections::HashMap, fmt, str::FromStr, time::Duration};
+
+use crate::{
+    engine::{apply, generate_moves, outcome, pass, side_to_move, Outcome},
+    model::*,
+};
+
+/*
+ * Notation of the Universal Hive Protocol (UHP), the text protocol used by
+ * Mzinga and other Hive engines and viewers.
+ *
+ * Pieces are named by color, bug and the order they were placed in, e.g.
+ * `wQ`, `bA2`. A move names the piece and where it goes relative to a piece
+ * already on the board, `bG1 -wS1` places the grasshopper to the left of the
+ * spider. `wB1 bQ` climbs on top of a piece.
+ */
+
+pub const GAME_TYPE: &str = "Base";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum UhpError
+{
+    InvalidPiece(String),
+    InvalidMove(String),
+    UnknownPiece(String),
+    IllegalMove(String),
+    InvalidGameString(String),
+    UnsupportedGameType(String),
+}
+
+impl fmt::Display for UhpError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        use UhpError::*;
+        match self
+        {
+            InvalidPiece(s) => write!(f, "invalid piece '{s}'"),
+            InvalidMove(s) => write!(f, "invalid move string '{s}'"),
+            UnknownPiece(s) => write!(f, "'{s}' is not on the board"),
+            IllegalMove(s) => write!(f, "'{s}' is not a legal move"),
+            InvalidGameString(s) => write!(f, "invalid game string '{s}'"),
+            UnsupportedGameType(s) => write!(f, "unsupported game type '{s}'"),
+        }
+    }
+}
+
+pub type UhpResult<T> = Result<T, UhpError>;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PieceId
+{
+    pub color:  Color,
+    pub r#type: BoardPiece,
+    pub n:      u8,
+}
+
+impl fmt::Display for PieceId
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        let color = match self.color
+        {
+            Color::White => 'w',
+            Color::Black => 'b',
+        };
+        let bug = match self.r#type
+        {
+            BoardPiece::Queen => return write!(f, "{color}Q"),
+            BoardPiece::Ant => 'A',
+            BoardPiece::Grasshopper => 'G',
+            BoardPiece::Spider => 'S',
+            BoardPiece::Beetle => 'B',
+        };
+        write!(f, "{color}{bug}{}", self.n)
+    }
+}
+
+impl FromStr for PieceId
+{
+    type Err = UhpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        let err = || UhpError::InvalidPiece(s.to_string());
+        let mut chars = s.chars();
+
+        let color = match chars.next()
+        {
+            Some('w') => Color::White,
+            Some('b') => Color::Black,
+            _ => return Err(err()),
+        };
+        let r#type = match chars.next()
+        {
+            Some('Q') => BoardPiece::Queen,
+            Some('A') => BoardPiece::Ant,
+            Some('G') => BoardPiece::Grasshopper,
+            Some('S') => BoardPiece::Spider,
+            Some('B') => BoardPiece::Beetle,
+            _ => return Err(err()),
+        };
+        let n = match (r#type, chars.as_str())
+        {
+            (BoardPiece::Queen, "") => 1,
+            (BoardPiece::Queen, _) => return Err(err()),
+            (_, n) => n.parse().map_err(|_| err())?,
+        };
+
+        let max = crate::engine::PIECES.iter().find(|(t, _)| *t == r#type).unwrap().1;
+        if n == 0 || n as usize > max
+        {
+            return Err(err());
+        }
+
+        Ok(Self {
+            color,
+            r#type,
+            n,
+        })
+    }
+}
+
+// Neighbor offsets in the same order as `r#move::neighbors`, with the
+// separator and whether it is written in front of the reference piece.
+const DIRECTIONS: [(Square, char, bool); 6] = [
+    ((1, 0, -1), '-', false),
+    ((1, -1, 0), '/', false),
+    ((0, -1, 1), '\\', true),
+    ((-1, 0, 1), '-', true),
+    ((-1, 1, 0), '/', true),
+    ((0, 1, -1), '\\', false),
+];
+
+fn sq_add(a: Square, b: Square) -> Square
+{
+    (a.0 + b.0, a.1 + b.1, a.2 + b.2)
+}
+
+fn sq_sub(a: Square, b: Square) -> Square
+{
+    (a.0 - b.0, a.1 - b.1, a.2 - b.2)
+}
+
+
+/*
+ * A board that also keeps track of which piece is which, since `Board` only
+ * knows the bug and color. Passes are stored as `None`.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct Position
+{
+    board:   Board,
+    stacks:  HashMap<Square, Vec<PieceId>>,
+    history: Vec<(Option<InternalMove>, String)>,
+}
+
+impl Position
+{
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    pub fn from_moves<'a, I>(moves: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Option<InternalMove>>,
+    {
+        let mut pos = Self::new();
+        for r#move in moves
+        {
+            pos.play(r#move.clone());
+        }
+        pos
+    }
+
+    pub fn board(&self) -> &Board
+    {
+        &self.board
+    }
+
+    pub fn history(&self) -> impl Iterator<Item = &Option<InternalMove>>
+    {
+        self.history.iter().map(|(r#move, _)| r#move)
+    }
+
+    fn locate(&self, id: PieceId) -> Option<Square>
+    {
+        self.stacks.iter().find(|(_, stack)| stack.contains(&id)).map(|(sq, _)| *sq)
+    }
+
+    fn next_id(&self, piece: Piece) -> PieceId
+    {
+        let placed = self
+            .stacks
+            .values()
+            .flatten()
+            .filter(|id| id.color == piece.color && id.r#type == piece.r#type)
+            .count();
+
+        PieceId {
+            color: piece.color, r#type: piece.r#type, n: placed as u8 + 1
+        }
+    }
+
+    // The move string of `move`, relative to the current position
+    pub fn move_string(&self, r#move: Option<&InternalMove>) -> String
+    {
+        let r#move = match r#move
+        {
+            Some(r#move) => r#move,
+            None => return "pass".to_string(),
+        };
+
+        let id = match r#move.old_sq.and_then(|sq| self.stacks.get(&sq)).and_then(|s| s.last())
+        {
+            Some(id) => *id,
+            None => self.next_id(r#move.piece),
+        };
+
+        if self.stacks.is_empty()
+        {
+            return id.to_string();
+        }
+
+        if let Some(top) = self.stacks.get(&r#move.sq).and_then(|s| s.last())
+        {
+            return format!("{id} {top}");
+        }
+
+        // The moving piece can not be its own reference
+        let reference = |sq: Square| {
+            let stack = self.stacks.get(&sq)?;
+            if Some(sq) == r#move.old_sq
+            {
+                stack.iter().rev().nth(1)
+            }
+            else
+            {
+                stack.last()
+            }
+        };
+
+        for (offset, sep, front) in DIRECTIONS
+        {
+            if let Some(other) = reference(sq_sub(r#move.sq, offset))
+            {
+                return if front
+                {
+                    format!("{id} {sep}{other}")
+                }
+                else
+                {
+                    format!("{id} {other}{sep}")
+                };
+            }
+        }
+
+        // Not connected to the hive, only happens for illegal moves
+        id.to_string()
+    }
+
+    // Parse a move string, without checking that the move is legal
+    pub fn parse_move(&self, s: &str) -> UhpResult<Option<InternalMove>>
+    {
+        let err = || UhpError::InvalidMove(s.to_string());
+
+        if s.eq_ignore_ascii_case("pass")
+        {
+            return Ok(None);
+        }
+
+        let mut parts = s.split_whitespace();
+        let id: PieceId = parts.next().ok_or_else(err)?.parse()?;
+        let target = parts.next();
+        if parts.next().is_some()
+        {
+            return Err(err());
+        }
+
+        let old_sq = self.locate(id);
+        if old_sq.is_none() && self.next_id(Piece::new(id.r#type, id.color)) != id
+        {
+            return Err(UhpError::InvalidPiece(id.to_string()));
+        }
+
+        let sq = match target
+        {
+            None if self.stacks.is_empty() => (0, 0, 0),
+            None => return Err(err()),
+            Some(target) =>
+            {
+                let is_sep = |c: char| matches!(c, '-' | '/' | '\\');
+
+                let (name, sep, front) = match (target.chars().next(), target.chars().last())
+                {
+                    (Some(c), _) if is_sep(c) => (&target[1..], Some(c), true),
+                    (_, Some(c)) if is_sep(c) => (&target[..target.len() - 1], Some(c), false),
+                    _ => (target, None, false),
+                };
+
+                let other: PieceId = name.parse()?;
+                let other_sq =
+                    self.locate(other).ok_or_else(|| UhpError::UnknownPiece(other.to_string()))?;
+
+                match sep
+                {
+                    None => other_sq,
+                    Some(sep) =>
+                    {
+                        let (offset, ..) = DIRECTIONS
+                            .iter()
+                            .find(|(_, s, f)| *s == sep && *f == front)
+                            .ok_or_else(err)?;
+                        sq_add(other_sq, *offset)
+                    },
+                }
+            },
+        };
+
+        Ok(Some(InternalMove {
+            piece: Piece::new(id.r#type, id.color),
+            sq,
+            old_sq,
+        }))
+    }
+
+    // All legal moves, a pass is the only legal move when nothing else is
+    pub fn legal_moves(&self) -> Vec<Option<InternalMove>>
+    {
+        if outcome(&self.board).is_some()
+        {
+            return Vec::new();
+        }
+
+        match generate_moves(&self.board)
+        {
+            moves if moves.is_empty() => vec![None],
+            moves => moves.into_iter().map(Some).collect(),
+        }
+    }
+
+    pub fn play(&mut self, r#move: Option<InternalMove>)
+    {
+        let s = self.move_string(r#move.as_ref());
+
+        match &r#move
+        {
+            Some(m) =>
+            {
+                let id = match m.old_sq.and_then(|sq| self.stacks.get_mut(&sq))
+                {
+                    Some(stack) =>
+                    {
+                        let id = stack.pop().unwrap();
+                        if stack.is_empty()
+                        {
+                            self.stacks.remove(&m.old_sq.unwrap());
+                        }
+                        id
+                    },
+                    None => self.next_id(m.piece),
+                };
+                self.stacks.entry(m.sq).or_default().push(id);
+                self.board = apply(&self.board, m);
+            },
+            None => self.board = pass(&self.board),
+        }
+
+        self.history.push((r#move, s));
+    }
+
+    // Parse, validate and play a move string
+    pub fn play_str(&mut self, s: &str) -> UhpResult<()>
+    {
+        let r#move = self.parse_move(s)?;
+
+        if !self.legal_moves().contains(&r#move)
+        {
+            return Err(UhpError::IllegalMove(s.to_string()));
+        }
+
+        self.play(r#move);
+        Ok(())
+    }
+
+    pub fn undo(&mut self, n: usize)
+    {
+        let keep = self.history.len().saturating_sub(n);
+        let moves = self.history.drain(..).take(keep).map(|(m, _)| m).collect::<Vec<_>>();
+
+        *self = Self::new();
+        for r#move in moves
+        {
+            self.play(r#move);
+        }
+    }
+
+    pub fn game_state(&self) -> &'static str
+    {
+        match outcome(&self.board)
+        {
+            Some(Outcome::Win(Color::White)) => "WhiteWins",
+            Some(Outcome::Win(Color::Black)) => "BlackWins",
+            Some(Outcome::Draw) => "Draw",
+            None if self.history.is_empty() => "NotStarted",
+            None => "InProgress",
+        }
+    }
+
+    pub fn turn_string(&self) -> String
+    {
+        let color = match side_to_move(&self.board)
+        {
+            Color::White => "White",
+            Color::Black => "Black",
+        };
+        format!("{color}[{}]", self.history.len() / 2 + 1)
+    }
+
+    // e.g. `Base;InProgress;White[2];wS1;bG1 -wS1`
+    pub fn game_string(&self) -> String
+    {
+        let mut s = format!("{GAME_TYPE};{};{}", self.game_state(), self.turn_string());
+        for (_, r#move) in &self.history
+        {
+            s.push(';');
+            s.push_str(r#move);
+        }
+        s
+    }
+}
+
+impl FromStr for Position
+{
+    type Err = UhpError;
+
+    // Accepts either a game type string or a full game string
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        let mut parts = s.split(';');
+
+        match parts.next()
+        {
+            Some(GAME_TYPE) => (),
+            Some(t) => return Err(UhpError::UnsupportedGameType(t.to_string())),
+            None => return Err(UhpError::InvalidGameString(s.to_string())),
+        }
+
+        let mut pos = Position::new();
+        match (parts.next(), parts.next())
+        {
+            (None, None) => return Ok(pos),
+            (Some(_), Some(_)) => (),
+            _ => return Err(UhpError::InvalidGameString(s.to_string())),
+        }
+
+        for r#move in parts
+        {
+            pos.play_str(r#move)?;
+        }
+        Ok(pos)
+    }
+}
+
+
+/*
+ * The client end of the protocol, for talking to an external engine. Only
+ * the commands and the reading of the answers are here, running the process
+ * is up to the caller.
+ */
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum EngineError
+{
+    // The engine answered with `err` or `invalidmove`
+    Refused(String),
+    // The engine answered `bestmove` with something that is not a legal move
+    BadMove(String),
+}
+
+impl fmt::Display for EngineError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self
+        {
+            EngineError::Refused(s) => write!(f, "engine error: {s}"),
+            EngineError::BadMove(s) => write!(f, "could not play the engine's move '{s}'"),
+        }
+    }
+}
+
+// Sets the engine up with the game so far
+pub fn newgame_command(pos: &Position) -> String
+{
+    format!("newgame {}", pos.game_string())
+}
+
+// Asks for a move within `time`, in whole seconds
+pub fn bestmove_command(time: Duration) -> String
+{
+    let secs = time.as_secs().max(1);
+    format!("bestmove time {:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+// An answer from the engine, read a line at a time. Every answer ends with a
+// line containing `ok`
+#[derive(Debug, Default)]
+pub struct Response
+{
+    lines: Vec<String>,
+}
+
+impl Response
+{
+    // Returns true once the answer is complete
+    pub fn push(&mut self, line: &str) -> bool
+    {
+        match line.trim()
+        {
+            "ok" => true,
+            line =>
+            {
+                self.lines.push(line.to_string());
+                false
+            },
+        }
+    }
+
+    pub fn finish(self) -> Result<Vec<String>, EngineError>
+    {
+        match self.lines.iter().find(|l| l.starts_with("err") || l.starts_with("invalidmove"))
+        {
+            Some(e) => Err(EngineError::Refused(e.clone())),
+            None => Ok(self.lines),
+        }
+    }
+}
+
+// The move in the answer to `bestmove`, None is a pass
+pub fn parse_bestmove(pos: &Position, lines: &[String])
+    -> Result<Option<InternalMove>, EngineError>
+{
+    let reply = lines.first().map_or("", String::as_str);
+    match pos.parse_move(reply)
+    {
+        Ok(r#move) if pos.legal_moves().contains(&r#move) => Ok(r#move),
+        _ => Err(EngineError::BadMove(reply.to_string())),
+    }
+}
+
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    fn test_piece_id()
+    {
+        for s in ["wQ", "bA3", "wG1", "bS2", "wB2"]
+        {
+            assert_eq!(s.parse::<PieceId>().unwrap().to_string(), s);
+        }
+
+        for s in ["", "w", "xA1", "wQ1", "wA4", "bS3", "wB0", "wK1"]
+        {
+            assert!(s.parse::<PieceId>().is_err());
+        }
+    }
+
+    #[test]
+    fn test_move_strings()
+    {
+        let mut pos = Position::new();
+        for s in ["wS1", "bG1 -wS1", "wQ wS1/", "bQ /bG1", "wB1 \\wQ", "bA1 bQ\\"]
+        {
+            pos.play_str(s).unwrap();
+        }
+
+        assert_eq!(
+            pos.game_string(),
+            "Base;InProgress;White[4];wS1;bG1 -wS1;wQ wS1/;bQ /bG1;wB1 \\wQ;bA1 bQ\\"
+        );
+
+        // Round trip every legal move through the notation
+        for r#move in pos.legal_moves()
+        {
+            let s = pos.move_string(r#move.as_ref());
+            assert_eq!(pos.parse_move(&s).unwrap(), r#move, "{s}");
+        }
+    }
+
+    #[test]
+    fn test_illegal_moves()
+    {
+        let mut pos = Position::new();
+        pos.play_str("wS1").unwrap();
+
+        assert_eq!(pos.play_str("wA1 wS1-"), Err(UhpError::IllegalMove("wA1 wS1-".into())));
+        assert_eq!(pos.play_str("bA2 wS1-"), Err(UhpError::InvalidPiece("bA2".into())));
+        assert_eq!(pos.play_str("bA1 wQ-"), Err(UhpError::UnknownPiece("wQ".into())));
+        assert!(pos.play_str("bA1 wS1-").is_ok());
+    }
+
+    #[test]
+    fn test_game_string_round_trip()
+    {
+        let s = "Base;InProgress;Black[3];wS1;bG1 -wS1;wQ wS1/;bQ /bG1;wB1 \\wQ";
+        let mut pos: Position = s.parse().unwrap();
+        assert_eq!(pos.game_string(), s);
+
+        pos.undo(2);
+        assert_eq!(pos.game_string(), "Base;InProgress;Black[2];wS1;bG1 -wS1;wQ wS1/");
+        assert_eq!("Base".parse::<Position>().unwrap().game_string(), "Base;NotStarted;White[1]");
+        assert!("Base+M".parse::<Position>().is_err());
+    }
+
+    #[test]
+    fn test_engine_answers()
+    {
+        assert_eq!(bestmove_command(Duration::from_millis(3725500)), "bestmove time 01:02:05");
+        assert_eq!(newgame_command(&Position::new()), "newgame Base;NotStarted;White[1]");
+
+        let read = |lines: &[&str]| {
+            let mut response = Response::default();
+            let done = lines.iter().map(|line| response.push(line)).collect::<Vec<_>>();
+            (done, response.finish())
+        };
+        let (done, res) = read(&["id engine v1.0", " ok "]);
+        assert_eq!(done, [false, true]);
+        assert_eq!(res, Ok(vec![String::from("id engine v1.0")]));
+
+        let (_, res) = read(&["invalidmove wQ wQ", "ok"]);
+        assert_eq!(res, Err(EngineError::Refused("invalidmove wQ wQ".into())));
+
+        let mut pos = Position::new();
+        pos.play_str("wS1").unwrap();
+        let answer = |s: &str| parse_bestmove(&pos, &[s.to_string()]);
+        assert_eq!(answer("bG1 -wS1").unwrap().unwrap().sq, (-1, 0, 1));
+        assert_eq!(answer("pass"), Err(EngineError::BadMove("pass".into())));
+        assert_eq!(answer("bG1"), Err(EngineError::BadMove("bG1".into())));
+        assert!(parse_bestmove(&pos, &[]).is_err());
+    }
+}
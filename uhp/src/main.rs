@@ -0,0 +1,43 @@
+mod session;
+use std::io::{self, BufRead, Write};
+
+use session::{Error, Session};
+
+
+/*
+ * Universal Hive Protocol engine.
+ * Reads one command per line from stdin and answers on stdout, every answer
+ * is terminated by a line containing `ok`.
+ */
+fn main() -> io::Result<()>
+{
+    let mut session = Session::default();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    writeln!(out, "{}\nok", session::info())?;
+    out.flush()?;
+
+    for line in io::stdin().lock().lines()
+    {
+        let line = line?;
+        let line = line.trim();
+
+        if line == "exit"
+        {
+            break;
+        }
+
+        match session.handle(line)
+        {
+            Ok(s) if s.is_empty() => (),
+            Ok(s) => writeln!(out, "{s}")?,
+            Err(Error::InvalidMove(e)) => writeln!(out, "invalidmove {e}")?,
+            Err(Error::Err(e)) => writeln!(out, "err {e}")?,
+        }
+        writeln!(out, "ok")?;
+        out.flush()?;
+    }
+
+    Ok(())
+}
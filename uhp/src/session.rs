@@ -0,0 +1,370 @@
+use std::time::{Duration, Instant};
+
+use shared::{
+    engine::{AlphaBeta, Engine, Mcts, SearchResult, TranspositionTable},
+    uhp::{Position, UhpError},
+};
+
+
+const TABLE_SIZE: usize = 1 << 16;
+const MAX_DEPTH: u32 = 8;
+
+#[derive(Debug)]
+pub enum Error
+{
+    InvalidMove(String),
+    Err(String),
+}
+
+impl From<UhpError> for Error
+{
+    fn from(e: UhpError) -> Self
+    {
+        match e
+        {
+            UhpError::InvalidPiece(_)
+            | UhpError::InvalidMove(_)
+            | UhpError::UnknownPiece(_)
+            | UhpError::IllegalMove(_) => Error::InvalidMove(e.to_string()),
+            _ => Error::Err(e.to_string()),
+        }
+    }
+}
+
+pub type SessionResult = Result<String, Error>;
+
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum EngineKind
+{
+    AlphaBeta,
+    Mcts,
+}
+
+pub struct Session
+{
+    pos:        Position,
+    engine:     EngineKind,
+    depth:      u32,
+    iterations: u32,
+    seed:       u64,
+}
+
+impl Default for Session
+{
+    fn default() -> Self
+    {
+        Self {
+            pos:        Position::new(),
+            engine:     EngineKind::AlphaBeta,
+            depth:      3,
+            iterations: 2000,
+            seed:       0,
+        }
+    }
+}
+
+// The id line, then the expansions on a line of their own, none here
+pub fn info() -> String
+{
+    format!("id {} v{}\n", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+}
+
+fn err<T>(s: &str) -> Result<T, Error>
+{
+    Err(Error::Err(s.to_string()))
+}
+
+// hh:mm:ss
+fn parse_time(s: &str) -> Option<Duration>
+{
+    let parts = s.split(':').map(|p| p.parse::<u64>().ok()).collect::<Option<Vec<_>>>()?;
+    match parts[..]
+    {
+        [h, m, s] => Some(Duration::from_secs(h * 3600 + m * 60 + s)),
+        _ => None,
+    }
+}
+
+impl Session
+{
+    pub fn handle(&mut self, line: &str) -> SessionResult
+    {
+        let (cmd, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args = args.trim();
+
+        match cmd
+        {
+            "" => Ok(String::new()),
+            "info" => Ok(info()),
+            "newgame" =>
+            {
+                self.pos = match args
+                {
+                    "" => Position::new(),
+                    s => s.parse()?,
+                };
+                Ok(self.pos.game_string())
+            },
+            "play" =>
+            {
+                self.pos.play_str(args)?;
+                Ok(self.pos.game_string())
+            },
+            "pass" =>
+            {
+                self.pos.play_str("pass")?;
+                Ok(self.pos.game_string())
+            },
+            "validmoves" => Ok(self
+                .pos
+                .legal_moves()
+                .iter()
+                .map(|m| self.pos.move_string(m.as_ref()))
+                .collect::<Vec<_>>()
+                .join(";")),
+            "bestmove" => self.best_move(args),
+            "undo" =>
+            {
+                let n = match args
+                {
+                    "" => 1,
+                    n => n.parse().or_else(|_| err("expected the number of moves to undo"))?,
+                };
+                self.pos.undo(n);
+                Ok(self.pos.game_string())
+            },
+            "options" => self.options(args),
+            _ => err(&format!("unknown command '{cmd}'")),
+        }
+    }
+
+    fn best_move(&mut self, args: &str) -> SessionResult
+    {
+        if self.pos.legal_moves().is_empty()
+        {
+            return err("the game is over");
+        }
+
+        let board = self.pos.board().clone();
+        let res = match args.split_once(' ')
+        {
+            None if args.is_empty() => self.search(self.depth, &board),
+            Some(("depth", depth)) => match depth.parse::<u32>()
+            {
+                Ok(depth) if depth > 0 => self.search(depth.min(MAX_DEPTH), &board),
+                _ => return err("expected a positive depth"),
+            },
+            Some(("time", time)) => match parse_time(time)
+            {
+                Some(time) => self.search_time(time, &board),
+                None => return err("expected the time as hh:mm:ss"),
+            },
+            _ => return err("expected 'bestmove', 'bestmove depth n' or 'bestmove time hh:mm:ss'"),
+        };
+
+        Ok(self.pos.move_string(res.best_move.as_ref()))
+    }
+
+    fn search(&self, depth: u32, board: &shared::model::Board) -> SearchResult
+    {
+        match self.engine
+        {
+            EngineKind::AlphaBeta =>
+            {
+                let mut engine = AlphaBeta::new(depth);
+                engine.table = Some(TranspositionTable::new(TABLE_SIZE));
+                engine.search(board)
+            },
+            EngineKind::Mcts => Mcts::new(self.iterations, self.seed).search(board),
+        }
+    }
+
+    /*
+     * Iterative deepening, or doubling the playouts, until the next search
+     * probably will not finish in time. A search still running at the
+     * deadline is given up for the result of the one before it.
+     */
+    fn search_time(&self, time: Duration, board: &shared::model::Board) -> SearchResult
+    {
+        const GROWTH: u32 = 8;
+        let start = Instant::now();
+        let deadline = start + time;
+
+        let mut best = None;
+        for level in 1..=MAX_DEPTH
+        {
+            let res = match self.engine
+            {
+                EngineKind::AlphaBeta =>
+                {
+                    let mut engine = AlphaBeta::new(level);
+                    engine.table = Some(TranspositionTable::new(TABLE_SIZE));
+                    engine.deadline = Some(deadline);
+                    let res = engine.search(board);
+                    match engine.aborted()
+                    {
+                        true => None,
+                        false => Some(res),
+                    }
+                },
+                EngineKind::Mcts =>
+                {
+                    let mut engine = Mcts::new(100 << level, self.seed);
+                    engine.deadline = Some(deadline);
+                    Some(engine.search(board))
+                },
+            };
+
+            match res
+            {
+                Some(res) => best = Some(res),
+                None => break,
+            }
+            if start.elapsed() * GROWTH >= time
+            {
+                break;
+            }
+        }
+
+        // Even the first depth did not finish, it is quick enough to not matter
+        best.unwrap_or_else(|| self.search(1, board))
+    }
+
+    fn option_line(&self, name: &str) -> Result<String, Error>
+    {
+        match name
+        {
+            "Engine" =>
+            {
+                let value = match self.engine
+                {
+                    EngineKind::AlphaBeta => "AlphaBeta",
+                    EngineKind::Mcts => "Mcts",
+                };
+                Ok(format!("Engine;enum;{value};AlphaBeta;AlphaBeta;Mcts"))
+            },
+            "Depth" => Ok(format!("Depth;int;{};3;1;{MAX_DEPTH}", self.depth)),
+            "Iterations" => Ok(format!("Iterations;int;{};2000;1;1000000", self.iterations)),
+            _ => err(&format!("unknown option '{name}'")),
+        }
+    }
+
+    /*
+     * options                 : list every option
+     * options get <name>      : one option
+     * options set <name> <v>  : set an option
+     */
+    fn options(&mut self, args: &str) -> SessionResult
+    {
+        let mut parts = args.split_whitespace();
+
+        match (parts.next(), parts.next(), parts.next())
+        {
+            (None, ..) => Ok(["Engine", "Depth", "Iterations"]
+                .into_iter()
+                .map(|name| self.option_line(name))
+                .collect::<Result<Vec<_>, _>>()?
+                .join("\n")),
+            (Some("get"), Some(name), None) => self.option_line(name),
+            (Some("set"), Some(name), Some(value)) =>
+            {
+                match (name, value)
+                {
+                    ("Engine", "AlphaBeta") => self.engine = EngineKind::AlphaBeta,
+                    ("Engine", "Mcts") => self.engine = EngineKind::Mcts,
+                    ("Depth", v) => match v.parse()
+                    {
+                        Ok(v) if (1..=MAX_DEPTH).contains(&v) => self.depth = v,
+                        _ => return err("invalid value"),
+                    },
+                    ("Iterations", v) => match v.parse()
+                    {
+                        Ok(v) if v > 0 => self.iterations = v,
+                        _ => return err("invalid value"),
+                    },
+                    _ => return err("invalid option or value"),
+                }
+                self.option_line(name)
+            },
+            _ => err("expected 'options', 'options get <name>' or 'options set <name> <value>'"),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    fn test_play_and_undo()
+    {
+        let mut session = Session::default();
+
+        assert_eq!(session.handle("newgame Base").unwrap(), "Base;NotStarted;White[1]");
+        assert_eq!(session.handle("play wS1").unwrap(), "Base;InProgress;Black[1];wS1");
+        assert_eq!(
+            session.handle("play bG1 -wS1").unwrap(),
+            "Base;InProgress;White[2];wS1;bG1 -wS1"
+        );
+        assert!(matches!(session.handle("play wQ -bG1"), Err(Error::InvalidMove(_))));
+        assert!(matches!(session.handle("fly wQ"), Err(Error::Err(_))));
+
+        assert_eq!(session.handle("undo").unwrap(), "Base;InProgress;Black[1];wS1");
+        assert_eq!(session.handle("validmoves").unwrap().split(';').count(), 5 * 6);
+    }
+
+    #[test]
+    fn test_best_move()
+    {
+        let mut session = Session::default();
+        session
+            .handle("newgame Base;InProgress;White[4];wQ;bQ wQ-;wA1 -wQ;bA1 bQ-;wA2 /wQ;bA2 bQ\\")
+            .unwrap();
+
+        for engine in ["AlphaBeta", "Mcts"]
+        {
+            session.handle(&format!("options set Engine {engine}")).unwrap();
+            session.handle("options set Iterations 200").unwrap();
+
+            let s = session.handle("bestmove depth 1").unwrap();
+            let mut copy = Session {
+                pos: session.pos.clone(),
+                ..Session::default()
+            };
+            assert!(copy.handle(&format!("play {s}")).is_ok(), "{engine}: {s}");
+        }
+
+        assert!(session.handle("bestmove time 1s").is_err());
+
+        // A search running past the time is given up on
+        for engine in ["AlphaBeta", "Mcts"]
+        {
+            session.handle(&format!("options set Engine {engine}")).unwrap();
+            let start = Instant::now();
+            assert!(session.handle("bestmove time 00:00:01").is_ok());
+            assert!(start.elapsed() < Duration::from_secs(2), "{engine}");
+        }
+    }
+
+    #[test]
+    fn test_info()
+    {
+        let info = Session::default().handle("info").unwrap();
+        let lines = info.split('\n').collect::<Vec<_>>();
+        assert_eq!(lines, [format!("id uhp v{}", env!("CARGO_PKG_VERSION")).as_str(), ""]);
+    }
+
+    #[test]
+    fn test_options()
+    {
+        let mut session = Session::default();
+
+        assert_eq!(session.handle("options").unwrap().lines().count(), 3);
+        assert_eq!(session.handle("options set Depth 2").unwrap(), "Depth;int;2;3;1;8");
+        assert!(session.handle("options set Depth 0").is_err());
+        assert!(session.handle("options get Colour").is_err());
+    }
+}